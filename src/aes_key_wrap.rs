@@ -0,0 +1,157 @@
+//! AES Key Wrap
+//!
+//! Defined in [RFC3394](https://tools.ietf.org/html/rfc3394), and used by the `A128KW`, `A192KW` and `A256KW`
+//! key management algorithms.
+use aes::{Aes128, Aes192, Aes256, Block};
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::cipher::consts::U16;
+use ring::constant_time::verify_slices_are_equal;
+
+use errors::Error;
+
+/// The default initial value from [RFC3394#2.2.3.1](https://tools.ietf.org/html/rfc3394#section-2.2.3.1)
+const DEFAULT_IV: [u8; 8] = [0xA6; 8];
+
+/// Wrap `key` with the key encryption key `kek`, which must be 16, 24 or 32 bytes long.
+/// The key to wrap must be a multiple of 8 bytes, and at least 16 bytes long.
+pub fn wrap(kek: &[u8], key: &[u8]) -> Result<Vec<u8>, Error> {
+    if key.len() < 16 || key.len() % 8 != 0 {
+        Err(format!("The key to wrap must be a multiple of 8 bytes and at least 16 bytes long, got {} bytes",
+                    key.len()))?;
+    }
+
+    match kek.len() {
+        16 => Ok(wrap_with(&Aes128::new_from_slice(kek).map_err(|_| invalid_kek_length(kek))?, key)),
+        24 => Ok(wrap_with(&Aes192::new_from_slice(kek).map_err(|_| invalid_kek_length(kek))?, key)),
+        32 => Ok(wrap_with(&Aes256::new_from_slice(kek).map_err(|_| invalid_kek_length(kek))?, key)),
+        _ => Err(invalid_kek_length(kek)),
+    }
+}
+
+/// Unwrap `wrapped` with the key encryption key `kek`, which must be 16, 24 or 32 bytes long.
+/// Returns an error if the integrity check of the wrapped key fails.
+pub fn unwrap(kek: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, Error> {
+    if wrapped.len() < 24 || wrapped.len() % 8 != 0 {
+        Err(format!("The wrapped key must be a multiple of 8 bytes and at least 24 bytes long, got {} bytes",
+                    wrapped.len()))?;
+    }
+
+    match kek.len() {
+        16 => unwrap_with(&Aes128::new_from_slice(kek).map_err(|_| invalid_kek_length(kek))?, wrapped),
+        24 => unwrap_with(&Aes192::new_from_slice(kek).map_err(|_| invalid_kek_length(kek))?, wrapped),
+        32 => unwrap_with(&Aes256::new_from_slice(kek).map_err(|_| invalid_kek_length(kek))?, wrapped),
+        _ => Err(invalid_kek_length(kek)),
+    }
+}
+
+fn invalid_kek_length(kek: &[u8]) -> Error {
+    Error::GenericError(format!("Invalid AES key wrap key length. Expected 16, 24 or 32 bytes, got {}",
+                                kek.len()))
+}
+
+/// XOR the big-endian counter `t` into the integrity check register
+fn xor_counter(register: &mut [u8], t: u64) {
+    for (byte, counter) in register.iter_mut().zip(&t.to_be_bytes()) {
+        *byte ^= *counter;
+    }
+}
+
+/// The wrapping process of [RFC3394#2.2.1](https://tools.ietf.org/html/rfc3394#section-2.2.1)
+fn wrap_with<C: BlockEncrypt<BlockSize = U16>>(cipher: &C, key: &[u8]) -> Vec<u8> {
+    let n = key.len() / 8;
+    let mut register = DEFAULT_IV;
+    let mut blocks: Vec<u8> = key.to_vec();
+    let mut block = Block::default();
+
+    for j in 0..6 {
+        for i in 0..n {
+            block[..8].copy_from_slice(&register);
+            block[8..].copy_from_slice(&blocks[i * 8..(i + 1) * 8]);
+            cipher.encrypt_block(&mut block);
+
+            register.copy_from_slice(&block[..8]);
+            xor_counter(&mut register, (n * j + i + 1) as u64);
+            blocks[i * 8..(i + 1) * 8].copy_from_slice(&block[8..]);
+        }
+    }
+
+    let mut wrapped = register.to_vec();
+    wrapped.append(&mut blocks);
+    wrapped
+}
+
+/// The unwrapping process of [RFC3394#2.2.2](https://tools.ietf.org/html/rfc3394#section-2.2.2)
+fn unwrap_with<C: BlockDecrypt<BlockSize = U16>>(cipher: &C, wrapped: &[u8]) -> Result<Vec<u8>, Error> {
+    let n = wrapped.len() / 8 - 1;
+    let mut register = [0; 8];
+    register.copy_from_slice(&wrapped[..8]);
+    let mut blocks: Vec<u8> = wrapped[8..].to_vec();
+    let mut block = Block::default();
+
+    for j in (0..6).rev() {
+        for i in (0..n).rev() {
+            xor_counter(&mut register, (n * j + i + 1) as u64);
+            block[..8].copy_from_slice(&register);
+            block[8..].copy_from_slice(&blocks[i * 8..(i + 1) * 8]);
+            cipher.decrypt_block(&mut block);
+
+            register.copy_from_slice(&block[..8]);
+            blocks[i * 8..(i + 1) * 8].copy_from_slice(&block[8..]);
+        }
+    }
+
+    verify_slices_are_equal(&register, &DEFAULT_IV)?;
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test::hex;
+
+    /// Test vectors from [RFC3394#4](https://tools.ietf.org/html/rfc3394#section-4)
+    #[test]
+    fn rfc3394_test_vectors() {
+        let vectors = [("000102030405060708090A0B0C0D0E0F",
+                        "00112233445566778899AABBCCDDEEFF",
+                        "1FA68B0A8112B447 AEF34BD8FB5A7B82 9D3E862371D2CFE5"),
+                       ("000102030405060708090A0B0C0D0E0F1011121314151617",
+                        "00112233445566778899AABBCCDDEEFF",
+                        "96778B25AE6CA435 F92B5B97C050AED2 468AB8A17AD84E5D"),
+                       ("000102030405060708090A0B0C0D0E0F101112131415161718191A1B1C1D1E1F",
+                        "00112233445566778899AABBCCDDEEFF",
+                        "64E8C3F9CE0F5BA2 63E9777905818A2A 93C8191E7D6E8AE7"),
+                       ("000102030405060708090A0B0C0D0E0F1011121314151617",
+                        "00112233445566778899AABBCCDDEEFF0001020304050607",
+                        "031D33264E15D332 68F24EC260743EDC E1C6C7DDEE725A93 6BA814915C6762D2"),
+                       ("000102030405060708090A0B0C0D0E0F101112131415161718191A1B1C1D1E1F",
+                        "00112233445566778899AABBCCDDEEFF0001020304050607",
+                        "A8F9BC1612C68B3F F6E6F4FBE30E71E4 769C8B80A32CB895 8CD5D17D6B254DA1"),
+                       ("000102030405060708090A0B0C0D0E0F101112131415161718191A1B1C1D1E1F",
+                        "00112233445566778899AABBCCDDEEFF000102030405060708090A0B0C0D0E0F",
+                        "28C9F404C4B810F4 CBCCB35CFB87F826 3F5786E2D80ED326 CBC7F0E71A99F43B FB988B9B7A02DD21")];
+
+        for &(kek, key, wrapped) in &vectors {
+            let (kek, key, wrapped) = (hex(kek), hex(key), hex(wrapped));
+            assert_eq!(not_err!(wrap(&kek, &key)), wrapped);
+            assert_eq!(not_err!(unwrap(&kek, &wrapped)), key);
+        }
+    }
+
+    #[test]
+    fn modified_wrapped_key_is_rejected() {
+        let kek = hex("000102030405060708090A0B0C0D0E0F");
+        let mut wrapped = hex("1FA68B0A8112B447 AEF34BD8FB5A7B82 9D3E862371D2CFE5");
+        wrapped[12] ^= 0x01;
+        assert_matches!(unwrap(&kek, &wrapped), Err(Error::UnspecifiedCryptographicError));
+    }
+
+    #[test]
+    fn invalid_lengths_are_rejected() {
+        assert!(wrap(&[0; 16], &[0; 8]).is_err());
+        assert!(wrap(&[0; 16], &[0; 20]).is_err());
+        assert!(wrap(&[0; 20], &[0; 16]).is_err());
+        assert!(unwrap(&[0; 16], &[0; 16]).is_err());
+        assert!(unwrap(&[0; 16], &[0; 25]).is_err());
+    }
+}
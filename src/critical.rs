@@ -0,0 +1,80 @@
+//! Validation of the `crit` (critical) header parameter
+//!
+//! Defined in [RFC7515#4.1.11](https://tools.ietf.org/html/rfc7515#section-4.1.11) for JWS, and additionally
+//! [RFC7516#4.1.13](https://tools.ietf.org/html/rfc7516#section-4.1.13) for JWE.
+use serde_json::{Map, Value};
+
+use errors::{Error, ValidationError};
+
+fn invalid_critical(message: String) -> Error {
+    Error::ValidationError(ValidationError::InvalidCriticalHeader(message))
+}
+
+/// Validate the `crit` header parameter of a header.
+///
+/// The list must not be empty, and every name in it must be present in the header, be one of the `understood`
+/// extensions, and not be one of the `registered` header parameters.
+pub fn validate(header: &Map<String, Value>, registered: &[&str], understood: &[&str]) -> Result<(), Error> {
+    let not_strings = || invalid_critical("crit must be an array of strings".to_string());
+    let critical = match header.get("crit") {
+        None => return Ok(()),
+        Some(critical) => critical.as_array().ok_or_else(&not_strings)?,
+    };
+
+    if critical.is_empty() {
+        Err(invalid_critical("crit must not be empty".to_string()))?;
+    }
+
+    for name in critical {
+        let name = name.as_str().ok_or_else(&not_strings)?;
+        if registered.contains(&name) {
+            Err(invalid_critical(format!("{} is a registered header parameter and cannot be critical", name)))?;
+        }
+        if !header.contains_key(name) {
+            Err(invalid_critical(format!("{} is critical but is missing", name)))?;
+        }
+        if !understood.contains(&name) {
+            Err(invalid_critical(format!("{} is critical but is not understood", name)))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use errors::{Error, ValidationError};
+    use super::*;
+
+    fn header(json: &str) -> Map<String, Value> {
+        not_err!(serde_json::from_str(json))
+    }
+
+    fn validate_header(json: &str) -> Result<(), Error> {
+        validate(&header(json), &["alg", "kid"], &["b64", "exp"])
+    }
+
+    #[test]
+    fn understood_critical_headers_are_accepted() {
+        not_err!(validate_header(r#"{"alg":"HS256"}"#));
+        not_err!(validate_header(r#"{"alg":"HS256","b64":false,"crit":["b64"]}"#));
+        not_err!(validate_header(r#"{"alg":"HS256","exp":1363284000,"crit":["exp"]}"#));
+    }
+
+    #[test]
+    fn invalid_critical_headers_are_rejected() {
+        let invalid = [r#"{"alg":"HS256","crit":[]}"#,
+                       r#"{"alg":"HS256","crit":"b64"}"#,
+                       r#"{"alg":"HS256","crit":[1]}"#,
+                       r#"{"alg":"HS256","kid":"key","crit":["kid"]}"#,
+                       r#"{"alg":"HS256","crit":["exp"]}"#,
+                       r#"{"alg":"HS256","http://example.invalid/UNDEFINED":true,
+                           "crit":["http://example.invalid/UNDEFINED"]}"#];
+
+        for json in &invalid {
+            let result = validate_header(json);
+            assert_matches!(result, Err(Error::ValidationError(ValidationError::InvalidCriticalHeader(_))));
+        }
+    }
+}
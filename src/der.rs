@@ -0,0 +1,133 @@
+//! Minimal ASN.1 DER encoding and decoding
+//!
+//! Only the small subset of DER needed to convert between the JOSE representations of keys and
+//! signatures and the DER representations expected by `ring` is implemented here.
+use errors::Error;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_SEQUENCE: u8 = 0x30;
+
+fn invalid_der() -> Error {
+    Error::GenericError("Invalid DER encoding".to_string())
+}
+
+fn encode_tlv(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut output = vec![tag];
+    let length = contents.len();
+    if length < 0x80 {
+        output.push(length as u8);
+    } else {
+        let length_bytes: Vec<u8> = (0..8)
+            .rev()
+            .map(|i| (length >> (i * 8)) as u8)
+            .skip_while(|byte| *byte == 0)
+            .collect();
+        output.push(0x80 | length_bytes.len() as u8);
+        output.extend_from_slice(&length_bytes);
+    }
+    output.extend_from_slice(contents);
+    output
+}
+
+/// Encode big-endian unsigned bytes as a DER `INTEGER`
+pub fn integer(unsigned: &[u8]) -> Vec<u8> {
+    let stripped: Vec<u8> = unsigned.iter().cloned().skip_while(|byte| *byte == 0).collect();
+    let mut contents = vec![];
+    if stripped.is_empty() || stripped[0] & 0x80 != 0 {
+        contents.push(0);
+    }
+    contents.extend_from_slice(&stripped);
+    encode_tlv(TAG_INTEGER, &contents)
+}
+
+/// Wrap already encoded DER values in a `SEQUENCE`
+pub fn sequence(values: &[Vec<u8>]) -> Vec<u8> {
+    let contents: Vec<u8> = values.iter().flat_map(|value| value.iter().cloned()).collect();
+    encode_tlv(TAG_SEQUENCE, &contents)
+}
+
+/// Read a single value with the expected tag, returning its contents and the remaining input
+fn read_tlv(tag: u8, input: &[u8]) -> Result<(&[u8], &[u8]), Error> {
+    if input.len() < 2 || input[0] != tag {
+        Err(invalid_der())?;
+    }
+
+    let (length, header_length) = if input[1] < 0x80 {
+        (input[1] as usize, 2)
+    } else {
+        let count = (input[1] & 0x7f) as usize;
+        if count == 0 || count > 4 || input.len() < 2 + count {
+            Err(invalid_der())?;
+        }
+        let length = input[2..2 + count].iter().fold(0usize, |acc, byte| (acc << 8) | *byte as usize);
+        (length, 2 + count)
+    };
+
+    if input.len() - header_length < length {
+        Err(invalid_der())?;
+    }
+    let (contents, rest) = input[header_length..].split_at(length);
+    Ok((contents, rest))
+}
+
+/// Read the contents of a `SEQUENCE`. The sequence must make up the entire input.
+pub fn read_sequence(input: &[u8]) -> Result<&[u8], Error> {
+    let (contents, rest) = read_tlv(TAG_SEQUENCE, input)?;
+    if !rest.is_empty() {
+        Err(invalid_der())?;
+    }
+    Ok(contents)
+}
+
+/// Read a non-negative `INTEGER`, returning its big-endian bytes without leading zeroes,
+/// and the remaining input
+pub fn read_unsigned_integer(input: &[u8]) -> Result<(&[u8], &[u8]), Error> {
+    let (contents, rest) = read_tlv(TAG_INTEGER, input)?;
+    if contents.is_empty() || contents[0] & 0x80 != 0 {
+        Err(invalid_der())?;
+    }
+    let leading_zeroes = contents.iter().take_while(|byte| **byte == 0).count();
+    Ok((&contents[leading_zeroes..], rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_is_encoded_minimally() {
+        assert_eq!(integer(&[0x00, 0x00, 0x01]), vec![0x02, 0x01, 0x01]);
+        assert_eq!(integer(&[0x80]), vec![0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(integer(&[]), vec![0x02, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn long_form_length_is_encoded() {
+        let encoded = integer(&[0x01; 200]);
+        assert_eq!(&encoded[0..3], &[0x02, 0x81, 200]);
+        assert_eq!(encoded.len(), 203);
+    }
+
+    #[test]
+    fn sequence_round_trip() {
+        let encoded = sequence(&[integer(&[0x01; 200]), integer(&[0xff, 0x01])]);
+
+        let contents = not_err!(read_sequence(&encoded));
+        let (first, rest) = not_err!(read_unsigned_integer(contents));
+        let (second, rest) = not_err!(read_unsigned_integer(rest));
+        assert_eq!(first, &[0x01; 200][..]);
+        assert_eq!(second, &[0xff, 0x01]);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn truncated_input_is_rejected() {
+        let encoded = sequence(&[integer(&[0x01, 0x02, 0x03])]);
+        assert!(read_sequence(&encoded[..encoded.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn negative_integer_is_rejected() {
+        assert!(read_unsigned_integer(&[0x02, 0x01, 0x80]).is_err());
+    }
+}
@@ -1,7 +1,9 @@
 //! Errors returned will be converted to one of the structs in this module.
 use std::{str, string, fmt, error, io};
 use data_encoding;
+use p256;
 use ring;
+use rsa;
 use serde_json;
 use url::ParseError;
 
@@ -37,6 +39,11 @@ pub enum Error {
     UnspecifiedCryptographicError,
     /// An unsupported or invalid operation
     UnsupportedOperation,
+    /// The decompressed payload is larger than the configured limit
+    DecompressionLimitExceeded {
+        /// Maximum size of the decompressed payload, in bytes
+        limit: usize,
+    },
 }
 
 #[derive(Debug)]
@@ -60,6 +67,15 @@ pub enum ValidationError {
         /// Actual number of parts
         actual: usize,
     },
+    /// No key could be found for the key ID (`kid`) of the token, if any
+    KeyNotFound(Option<String>),
+    /// More than one key could be used for the key ID (`kid`) of the token, if any
+    AmbiguousKey(Option<String>),
+    /// The key found for the key ID (`kid`) of the token, if any, is not allowed to be used with the
+    /// algorithm of the token
+    KeyNotAllowed(Option<String>),
+    /// The critical header parameters (`crit`) of the token are invalid or not understood
+    InvalidCriticalHeader(String),
 }
 
 macro_rules! impl_from_error {
@@ -85,6 +101,18 @@ impl From<ring::error::Unspecified> for Error {
     }
 }
 
+impl From<p256::ecdsa::Error> for Error {
+    fn from(_: p256::ecdsa::Error) -> Self {
+        Error::UnspecifiedCryptographicError
+    }
+}
+
+impl From<rsa::Error> for Error {
+    fn from(_: rsa::Error) -> Self {
+        Error::UnspecifiedCryptographicError
+    }
+}
+
 impl From<string::FromUtf8Error> for Error {
     fn from(e: string::FromUtf8Error) -> Self {
         Error::Utf8(e.utf8_error())
@@ -106,6 +134,7 @@ impl error::Error for Error {
             WrongKeyType { .. } => "The wrong type of key was provided for the cryptographic operation",
             UnspecifiedCryptographicError => "An Unspecified Cryptographic Error",
             UnsupportedOperation => "This operation is not supported",
+            DecompressionLimitExceeded { .. } => "The decompressed payload is larger than the configured limit",
         }
     }
 
@@ -147,6 +176,9 @@ impl fmt::Display for Error {
             }
             UnspecifiedCryptographicError => write!(f, "{}", error::Error::description(self)),
             UnsupportedOperation => write!(f, "{}", error::Error::description(self)),
+            DecompressionLimitExceeded { limit } => {
+                write!(f, "The decompressed payload is larger than the limit of {} bytes", limit)
+            }
         }
     }
 }
@@ -162,6 +194,10 @@ impl error::Error for ValidationError {
             PartsLengthError { .. } => "Unexpected number of parts in compact JSON representation",
             MissingRequired(_) => "Missing required field",
             TemporalError(_) => "Temporal validation failed",
+            KeyNotFound(_) => "No key could be found for the token",
+            AmbiguousKey(_) => "More than one key could be used for the token",
+            KeyNotAllowed(_) => "The key is not allowed to be used with the algorithm of the token",
+            InvalidCriticalHeader(_) => "Invalid critical header parameters",
         }
     }
 
@@ -177,13 +213,17 @@ impl fmt::Display for ValidationError {
 
         match *self {
             MissingRequired(ref field) => write!(f, "{} is required but is missing", field),
-            TemporalError(ref err) => write!(f, "{}: {}", self.description(), err),
+            TemporalError(ref err) |
+            InvalidCriticalHeader(ref err) => write!(f, "{}: {}", self.description(), err),
             PartsLengthError { expected, actual } => {
                 write!(f,
                        "Expected {} parts in Compact JSON representation but got {}",
                        expected,
                        actual)
             }
+            KeyNotFound(Some(ref key_id)) |
+            AmbiguousKey(Some(ref key_id)) |
+            KeyNotAllowed(Some(ref key_id)) => write!(f, "{} (key ID \"{}\")", self.description(), key_id),
             _ => write!(f, "{}", error::Error::description(self)),
         }
 
@@ -3,21 +3,53 @@
 //! Code for implementing JWA according to [RFC 7518](https://tools.ietf.org/html/rfc7518).
 //!
 //! Typically, you will not use these directly, but as part of a JWS or JWE.
+use aes::{Aes128, Aes192, Aes256};
+#[cfg(feature = "aes192")]
+use aes_gcm::{AeadInPlace, AesGcm, Nonce, Tag};
+#[cfg(feature = "aes192")]
+use aes_gcm::aead::consts::U12;
+use cbc::cipher::{BlockCipher, BlockDecryptMut, BlockEncryptMut, KeyInit, KeyIvInit};
+use cbc::cipher::block_padding::Pkcs7;
+use num::BigUint;
+use p256;
+use p256::elliptic_curve::{AffinePoint, CurveArithmetic, FieldBytesSize, PublicKey, SecretKey};
+use p256::elliptic_curve::ecdh::{self, EphemeralSecret};
+use p256::elliptic_curve::sec1::{FromEncodedPoint, ModulusSize, ToEncodedPoint};
+use p384;
+use pbkdf2;
+use rand::rngs::OsRng;
 use ring::{aead, digest, hmac, rand, signature};
 use ring::constant_time::verify_slices_are_equal;
 use ring::rand::SystemRandom;
+use rsa::{self, Oaep, Pkcs1v15Encrypt, RsaPublicKey};
+#[cfg(feature = "rsa-decryption")]
+use rsa::RsaPrivateKey;
+#[cfg(feature = "rsa-decryption")]
+use rsa::traits::PublicKeyParts;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
+use serde_json;
+use sha1::Sha1;
+use sha2::{Sha256, Sha384, Sha512};
+#[cfg(feature = "rsa-decryption")]
+use subtle::{ConditionallySelectable, ConstantTimeEq};
 use untrusted;
+use x25519_dalek;
 
+use aes_key_wrap;
+use der;
 use errors::Error;
 use jwk;
-use jws::Secret;
+use jws::{ECDSAKeyPair, Secret};
 
 /// AES GCM Tag Size, in bytes
 const TAG_SIZE: usize = 128 / 8;
 /// AES GCM Nonce length, in bytes
 const NONCE_LENGTH: usize = 96 / 8;
+/// AES CBC Initialization Vector length, in bytes
+const CBC_IV_LENGTH: usize = 128 / 8;
+/// Minimum length of the PBES2 salt input (`p2s`), in bytes
+const PBES2_MIN_SALT_INPUT_LENGTH: usize = 64 / 8;
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 /// Algorithms described by [RFC 7518](https://tools.ietf.org/html/rfc7518).
@@ -72,26 +104,29 @@ pub enum SignatureAlgorithm {
     /// RSASSA-PSS using SHA-512 and MGF1 with SHA-512
     /// The size of the salt value is the same size as the hash function output.
     PS512,
+    /// Edwards-curve Digital Signature Algorithm, as defined in [RFC 8037](https://tools.ietf.org/html/rfc8037).
+    /// Only the Ed25519 curve is supported.
+    EdDSA,
 }
 
 /// Algorithms for key management as defined in [RFC7518#4](https://tools.ietf.org/html/rfc7518#section-4)
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub enum KeyManagementAlgorithm {
-    /// RSAES-PKCS1-v1_5
+    /// RSAES-PKCS1-v1_5. This algorithm is deprecated, and JWEs only use it when `allow_rsa1_5` is set in the
+    /// encryption or decryption options. Decryption requires the `rsa-decryption` feature.
     RSA1_5,
-    /// RSAES OAEP using default parameters
+    /// RSAES OAEP using default parameters. Decryption requires the `rsa-decryption` feature.
     #[serde(rename = "RSA-OAEP")]
     RSA_OAEP,
-    /// RSAES OAEP using SHA-256 and MGF1 with SHA-256
+    /// RSAES OAEP using SHA-256 and MGF1 with SHA-256. Decryption requires the `rsa-decryption` feature.
     #[serde(rename = "RSA-OAEP-256")]
     RSA_OAEP_256,
-    /// AES Key Wrap using 128-bit key. _Unsupported_
+    /// AES Key Wrap using 128-bit key
     A128KW,
-    /// AES Key Wrap using 192-bit key. _Unsupported_.
-    /// This is [not supported](https://github.com/briansmith/ring/issues/112) by `ring`.
+    /// AES Key Wrap using 192-bit key
     A192KW,
-    /// AES Key Wrap using 256-bit key. _Unsupported_
+    /// AES Key Wrap using 256-bit key
     A256KW,
     /// Direct use of a shared symmetric key
     #[serde(rename = "dir")]
@@ -207,6 +242,7 @@ impl SignatureAlgorithm {
             HS256 | HS384 | HS512 => Self::sign_hmac(data, secret, self),
             RS256 | RS384 | RS512 | PS256 | PS384 | PS512 => Self::sign_rsa(data, secret, self),
             ES256 | ES384 | ES512 => Self::sign_ecdsa(data, secret, self),
+            EdDSA => Self::sign_eddsa(data, secret),
         }
     }
 
@@ -217,7 +253,7 @@ impl SignatureAlgorithm {
         match *self {
             None => Self::verify_none(expected_signature, secret),
             HS256 | HS384 | HS512 => Self::verify_hmac(expected_signature, data, secret, self),
-            RS256 | RS384 | RS512 | PS256 | PS384 | PS512 | ES256 | ES384 | ES512 => {
+            RS256 | RS384 | RS512 | PS256 | PS384 | PS512 | ES256 | ES384 | ES512 | EdDSA => {
                 Self::verify_public_key(expected_signature, data, secret, self)
             }
         }
@@ -270,13 +306,35 @@ impl SignatureAlgorithm {
         Ok(signature)
     }
 
-    fn sign_ecdsa(_data: &[u8], _secret: &Secret, _algorithm: &SignatureAlgorithm) -> Result<Vec<u8>, Error> {
-        // Not supported at the moment by ring
-        // Tracking issues:
-        //  - P-256: https://github.com/briansmith/ring/issues/207
-        //  - P-384: https://github.com/briansmith/ring/issues/209
-        //  - P-521: Probably never: https://github.com/briansmith/ring/issues/268
-        Err(Error::UnsupportedOperation)
+    fn sign_ecdsa(data: &[u8], secret: &Secret, algorithm: &SignatureAlgorithm) -> Result<Vec<u8>, Error> {
+        use p256::ecdsa::signature::Signer;
+
+        let key_pair = match *secret {
+            Secret::ECDSAKeyPair(ref key_pair) => key_pair,
+            _ => Err("Invalid secret type. An ECDSAKeyPair is required".to_string())?,
+        };
+
+        // P-521 is not supported: https://github.com/briansmith/ring/issues/268
+        match (algorithm, &**key_pair) {
+            (&SignatureAlgorithm::ES256, &ECDSAKeyPair::P256(ref key)) => {
+                let signature: p256::ecdsa::Signature = key.try_sign(data)?;
+                Ok(signature.to_bytes().to_vec())
+            }
+            (&SignatureAlgorithm::ES384, &ECDSAKeyPair::P384(ref key)) => {
+                let signature: p384::ecdsa::Signature = key.try_sign(data)?;
+                Ok(signature.to_bytes().to_vec())
+            }
+            (&SignatureAlgorithm::ES512, _) => Err(Error::UnsupportedOperation),
+            _ => Err("Invalid secret type. The ECDSAKeyPair curve does not match the algorithm".to_string())?,
+        }
+    }
+
+    fn sign_eddsa(data: &[u8], secret: &Secret) -> Result<Vec<u8>, Error> {
+        let key_pair = match *secret {
+            Secret::Ed25519KeyPair(ref key_pair) => key_pair,
+            _ => Err("Invalid secret type. An Ed25519KeyPair is required".to_string())?,
+        };
+        Ok(key_pair.sign(data).as_slice().to_vec())
     }
 
     fn verify_none(expected_signature: &[u8], secret: &Secret) -> Result<bool, Error> {
@@ -314,9 +372,10 @@ impl SignatureAlgorithm {
             SignatureAlgorithm::PS256 => &signature::RSA_PSS_2048_8192_SHA256,
             SignatureAlgorithm::PS384 => &signature::RSA_PSS_2048_8192_SHA384,
             SignatureAlgorithm::PS512 => &signature::RSA_PSS_2048_8192_SHA512,
-            SignatureAlgorithm::ES256 => &signature::ECDSA_P256_SHA256_ASN1,
-            SignatureAlgorithm::ES384 => &signature::ECDSA_P384_SHA384_ASN1,
+            SignatureAlgorithm::ES256 |
+            SignatureAlgorithm::ES384 => return Self::verify_ecdsa(expected_signature, data, public_key, algorithm),
             SignatureAlgorithm::ES512 => Err(Error::UnsupportedOperation)?,
+            SignatureAlgorithm::EdDSA => &signature::ED25519,
             _ => unreachable!("Should not happen"),
         };
 
@@ -330,6 +389,35 @@ impl SignatureAlgorithm {
             Err(_) => Ok(false),
         }
     }
+
+    /// Verify a signature in the fixed-length `R || S` format of
+    /// [RFC7518#3.4](https://tools.ietf.org/html/rfc7518#section-3.4) with an uncompressed SEC1 public key
+    fn verify_ecdsa(expected_signature: &[u8],
+                    data: &[u8],
+                    public_key: &[u8],
+                    algorithm: &SignatureAlgorithm)
+                    -> Result<bool, Error> {
+        use p256::ecdsa::signature::Verifier;
+
+        let verified = match *algorithm {
+            SignatureAlgorithm::ES256 => {
+                match (p256::ecdsa::VerifyingKey::from_sec1_bytes(public_key),
+                       p256::ecdsa::Signature::from_slice(expected_signature)) {
+                    (Ok(key), Ok(signature)) => key.verify(data, &signature).is_ok(),
+                    _ => false,
+                }
+            }
+            SignatureAlgorithm::ES384 => {
+                match (p384::ecdsa::VerifyingKey::from_sec1_bytes(public_key),
+                       p384::ecdsa::Signature::from_slice(expected_signature)) {
+                    (Ok(key), Ok(signature)) => key.verify(data, &signature).is_ok(),
+                    _ => false,
+                }
+            }
+            _ => unreachable!("Should not happen"),
+        };
+        Ok(verified)
+    }
 }
 
 impl KeyManagementAlgorithm {
@@ -362,7 +450,12 @@ impl KeyManagementAlgorithm {
 
         match *self {
             DirectSymmetricKey => self.cek_direct(key),
-            A128GCMKW | A256GCMKW => self.cek_aes_gcm(content_alg),
+            A128KW | A192KW | A256KW | A128GCMKW | A256GCMKW => self.cek_random(content_alg),
+            #[cfg(feature = "aes192")]
+            A192GCMKW => self.cek_random(content_alg),
+            RSA1_5 | RSA_OAEP | RSA_OAEP_256 => self.cek_random(content_alg),
+            ECDH_ES_A128KW | ECDH_ES_A192KW | ECDH_ES_A256KW => self.cek_random(content_alg),
+            PBES2_HS256_A128KW | PBES2_HS384_A192KW | PBES2_HS512_A256KW => self.cek_random(content_alg),
             _ => Err(Error::UnsupportedOperation),
         }
     }
@@ -376,23 +469,15 @@ impl KeyManagementAlgorithm {
         }
     }
 
-    fn cek_aes_gcm(&self, content_alg: ContentEncryptionAlgorithm) -> Result<jwk::JWK<::Empty>, Error> {
+    fn cek_random(&self, content_alg: ContentEncryptionAlgorithm) -> Result<jwk::JWK<::Empty>, Error> {
         let key = content_alg.generate_key()?;
-        Ok(jwk::JWK {
-               algorithm: jwk::AlgorithmParameters::OctectKey {
-                   value: key,
-                   key_type: Default::default(),
-               },
-               common: jwk::CommonParameters {
-                   public_key_use: Some(jwk::PublicKeyUse::Encryption),
-                   algorithm: Some(Algorithm::ContentEncryption(content_alg)),
-                   ..Default::default()
-               },
-               additional: Default::default(),
-           })
+        Ok(content_encryption_key(key, content_alg))
     }
 
-    /// Encrypt or wrap a key with the provided algorithm
+    /// Encrypt or wrap a key with the provided algorithm.
+    /// For the key agreement with key wrapping algorithms, `key` is the key returned by
+    /// [`agree_key_for_encryption`](#method.agree_key_for_encryption).
+    /// For the PBES2 algorithms, `key` is the key returned by [`pbes2_derive_key`](#method.pbes2_derive_key).
     pub fn encrypt<T: Serialize + DeserializeOwned>(&self,
                                                     payload: &[u8],
                                                     key: &jwk::JWK<T>)
@@ -400,13 +485,21 @@ impl KeyManagementAlgorithm {
         use self::KeyManagementAlgorithm::*;
 
         match *self {
+            A128KW | A192KW | A256KW => self.aes_key_wrap_encrypt(payload, key),
+            ECDH_ES_A128KW | ECDH_ES_A192KW | ECDH_ES_A256KW => self.aes_key_wrap_encrypt(payload, key),
+            PBES2_HS256_A128KW | PBES2_HS384_A192KW | PBES2_HS512_A256KW => self.aes_key_wrap_encrypt(payload, key),
             A128GCMKW | A192GCMKW | A256GCMKW => self.aes_gcm_encrypt(payload, key),
+            RSA1_5 => self.rsa1_5_encrypt(payload, key),
+            RSA_OAEP | RSA_OAEP_256 => self.rsa_oaep_encrypt(payload, key),
             DirectSymmetricKey => Ok(Default::default()),
             _ => Err(Error::UnsupportedOperation),
         }
     }
 
-    /// Decrypt or unwrap a CEK with the provided algorithm
+    /// Decrypt or unwrap a CEK with the provided algorithm.
+    /// For the key agreement with key wrapping algorithms, `key` is the key returned by
+    /// [`agree_key_for_decryption`](#method.agree_key_for_decryption).
+    /// For the PBES2 algorithms, `key` is the key returned by [`pbes2_derive_key`](#method.pbes2_derive_key).
     pub fn decrypt<T: Serialize + DeserializeOwned>(&self,
                                                     encrypted: &EncryptionResult,
                                                     content_alg: ContentEncryptionAlgorithm,
@@ -415,24 +508,81 @@ impl KeyManagementAlgorithm {
         use self::KeyManagementAlgorithm::*;
 
         match *self {
+            A128KW | A192KW | A256KW => self.aes_key_wrap_decrypt(encrypted, content_alg, key),
+            ECDH_ES_A128KW | ECDH_ES_A192KW | ECDH_ES_A256KW => self.aes_key_wrap_decrypt(encrypted, content_alg, key),
+            PBES2_HS256_A128KW | PBES2_HS384_A192KW | PBES2_HS512_A256KW => {
+                self.aes_key_wrap_decrypt(encrypted, content_alg, key)
+            }
             A128GCMKW | A192GCMKW | A256GCMKW => self.aes_gcm_decrypt(encrypted, content_alg, key),
+            #[cfg(feature = "rsa-decryption")]
+            RSA1_5 => self.rsa1_5_decrypt(encrypted, content_alg, key),
+            #[cfg(feature = "rsa-decryption")]
+            RSA_OAEP | RSA_OAEP_256 => self.rsa_oaep_decrypt(encrypted, content_alg, key),
+            #[cfg(not(feature = "rsa-decryption"))]
+            RSA_OAEP | RSA_OAEP_256 => {
+                Err("RSA-OAEP decryption requires the `rsa-decryption` feature, because of RUSTSEC-2023-0071"
+                        .to_string())?
+            }
             DirectSymmetricKey => Ok(key.clone_without_additional()),
             _ => Err(Error::UnsupportedOperation),
         }
     }
 
+    /// Returns the length of the key encryption key of an AES Key Wrap algorithm, in bytes
+    fn aes_key_wrap_length(&self) -> Result<usize, Error> {
+        use self::KeyManagementAlgorithm::*;
+
+        match *self {
+            A128KW | ECDH_ES_A128KW | PBES2_HS256_A128KW => Ok(128 / 8),
+            A192KW | ECDH_ES_A192KW | PBES2_HS384_A192KW => Ok(192 / 8),
+            A256KW | ECDH_ES_A256KW | PBES2_HS512_A256KW => Ok(256 / 8),
+            _ => Err(Error::UnsupportedOperation),
+        }
+    }
+
+    /// Returns the key encryption key of an AES Key Wrap algorithm, after checking that it has the right length
+    fn aes_key_wrap_key<'a, T: Serialize + DeserializeOwned>(&self, key: &'a jwk::JWK<T>) -> Result<&'a [u8], Error> {
+        let length = self.aes_key_wrap_length()?;
+        let kek = key.algorithm.octect_key()?;
+        if kek.len() != length {
+            Err(format!("Invalid key length for {:?}. Expected {} bytes, got {}", self, length, kek.len()))?;
+        }
+        Ok(kek)
+    }
+
+    fn aes_key_wrap_encrypt<T: Serialize + DeserializeOwned>(&self,
+                                                             payload: &[u8],
+                                                             key: &jwk::JWK<T>)
+                                                             -> Result<EncryptionResult, Error> {
+        let encrypted = aes_key_wrap::wrap(self.aes_key_wrap_key(key)?, payload)?;
+        Ok(EncryptionResult {
+               encrypted: encrypted,
+               ..Default::default()
+           })
+    }
+
+    fn aes_key_wrap_decrypt<T: Serialize + DeserializeOwned>(&self,
+                                                             encrypted: &EncryptionResult,
+                                                             content_alg: ContentEncryptionAlgorithm,
+                                                             key: &jwk::JWK<T>)
+                                                             -> Result<jwk::JWK<::Empty>, Error> {
+        let cek = aes_key_wrap::unwrap(self.aes_key_wrap_key(key)?, &encrypted.encrypted)?;
+        Ok(content_encryption_key(cek, content_alg))
+    }
+
     fn aes_gcm_encrypt<T: Serialize + DeserializeOwned>(&self,
                                                         payload: &[u8],
                                                         key: &jwk::JWK<T>)
                                                         -> Result<EncryptionResult, Error> {
         use self::KeyManagementAlgorithm::*;
 
-        let algorithm = match *self {
-            A128GCMKW => &aead::AES_128_GCM,
-            A256GCMKW => &aead::AES_256_GCM,
-            _ => Err(Error::UnsupportedOperation)?,
-        };
-        aes_gcm_encrypt(algorithm, payload, &[], key)
+        match *self {
+            A128GCMKW => aes_gcm_encrypt(&aead::AES_128_GCM, payload, &[], key),
+            #[cfg(feature = "aes192")]
+            A192GCMKW => aes_192_gcm_encrypt(payload, &[], key),
+            A256GCMKW => aes_gcm_encrypt(&aead::AES_256_GCM, payload, &[], key),
+            _ => Err(Error::UnsupportedOperation),
+        }
     }
 
     fn aes_gcm_decrypt<T: Serialize + DeserializeOwned>(&self,
@@ -442,40 +592,275 @@ impl KeyManagementAlgorithm {
                                                         -> Result<jwk::JWK<::Empty>, Error> {
         use self::KeyManagementAlgorithm::*;
 
-        let algorithm = match *self {
-            A128GCMKW => &aead::AES_128_GCM,
-            A256GCMKW => &aead::AES_256_GCM,
+        let cek = match *self {
+            A128GCMKW => aes_gcm_decrypt(&aead::AES_128_GCM, encrypted, key)?,
+            #[cfg(feature = "aes192")]
+            A192GCMKW => aes_192_gcm_decrypt(encrypted, key)?,
+            A256GCMKW => aes_gcm_decrypt(&aead::AES_256_GCM, encrypted, key)?,
             _ => Err(Error::UnsupportedOperation)?,
         };
+        Ok(content_encryption_key(cek, content_alg))
+    }
 
-        let cek = aes_gcm_decrypt(algorithm, encrypted, key)?;
-        Ok(jwk::JWK {
-               algorithm: jwk::AlgorithmParameters::OctectKey {
-                   value: cek,
-                   key_type: Default::default(),
-               },
-               common: jwk::CommonParameters {
-                   public_key_use: Some(jwk::PublicKeyUse::Encryption),
-                   algorithm: Some(Algorithm::ContentEncryption(content_alg)),
-                   ..Default::default()
-               },
-               additional: Default::default(),
+    fn rsa1_5_encrypt<T: Serialize + DeserializeOwned>(&self,
+                                                       payload: &[u8],
+                                                       key: &jwk::JWK<T>)
+                                                       -> Result<EncryptionResult, Error> {
+        let public_key = rsa_public_key(rsa_key_parameters(key)?)?;
+        let encrypted = public_key.encrypt(&mut OsRng, Pkcs1v15Encrypt, payload)?;
+        Ok(EncryptionResult {
+               encrypted: encrypted,
+               ..Default::default()
            })
     }
+
+    /// Decrypt the CEK with the countermeasures of [RFC7516#11.5](https://tools.ietf.org/html/rfc7516#section-11.5).
+    /// A random CEK is generated before decrypting, and is used instead of the decrypted CEK if the padding is invalid
+    /// or the decrypted CEK has the wrong length. The failure is then only reported when the content fails to be
+    /// authenticated, which does not reveal whether the padding was valid.
+    ///
+    /// Like `RSA-OAEP`, decryption is only available with the `rsa-decryption` feature, because of
+    /// [RUSTSEC-2023-0071](https://rustsec.org/advisories/RUSTSEC-2023-0071.html).
+    #[cfg(feature = "rsa-decryption")]
+    fn rsa1_5_decrypt<T: Serialize + DeserializeOwned>(&self,
+                                                       encrypted: &EncryptionResult,
+                                                       content_alg: ContentEncryptionAlgorithm,
+                                                       key: &jwk::JWK<T>)
+                                                       -> Result<jwk::JWK<::Empty>, Error> {
+        let private_key = rsa_private_key(rsa_key_parameters(key)?)?;
+        let random_cek = content_alg.generate_key()?;
+        let key_length = private_key.size();
+        if key_length < random_cek.len() + 11 {
+            Err("The RSA key is too small for the content encryption key".to_string())?;
+        }
+
+        // An invalid ciphertext is handled like an invalid padding, and leaves the encoded message empty
+        let ciphertext = rsa::BigUint::from_bytes_be(&encrypted.encrypted);
+        let mut encoded = vec![0; key_length];
+        if let Ok(message) = rsa::hazmat::rsa_decrypt_and_check(&private_key, Some(&mut OsRng), &ciphertext) {
+            let message = message.to_bytes_be();
+            if message.len() <= key_length {
+                encoded[key_length - message.len()..].copy_from_slice(&message);
+            }
+        }
+
+        Ok(content_encryption_key(rsa1_5_select_cek(&encoded, &random_cek), content_alg))
+    }
+
+    /// The OAEP padding for the algorithm
+    fn rsa_oaep_padding(&self) -> Result<Oaep, Error> {
+        use self::KeyManagementAlgorithm::*;
+
+        match *self {
+            RSA_OAEP => Ok(Oaep::new::<Sha1>()),
+            RSA_OAEP_256 => Ok(Oaep::new::<Sha256>()),
+            _ => Err(Error::UnsupportedOperation),
+        }
+    }
+
+    fn rsa_oaep_encrypt<T: Serialize + DeserializeOwned>(&self,
+                                                         payload: &[u8],
+                                                         key: &jwk::JWK<T>)
+                                                         -> Result<EncryptionResult, Error> {
+        let public_key = rsa_public_key(rsa_key_parameters(key)?)?;
+        let encrypted = public_key.encrypt(&mut OsRng, self.rsa_oaep_padding()?, payload)?;
+        Ok(EncryptionResult {
+               encrypted: encrypted,
+               ..Default::default()
+           })
+    }
+
+    /// Decrypt the CEK with the RSA private `key`.
+    ///
+    /// The private key operation of the `rsa` crate is not constant time, and is affected by the Marvin timing side
+    /// channel ([RUSTSEC-2023-0071](https://rustsec.org/advisories/RUSTSEC-2023-0071.html)), so decryption is
+    /// only available with the `rsa-decryption` feature until a constant time RSA implementation is used.
+    #[cfg(feature = "rsa-decryption")]
+    fn rsa_oaep_decrypt<T: Serialize + DeserializeOwned>(&self,
+                                                         encrypted: &EncryptionResult,
+                                                         content_alg: ContentEncryptionAlgorithm,
+                                                         key: &jwk::JWK<T>)
+                                                         -> Result<jwk::JWK<::Empty>, Error> {
+        let private_key = rsa_private_key(rsa_key_parameters(key)?)?;
+        let cek = private_key.decrypt(self.rsa_oaep_padding()?, &encrypted.encrypted)?;
+        Ok(content_encryption_key(cek, content_alg))
+    }
+
+    /// Derive a key with an ECDH-ES key agreement between a newly generated ephemeral key pair and the recipient's
+    /// public `key`, as described in [RFC7518#4.6](https://tools.ietf.org/html/rfc7518#section-4.6).
+    /// The key is either an Elliptic Curve key on P-256 or P-384, or an X25519 Octet Key Pair, as described in
+    /// [RFC 8037](https://tools.ietf.org/html/rfc8037#section-3.2).
+    /// `party_u_info` and `party_v_info` are the values of the `apu` and `apv` header parameters, which are empty
+    /// when the parameters are absent.
+    ///
+    /// Returns the agreed key, along with the ephemeral public key to send to the recipient in the `epk` header
+    /// parameter. The agreed key is the CEK for `ECDH-ES`, and the key used to wrap the CEK for the
+    /// `ECDH-ES+A128KW`, `ECDH-ES+A192KW` and `ECDH-ES+A256KW` algorithms.
+    pub fn agree_key_for_encryption<T: Serialize + DeserializeOwned>
+        (&self,
+         content_alg: ContentEncryptionAlgorithm,
+         key: &jwk::JWK<T>,
+         party_u_info: &[u8],
+         party_v_info: &[u8])
+         -> Result<(jwk::JWK<::Empty>, jwk::JWK<::Empty>), Error> {
+        let (shared_secret, ephemeral_public_key) = match key.algorithm {
+            jwk::AlgorithmParameters::EllipticCurve(ref parameters) => {
+                match parameters.curve {
+                    jwk::EllipticCurve::P256 => ecdh_ephemeral::<p256::NistP256>(parameters)?,
+                    jwk::EllipticCurve::P384 => ecdh_ephemeral::<p384::NistP384>(parameters)?,
+                    jwk::EllipticCurve::P521 => Err(Error::UnsupportedOperation)?,
+                }
+            }
+            jwk::AlgorithmParameters::OctetKeyPair(ref parameters) => x25519_ephemeral(parameters)?,
+            ref others => Err(unexpected_key_type_error!(jwk::KeyType::EllipticCurve, others.key_type()))?,
+        };
+
+        let agreed_key = self.ecdh_es_derive_key(&shared_secret, content_alg, party_u_info, party_v_info)?;
+        Ok((agreed_key, ephemeral_public_key))
+    }
+
+    /// Derive a key with an ECDH-ES key agreement between the `ephemeral_public_key` of the producer, found in the
+    /// `epk` header parameter, and the recipient's private `key`.
+    /// The ephemeral public key is rejected if it is not a point on the curve of the recipient's key.
+    ///
+    /// Returns the agreed key, which is the CEK for `ECDH-ES`, and the key used to unwrap the CEK for the
+    /// `ECDH-ES+A128KW`, `ECDH-ES+A192KW` and `ECDH-ES+A256KW` algorithms.
+    pub fn agree_key_for_decryption<T: Serialize + DeserializeOwned>(&self,
+                                                                     content_alg: ContentEncryptionAlgorithm,
+                                                                     ephemeral_public_key: &jwk::JWK<::Empty>,
+                                                                     key: &jwk::JWK<T>,
+                                                                     party_u_info: &[u8],
+                                                                     party_v_info: &[u8])
+                                                                     -> Result<jwk::JWK<::Empty>, Error> {
+        use jwk::AlgorithmParameters::{EllipticCurve, OctetKeyPair};
+
+        let shared_secret = match (&ephemeral_public_key.algorithm, &key.algorithm) {
+            (&EllipticCurve(ref ephemeral_parameters), &EllipticCurve(ref parameters)) => {
+                if ephemeral_parameters.curve != parameters.curve {
+                    Err(format!("The ephemeral public key is on curve {:?}, but the key is on curve {:?}",
+                                ephemeral_parameters.curve,
+                                parameters.curve))?;
+                }
+                match parameters.curve {
+                    jwk::EllipticCurve::P256 => ecdh_static::<p256::NistP256>(ephemeral_parameters, parameters)?,
+                    jwk::EllipticCurve::P384 => ecdh_static::<p384::NistP384>(ephemeral_parameters, parameters)?,
+                    jwk::EllipticCurve::P521 => Err(Error::UnsupportedOperation)?,
+                }
+            }
+            (&OctetKeyPair(ref ephemeral_parameters), &OctetKeyPair(ref parameters)) => {
+                if ephemeral_parameters.curve != parameters.curve {
+                    Err(format!("The ephemeral public key is on curve {:?}, but the key is on curve {:?}",
+                                ephemeral_parameters.curve,
+                                parameters.curve))?;
+                }
+                x25519_static(ephemeral_parameters, parameters)?
+            }
+            (ephemeral, others) => {
+                Err(format!("The ephemeral public key is of type {}, but the key is of type {}",
+                            ephemeral.key_type(),
+                            others.key_type()))?
+            }
+        };
+
+        self.ecdh_es_derive_key(&shared_secret, content_alg, party_u_info, party_v_info)
+    }
+
+    /// Derive the key from the ECDH shared secret with the Concat KDF, as described in
+    /// [RFC7518#4.6.2](https://tools.ietf.org/html/rfc7518#section-4.6.2)
+    fn ecdh_es_derive_key(&self,
+                          shared_secret: &[u8],
+                          content_alg: ContentEncryptionAlgorithm,
+                          party_u_info: &[u8],
+                          party_v_info: &[u8])
+                          -> Result<jwk::JWK<::Empty>, Error> {
+        use self::KeyManagementAlgorithm::*;
+
+        match *self {
+            ECDH_ES => {
+                // In the Direct Key Agreement mode, the algorithm ID is the `enc` header parameter
+                let algorithm_id = serde_json::to_value(content_alg)?;
+                let algorithm_id = algorithm_id.as_str().unwrap_or_default();
+                let cek = concat_kdf(shared_secret,
+                                     algorithm_id.as_bytes(),
+                                     party_u_info,
+                                     party_v_info,
+                                     content_alg.key_length()?);
+                Ok(content_encryption_key(cek, content_alg))
+            }
+            ECDH_ES_A128KW | ECDH_ES_A192KW | ECDH_ES_A256KW => {
+                // In the Key Agreement with Key Wrapping mode, the algorithm ID is the `alg` header parameter
+                let algorithm_id = serde_json::to_value(self)?;
+                let algorithm_id = algorithm_id.as_str().unwrap_or_default();
+                let kek = concat_kdf(shared_secret,
+                                     algorithm_id.as_bytes(),
+                                     party_u_info,
+                                     party_v_info,
+                                     self.aes_key_wrap_length()?);
+                Ok(jwk::JWK::new_octect_key(&kek, Default::default()))
+            }
+            _ => Err(Error::UnsupportedOperation),
+        }
+    }
+
+    /// Derive the key encryption key of a PBES2 algorithm from the password in the octet key `key`, as described in
+    /// [RFC7518#4.8.1.1](https://tools.ietf.org/html/rfc7518#section-4.8.1.1).
+    ///
+    /// `salt_input` is the `p2s` header parameter, which must be at least 8 bytes long, and `iteration_count` is the
+    /// `p2c` header parameter.
+    pub fn pbes2_derive_key<T: Serialize + DeserializeOwned>(&self,
+                                                             key: &jwk::JWK<T>,
+                                                             salt_input: &[u8],
+                                                             iteration_count: u32)
+                                                             -> Result<jwk::JWK<::Empty>, Error> {
+        use self::KeyManagementAlgorithm::*;
+
+        if salt_input.len() < PBES2_MIN_SALT_INPUT_LENGTH {
+            Err(format!("The PBES2 salt input must be at least {} bytes long, got {}",
+                        PBES2_MIN_SALT_INPUT_LENGTH,
+                        salt_input.len()))?;
+        }
+        if iteration_count == 0 {
+            Err("The PBES2 iteration count must be positive".to_string())?;
+        }
+
+        let password = key.algorithm.octect_key()?;
+        // The salt is the UTF-8 `alg` header parameter, a zero byte and the salt input
+        let algorithm_id = serde_json::to_value(self)?;
+        let mut salt = algorithm_id.as_str().unwrap_or_default().as_bytes().to_vec();
+        salt.push(0);
+        salt.extend_from_slice(salt_input);
+
+        let mut kek = vec![0; self.aes_key_wrap_length()?];
+        match *self {
+            PBES2_HS256_A128KW => pbkdf2::pbkdf2_hmac::<Sha256>(password, &salt, iteration_count, &mut kek),
+            PBES2_HS384_A192KW => pbkdf2::pbkdf2_hmac::<Sha384>(password, &salt, iteration_count, &mut kek),
+            PBES2_HS512_A256KW => pbkdf2::pbkdf2_hmac::<Sha512>(password, &salt, iteration_count, &mut kek),
+            _ => Err(Error::UnsupportedOperation)?,
+        }
+        Ok(jwk::JWK::new_octect_key(&kek, Default::default()))
+    }
 }
 
 impl ContentEncryptionAlgorithm {
-    /// Convenience function to generate a new random key with the required length
-    pub fn generate_key(&self) -> Result<Vec<u8>, Error> {
+    /// Returns the length of the key required by the algorithm, in bytes
+    fn key_length(&self) -> Result<usize, Error> {
         use self::ContentEncryptionAlgorithm::*;
 
-        let length: usize = match *self {
-            A128GCM => 128 / 8,
-            A256GCM => 256 / 8,
-            _ => Err(Error::UnsupportedOperation)?,
-        };
+        match *self {
+            A128CBC_HS256 => Ok(256 / 8),
+            A192CBC_HS384 => Ok(384 / 8),
+            A256CBC_HS512 => Ok(512 / 8),
+            A128GCM => Ok(128 / 8),
+            #[cfg(feature = "aes192")]
+            A192GCM => Ok(192 / 8),
+            A256GCM => Ok(256 / 8),
+            _ => Err(Error::UnsupportedOperation),
+        }
+    }
 
-        let mut key: Vec<u8> = vec![0; length];
+    /// Convenience function to generate a new random key with the required length
+    pub fn generate_key(&self) -> Result<Vec<u8>, Error> {
+        let mut key: Vec<u8> = vec![0; self.key_length()?];
         rng().fill(&mut key)?;
         Ok(key)
     }
@@ -489,8 +874,12 @@ impl ContentEncryptionAlgorithm {
         use self::ContentEncryptionAlgorithm::*;
 
         match *self {
+            A128CBC_HS256 | A192CBC_HS384 | A256CBC_HS512 => {
+                let mut iv: Vec<u8> = vec![0; CBC_IV_LENGTH];
+                rng().fill(&mut iv)?;
+                self.aes_cbc_hmac_sha2_encrypt(payload, aad, &iv, key)
+            }
             A128GCM | A192GCM | A256GCM => self.aes_gcm_encrypt(payload, aad, key),
-            _ => Err(Error::UnsupportedOperation),
         }
 
     }
@@ -503,11 +892,89 @@ impl ContentEncryptionAlgorithm {
         use self::ContentEncryptionAlgorithm::*;
 
         match *self {
+            A128CBC_HS256 | A192CBC_HS384 | A256CBC_HS512 => self.aes_cbc_hmac_sha2_decrypt(encrypted, key),
             A128GCM | A192GCM | A256GCM => self.aes_gcm_decrypt(encrypted, key),
-            _ => Err(Error::UnsupportedOperation),
         }
     }
 
+    /// Split the key of an AES CBC HMAC SHA2 algorithm into the MAC key and the encryption key, as described in
+    /// [RFC7518#5.2.2.1](https://tools.ietf.org/html/rfc7518#section-5.2.2.1). Also returns the HMAC digest
+    /// algorithm.
+    fn aes_cbc_hmac_sha2_keys<'a, T: Serialize + DeserializeOwned>
+        (&self,
+         key: &'a jwk::JWK<T>)
+         -> Result<(&'a [u8], &'a [u8], &'static digest::Algorithm), Error> {
+        use self::ContentEncryptionAlgorithm::*;
+
+        let digest = match *self {
+            A128CBC_HS256 => &digest::SHA256,
+            A192CBC_HS384 => &digest::SHA384,
+            A256CBC_HS512 => &digest::SHA512,
+            _ => Err(Error::UnsupportedOperation)?,
+        };
+
+        let key = key.algorithm.octect_key()?;
+        let length = self.key_length()?;
+        if key.len() != length {
+            Err(format!("Invalid key length for {:?}. Expected {} bytes, got {}", self, length, key.len()))?;
+        }
+        let (mac_key, enc_key) = key.split_at(length / 2);
+        Ok((mac_key, enc_key, digest))
+    }
+
+    /// Encrypt with AES CBC HMAC SHA2, as described in
+    /// [RFC7518#5.2.2.1](https://tools.ietf.org/html/rfc7518#section-5.2.2.1)
+    fn aes_cbc_hmac_sha2_encrypt<T: Serialize + DeserializeOwned>(&self,
+                                                                  payload: &[u8],
+                                                                  aad: &[u8],
+                                                                  iv: &[u8],
+                                                                  key: &jwk::JWK<T>)
+                                                                  -> Result<EncryptionResult, Error> {
+        let (mac_key, enc_key, digest) = self.aes_cbc_hmac_sha2_keys(key)?;
+
+        let encrypted = match enc_key.len() {
+            16 => cbc_encryptor::<Aes128>(enc_key, iv)?.encrypt_padded_vec_mut::<Pkcs7>(payload),
+            24 => cbc_encryptor::<Aes192>(enc_key, iv)?.encrypt_padded_vec_mut::<Pkcs7>(payload),
+            32 => cbc_encryptor::<Aes256>(enc_key, iv)?.encrypt_padded_vec_mut::<Pkcs7>(payload),
+            _ => Err(Error::UnsupportedOperation)?,
+        };
+        let tag = aes_cbc_hmac_sha2_tag(digest, mac_key, aad, iv, &encrypted);
+
+        Ok(EncryptionResult {
+               nonce: iv.to_vec(),
+               encrypted: encrypted,
+               tag: tag,
+               additional_data: aad.to_vec(),
+           })
+    }
+
+    /// Decrypt with AES CBC HMAC SHA2, as described in
+    /// [RFC7518#5.2.2.2](https://tools.ietf.org/html/rfc7518#section-5.2.2.2). The authentication tag is checked
+    /// in constant time before anything is decrypted.
+    fn aes_cbc_hmac_sha2_decrypt<T: Serialize + DeserializeOwned>(&self,
+                                                                  encrypted: &EncryptionResult,
+                                                                  key: &jwk::JWK<T>)
+                                                                  -> Result<Vec<u8>, Error> {
+        let (mac_key, enc_key, digest) = self.aes_cbc_hmac_sha2_keys(key)?;
+
+        let tag = aes_cbc_hmac_sha2_tag(digest,
+                                        mac_key,
+                                        &encrypted.additional_data,
+                                        &encrypted.nonce,
+                                        &encrypted.encrypted);
+        verify_slices_are_equal(&tag, &encrypted.tag)?;
+
+        let iv = &encrypted.nonce;
+        let ciphertext = &encrypted.encrypted;
+        let decrypted = match enc_key.len() {
+            16 => cbc_decryptor::<Aes128>(enc_key, iv)?.decrypt_padded_vec_mut::<Pkcs7>(ciphertext),
+            24 => cbc_decryptor::<Aes192>(enc_key, iv)?.decrypt_padded_vec_mut::<Pkcs7>(ciphertext),
+            32 => cbc_decryptor::<Aes256>(enc_key, iv)?.decrypt_padded_vec_mut::<Pkcs7>(ciphertext),
+            _ => Err(Error::UnsupportedOperation)?,
+        };
+        decrypted.map_err(|_| Error::UnspecifiedCryptographicError)
+    }
+
     fn aes_gcm_encrypt<T: Serialize + DeserializeOwned>(&self,
                                                         payload: &[u8],
                                                         aad: &[u8],
@@ -515,12 +982,13 @@ impl ContentEncryptionAlgorithm {
                                                         -> Result<EncryptionResult, Error> {
         use self::ContentEncryptionAlgorithm::*;
 
-        let algorithm = match *self {
-            A128GCM => &aead::AES_128_GCM,
-            A256GCM => &aead::AES_256_GCM,
-            _ => Err(Error::UnsupportedOperation)?,
-        };
-        aes_gcm_encrypt(algorithm, payload, aad, key)
+        match *self {
+            A128GCM => aes_gcm_encrypt(&aead::AES_128_GCM, payload, aad, key),
+            #[cfg(feature = "aes192")]
+            A192GCM => aes_192_gcm_encrypt(payload, aad, key),
+            A256GCM => aes_gcm_encrypt(&aead::AES_256_GCM, payload, aad, key),
+            _ => Err(Error::UnsupportedOperation),
+        }
     }
 
     fn aes_gcm_decrypt<T: Serialize + DeserializeOwned>(&self,
@@ -529,13 +997,57 @@ impl ContentEncryptionAlgorithm {
                                                         -> Result<Vec<u8>, Error> {
         use self::ContentEncryptionAlgorithm::*;
 
-        let algorithm = match *self {
-            A128GCM => &aead::AES_128_GCM,
-            A256GCM => &aead::AES_256_GCM,
-            _ => Err(Error::UnsupportedOperation)?,
-        };
-        aes_gcm_decrypt(algorithm, encrypted, key)
+        match *self {
+            A128GCM => aes_gcm_decrypt(&aead::AES_128_GCM, encrypted, key),
+            #[cfg(feature = "aes192")]
+            A192GCM => aes_192_gcm_decrypt(encrypted, key),
+            A256GCM => aes_gcm_decrypt(&aead::AES_256_GCM, encrypted, key),
+            _ => Err(Error::UnsupportedOperation),
+        }
+    }
+}
+
+/// Returns the length in bytes of each of the `R` and `S` values of an ECDSA signature for the algorithm
+fn ecdsa_component_length(algorithm: SignatureAlgorithm) -> Result<usize, Error> {
+    match algorithm {
+        SignatureAlgorithm::ES256 => Ok(32),
+        SignatureAlgorithm::ES384 => Ok(48),
+        SignatureAlgorithm::ES512 => Ok(66),
+        _ => Err(format!("{:?} is not an ECDSA algorithm", algorithm))?,
+    }
+}
+
+/// Convert an ASN.1 DER encoded ECDSA signature into the fixed-length `R || S` form that JWS uses, as
+/// described in [RFC 7518 Section 3.4](https://tools.ietf.org/html/rfc7518#section-3.4).
+///
+/// Use this to turn signatures produced by tools such as OpenSSL into JWS signatures.
+pub fn ecdsa_signature_der_to_fixed(algorithm: SignatureAlgorithm, der: &[u8]) -> Result<Vec<u8>, Error> {
+    let length = ecdsa_component_length(algorithm)?;
+    let contents = der::read_sequence(der)?;
+    let (r, rest) = der::read_unsigned_integer(contents)?;
+    let (s, rest) = der::read_unsigned_integer(rest)?;
+    if !rest.is_empty() || r.len() > length || s.len() > length {
+        Err("Invalid DER encoded ECDSA signature".to_string())?;
     }
+
+    let mut fixed = vec![0; length * 2];
+    fixed[length - r.len()..length].copy_from_slice(r);
+    fixed[length * 2 - s.len()..].copy_from_slice(s);
+    Ok(fixed)
+}
+
+/// Convert a fixed-length `R || S` JWS ECDSA signature into its ASN.1 DER encoded form, as
+/// expected by tools such as OpenSSL.
+pub fn ecdsa_signature_fixed_to_der(algorithm: SignatureAlgorithm, fixed: &[u8]) -> Result<Vec<u8>, Error> {
+    let length = ecdsa_component_length(algorithm)?;
+    if fixed.len() != length * 2 {
+        Err(format!("Invalid ECDSA signature length. Expected {} bytes, got {}",
+                    length * 2,
+                    fixed.len()))?;
+    }
+
+    let (r, s) = fixed.split_at(length);
+    Ok(der::sequence(&[der::integer(r), der::integer(s)]))
 }
 
 /// Return a psuedo random number generator
@@ -550,6 +1062,272 @@ pub fn rng() -> &'static SystemRandom {
     RANDOM.deref()
 }
 
+/// Wrap the bytes of a Content Encryption Key (CEK) into a JWK
+fn content_encryption_key(key: Vec<u8>, content_alg: ContentEncryptionAlgorithm) -> jwk::JWK<::Empty> {
+    jwk::JWK {
+        algorithm: jwk::AlgorithmParameters::OctectKey {
+            value: key,
+            key_type: Default::default(),
+        },
+        common: jwk::CommonParameters {
+            public_key_use: Some(jwk::PublicKeyUse::Encryption),
+            algorithm: Some(Algorithm::ContentEncryption(content_alg)),
+            ..Default::default()
+        },
+        additional: Default::default(),
+    }
+}
+
+/// Returns the RSA parameters of a JWK
+fn rsa_key_parameters<T: Serialize + DeserializeOwned>(key: &jwk::JWK<T>) -> Result<&jwk::RSAKeyParameters, Error> {
+    match key.algorithm {
+        jwk::AlgorithmParameters::RSA(ref parameters) => Ok(parameters),
+        ref others => Err(unexpected_key_type_error!(jwk::KeyType::RSA, others.key_type())),
+    }
+}
+
+/// Convert an integer of a JWK into the representation used by the `rsa` crate
+fn rsa_integer(value: &BigUint) -> rsa::BigUint {
+    rsa::BigUint::from_bytes_be(&value.to_bytes_be())
+}
+
+/// Remove the PKCS #1 v1.5 padding of the `encoded` message, described in
+/// [RFC3447#7.2.2](https://tools.ietf.org/html/rfc3447#section-7.2.2), and return the decrypted CEK. The padding is
+/// only valid if the CEK has the length of `random_cek`, which is returned otherwise. The padding is checked and the
+/// CEK is selected in constant time.
+#[cfg(feature = "rsa-decryption")]
+fn rsa1_5_select_cek(encoded: &[u8], random_cek: &[u8]) -> Vec<u8> {
+    let separator = encoded.len() - random_cek.len() - 1;
+    let mut valid = encoded[0].ct_eq(&0) & encoded[1].ct_eq(&2) & encoded[separator].ct_eq(&0);
+    for byte in &encoded[2..separator] {
+        valid &= !byte.ct_eq(&0);
+    }
+
+    random_cek.iter()
+        .zip(&encoded[separator + 1..])
+        .map(|(random, decrypted)| u8::conditional_select(random, decrypted, valid))
+        .collect()
+}
+
+/// Build an RSA public key from the parameters of a JWK
+fn rsa_public_key(parameters: &jwk::RSAKeyParameters) -> Result<RsaPublicKey, Error> {
+    Ok(RsaPublicKey::new(rsa_integer(&parameters.n), rsa_integer(&parameters.e))?)
+}
+
+/// Build an RSA private key from the parameters of a JWK. The primes are recovered from the private exponent
+/// when they are absent.
+#[cfg(feature = "rsa-decryption")]
+fn rsa_private_key(parameters: &jwk::RSAKeyParameters) -> Result<RsaPrivateKey, Error> {
+    let d = parameters
+        .d
+        .as_ref()
+        .ok_or_else(|| "The RSA key is not a private key".to_string())?;
+    let primes = match (parameters.p.as_ref(), parameters.q.as_ref()) {
+        (Some(p), Some(q)) => vec![rsa_integer(p), rsa_integer(q)],
+        _ => vec![],
+    };
+    Ok(RsaPrivateKey::from_components(rsa_integer(&parameters.n),
+                                      rsa_integer(&parameters.e),
+                                      rsa_integer(d),
+                                      primes)?)
+}
+
+/// Build the public key of the parameters of an Elliptic Curve JWK. The key is rejected if its coordinates are not
+/// a point on the curve.
+fn ec_public_key<C>(parameters: &jwk::EllipticCurveKeyParameters) -> Result<PublicKey<C>, Error>
+    where C: CurveArithmetic,
+          AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+          FieldBytesSize<C>: ModulusSize
+{
+    if parameters.x.len() != parameters.y.len() {
+        Err("The coordinates of the Elliptic Curve public key have different lengths".to_string())?;
+    }
+
+    let mut point = vec![0x04];
+    point.extend_from_slice(&parameters.x);
+    point.extend_from_slice(&parameters.y);
+    PublicKey::<C>::from_sec1_bytes(&point)
+        .map_err(|_| Error::GenericError("The Elliptic Curve public key is not a point on the curve".to_string()))
+}
+
+/// Perform an ECDH key agreement between a newly generated ephemeral key pair and the public key of the
+/// recipient. Returns the shared secret, and the ephemeral public key.
+fn ecdh_ephemeral<C>(parameters: &jwk::EllipticCurveKeyParameters)
+                     -> Result<(Vec<u8>, jwk::JWK<::Empty>), Error>
+    where C: CurveArithmetic,
+          AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+          FieldBytesSize<C>: ModulusSize
+{
+    let public_key = ec_public_key::<C>(parameters)?;
+    let ephemeral_secret = EphemeralSecret::<C>::random(&mut OsRng);
+    let shared_secret = ephemeral_secret.diffie_hellman(&public_key);
+
+    let ephemeral_point = ephemeral_secret.public_key().to_encoded_point(false);
+    let (x, y) = ephemeral_point.as_bytes()[1..].split_at(parameters.x.len());
+    let ephemeral_public_key = jwk::JWK {
+        common: Default::default(),
+        algorithm: jwk::AlgorithmParameters::EllipticCurve(jwk::EllipticCurveKeyParameters {
+                                                               key_type: Default::default(),
+                                                               curve: parameters.curve.clone(),
+                                                               x: x.to_vec(),
+                                                               y: y.to_vec(),
+                                                               d: None,
+                                                           }),
+        additional: Default::default(),
+    };
+
+    Ok((shared_secret.raw_secret_bytes().to_vec(), ephemeral_public_key))
+}
+
+/// Perform an ECDH key agreement between the ephemeral public key of the producer and the private key of the
+/// recipient. Returns the shared secret.
+fn ecdh_static<C>(ephemeral_parameters: &jwk::EllipticCurveKeyParameters,
+                  parameters: &jwk::EllipticCurveKeyParameters)
+                  -> Result<Vec<u8>, Error>
+    where C: CurveArithmetic,
+          AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+          FieldBytesSize<C>: ModulusSize
+{
+    let ephemeral_public_key = ec_public_key::<C>(ephemeral_parameters)?;
+    let d = parameters
+        .d
+        .as_ref()
+        .ok_or_else(|| "The JWK does not contain the private key parameter `d`".to_string())?;
+    let secret_key = SecretKey::<C>::from_slice(d).map_err(|_| Error::UnspecifiedCryptographicError)?;
+
+    let shared_secret = ecdh::diffie_hellman(secret_key.to_nonzero_scalar(), ephemeral_public_key.as_affine());
+    Ok(shared_secret.raw_secret_bytes().to_vec())
+}
+
+/// Parse the public key of an X25519 Octet Key Pair
+fn x25519_public_key(parameters: &jwk::OctetKeyPairParameters) -> Result<x25519_dalek::PublicKey, Error> {
+    match parameters.curve {
+        jwk::OctetKeyPairCurve::X25519 => {}
+        jwk::OctetKeyPairCurve::X448 => Err(Error::UnsupportedOperation)?,
+        others => Err(format!("Keys on the {:?} curve cannot be used for key agreement", others))?,
+    }
+
+    let mut public_key = [0; 32];
+    if parameters.x.len() != public_key.len() {
+        Err(format!("Invalid X25519 public key length. Expected 32 bytes, got {}", parameters.x.len()))?;
+    }
+    public_key.copy_from_slice(&parameters.x);
+    Ok(x25519_dalek::PublicKey::from(public_key))
+}
+
+/// Returns the bytes of an X25519 shared secret, which must not be all zeros as required by
+/// [RFC 8037](https://tools.ietf.org/html/rfc8037#section-3.2)
+fn x25519_shared_secret_bytes(shared_secret: &x25519_dalek::SharedSecret) -> Result<Vec<u8>, Error> {
+    if !shared_secret.was_contributory() {
+        Err("The X25519 shared secret is all zeros".to_string())?;
+    }
+    Ok(shared_secret.as_bytes().to_vec())
+}
+
+/// Perform an X25519 key agreement between a newly generated ephemeral key pair and the public key of the
+/// recipient. Returns the shared secret, and the ephemeral public key.
+fn x25519_ephemeral(parameters: &jwk::OctetKeyPairParameters) -> Result<(Vec<u8>, jwk::JWK<::Empty>), Error> {
+    let public_key = x25519_public_key(parameters)?;
+    let ephemeral_secret = x25519_dalek::EphemeralSecret::random_from_rng(OsRng);
+    let ephemeral_public_key = x25519_dalek::PublicKey::from(&ephemeral_secret);
+    let shared_secret = x25519_shared_secret_bytes(&ephemeral_secret.diffie_hellman(&public_key))?;
+
+    let ephemeral_public_key = jwk::JWK {
+        common: Default::default(),
+        algorithm: jwk::AlgorithmParameters::OctetKeyPair(jwk::OctetKeyPairParameters {
+                                                              key_type: Default::default(),
+                                                              curve: jwk::OctetKeyPairCurve::X25519,
+                                                              x: ephemeral_public_key.as_bytes().to_vec(),
+                                                              d: None,
+                                                          }),
+        additional: Default::default(),
+    };
+
+    Ok((shared_secret, ephemeral_public_key))
+}
+
+/// Perform an X25519 key agreement between the ephemeral public key of the producer and the private key of the
+/// recipient. Returns the shared secret.
+fn x25519_static(ephemeral_parameters: &jwk::OctetKeyPairParameters,
+                 parameters: &jwk::OctetKeyPairParameters)
+                 -> Result<Vec<u8>, Error> {
+    let ephemeral_public_key = x25519_public_key(ephemeral_parameters)?;
+    let d = parameters
+        .d
+        .as_ref()
+        .ok_or_else(|| "The JWK does not contain the private key parameter `d`".to_string())?;
+
+    let mut secret = [0; 32];
+    if d.len() != secret.len() {
+        Err(format!("Invalid X25519 private key length. Expected 32 bytes, got {}", d.len()))?;
+    }
+    secret.copy_from_slice(d);
+    let secret = x25519_dalek::StaticSecret::from(secret);
+    x25519_shared_secret_bytes(&secret.diffie_hellman(&ephemeral_public_key))
+}
+
+/// The Concat KDF of [NIST SP 800-56A](https://doi.org/10.6028/NIST.SP.800-56Ar2) with SHA-256, using the
+/// `OtherInfo` described in [RFC7518#4.6.2](https://tools.ietf.org/html/rfc7518#section-4.6.2).
+/// `key_length` is in bytes.
+fn concat_kdf(shared_secret: &[u8],
+              algorithm_id: &[u8],
+              party_u_info: &[u8],
+              party_v_info: &[u8],
+              key_length: usize)
+              -> Vec<u8> {
+    let mut other_info = vec![];
+    for data in &[algorithm_id, party_u_info, party_v_info] {
+        other_info.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        other_info.extend_from_slice(data);
+    }
+    other_info.extend_from_slice(&(key_length as u32 * 8).to_be_bytes());
+
+    let mut key = Vec::with_capacity(key_length);
+    let mut counter: u32 = 1;
+    while key.len() < key_length {
+        let mut context = digest::Context::new(&digest::SHA256);
+        context.update(&counter.to_be_bytes());
+        context.update(shared_secret);
+        context.update(&other_info);
+        key.extend_from_slice(context.finish().as_ref());
+        counter += 1;
+    }
+    key.truncate(key_length);
+    key
+}
+
+fn cbc_encryptor<C>(key: &[u8], iv: &[u8]) -> Result<cbc::Encryptor<C>, Error>
+    where C: BlockEncryptMut + BlockCipher + KeyInit
+{
+    cbc::Encryptor::<C>::new_from_slices(key, iv).map_err(|_| Error::UnspecifiedCryptographicError)
+}
+
+fn cbc_decryptor<C>(key: &[u8], iv: &[u8]) -> Result<cbc::Decryptor<C>, Error>
+    where C: BlockDecryptMut + BlockCipher + KeyInit
+{
+    cbc::Decryptor::<C>::new_from_slices(key, iv).map_err(|_| Error::UnspecifiedCryptographicError)
+}
+
+/// Compute the authentication tag of AES CBC HMAC SHA2 over the additional authenticated data, the
+/// initialization vector, the ciphertext and the length of the additional authenticated data in bits.
+/// The HMAC is truncated to the length of the MAC key.
+fn aes_cbc_hmac_sha2_tag(digest: &'static digest::Algorithm,
+                         mac_key: &[u8],
+                         aad: &[u8],
+                         iv: &[u8],
+                         ciphertext: &[u8])
+                         -> Vec<u8> {
+    let aad_length = (aad.len() as u64 * 8).to_be_bytes();
+
+    let key = hmac::SigningKey::new(digest, mac_key);
+    let mut context = hmac::SigningContext::with_key(&key);
+    context.update(aad);
+    context.update(iv);
+    context.update(ciphertext);
+    context.update(&aad_length);
+    context.sign().as_ref()[..mac_key.len()].to_vec()
+}
+
 /// Encrypt a payload with AES GCM
 fn aes_gcm_encrypt<T: Serialize + DeserializeOwned>(algorithm: &'static aead::Algorithm,
                                                     payload: &[u8],
@@ -604,6 +1382,58 @@ fn aes_gcm_decrypt<T: Serialize + DeserializeOwned>(algorithm: &'static aead::Al
     Ok(plaintext.to_vec())
 }
 
+/// AES GCM with a 192 bit key, which *ring* does not provide
+#[cfg(feature = "aes192")]
+type Aes192Gcm = AesGcm<Aes192, U12>;
+
+/// Encrypt a payload with AES 192 GCM
+#[cfg(feature = "aes192")]
+fn aes_192_gcm_encrypt<T: Serialize + DeserializeOwned>(payload: &[u8],
+                                                        aad: &[u8],
+                                                        key: &jwk::JWK<T>)
+                                                        -> Result<EncryptionResult, Error> {
+    let key = key.algorithm.octect_key()?;
+    let cipher = Aes192Gcm::new_from_slice(key).map_err(|_| Error::UnspecifiedCryptographicError)?;
+
+    let mut nonce = Nonce::<U12>::default();
+    rng().fill(&mut nonce)?;
+
+    let mut encrypted = payload.to_vec();
+    let tag = cipher
+        .encrypt_in_place_detached(&nonce, aad, &mut encrypted)
+        .map_err(|_| Error::UnspecifiedCryptographicError)?;
+    Ok(EncryptionResult {
+           nonce: nonce.to_vec(),
+           encrypted: encrypted,
+           tag: tag.to_vec(),
+           additional_data: aad.to_vec(),
+       })
+}
+
+/// Decrypts a payload with AES 192 GCM
+#[cfg(feature = "aes192")]
+fn aes_192_gcm_decrypt<T: Serialize + DeserializeOwned>(encrypted: &EncryptionResult,
+                                                        key: &jwk::JWK<T>)
+                                                        -> Result<Vec<u8>, Error> {
+    if encrypted.nonce.len() != NONCE_LENGTH || encrypted.tag.len() != TAG_SIZE {
+        Err(Error::UnspecifiedCryptographicError)?;
+    }
+
+    let mut nonce = Nonce::<U12>::default();
+    nonce.copy_from_slice(&encrypted.nonce);
+    let mut tag = Tag::default();
+    tag.copy_from_slice(&encrypted.tag);
+
+    let key = key.algorithm.octect_key()?;
+    let cipher = Aes192Gcm::new_from_slice(key).map_err(|_| Error::UnspecifiedCryptographicError)?;
+
+    let mut plaintext = encrypted.encrypted.to_vec();
+    cipher
+        .decrypt_in_place_detached(&nonce, &encrypted.additional_data, &mut plaintext, &tag)
+        .map_err(|_| Error::UnspecifiedCryptographicError)?;
+    Ok(plaintext)
+}
+
 #[cfg(test)]
 mod tests {
     use ring::constant_time::verify_slices_are_equal;
@@ -611,6 +1441,7 @@ mod tests {
     use super::*;
     use CompactPart;
     use jwa;
+    use test::hex;
 
     #[test]
     fn sign_and_verify_none() {
@@ -708,14 +1539,95 @@ mod tests {
         assert!(valid);
     }
 
+    #[test]
+    fn sign_and_verify_es256_round_trip() {
+        let private_key = Secret::ecdsa_keypair_from_file("test/fixtures/ecdsa_p256_private_key.der").unwrap();
+        let payload = "payload".to_string();
+        let payload_bytes = payload.as_bytes();
+
+        let actual_signature = not_err!(SignatureAlgorithm::ES256.sign(payload_bytes, &private_key));
+
+        let public_key = match private_key {
+            Secret::ECDSAKeyPair(ref key_pair) => Secret::PublicKey(key_pair.public_key()),
+            _ => unreachable!(),
+        };
+        let valid = not_err!(SignatureAlgorithm::ES256.verify(actual_signature.as_slice(), payload_bytes, &public_key));
+        assert!(valid);
+    }
+
+    #[test]
+    fn sign_and_verify_es384_round_trip() {
+        let private_key = Secret::ecdsa_keypair_from_file("test/fixtures/ecdsa_p384_private_key.der").unwrap();
+        let payload = "payload".to_string();
+        let payload_bytes = payload.as_bytes();
+
+        let actual_signature = not_err!(SignatureAlgorithm::ES384.sign(payload_bytes, &private_key));
+
+        let public_key = match private_key {
+            Secret::ECDSAKeyPair(ref key_pair) => Secret::PublicKey(key_pair.public_key()),
+            _ => unreachable!(),
+        };
+        let valid = not_err!(SignatureAlgorithm::ES384.verify(actual_signature.as_slice(), payload_bytes, &public_key));
+        assert!(valid);
+    }
+
+    /// Key from [RFC 7515 Appendix A.3](https://tools.ietf.org/html/rfc7515#appendix-A.3)
+    #[test]
+    fn sign_and_verify_es256_round_trip_with_jwk() {
+        let jwk: jwk::JWK<::Empty> = not_err!(::serde_json::from_str(r#"{
+            "kty": "EC",
+            "crv": "P-256",
+            "x": "f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU",
+            "y": "x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0",
+            "d": "jpsQnnGQmL-YBIffH1136cspYG6-0iY7X1fCE9-E9LI"
+        }"#));
+        let private_key = not_err!(Secret::ecdsa_keypair_from_jwk(&jwk));
+        let payload = "payload".to_string();
+        let payload_bytes = payload.as_bytes();
+
+        let actual_signature = not_err!(SignatureAlgorithm::ES256.sign(payload_bytes, &private_key));
+
+        let public_key = match private_key {
+            Secret::ECDSAKeyPair(ref key_pair) => Secret::PublicKey(key_pair.public_key()),
+            _ => unreachable!(),
+        };
+        let valid = not_err!(SignatureAlgorithm::ES256.verify(actual_signature.as_slice(), payload_bytes, &public_key));
+        assert!(valid);
+    }
+
+    #[test]
+    #[should_panic(expected = "do not match the private key")]
+    fn ecdsa_keypair_from_jwk_rejects_mismatched_public_key() {
+        let jwk: jwk::JWK<::Empty> = not_err!(::serde_json::from_str(r#"{
+            "kty": "EC",
+            "crv": "P-256",
+            "x": "x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0",
+            "y": "f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU",
+            "d": "jpsQnnGQmL-YBIffH1136cspYG6-0iY7X1fCE9-E9LI"
+        }"#));
+        Secret::ecdsa_keypair_from_jwk(&jwk).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "does not match the algorithm")]
+    fn sign_ecdsa_with_wrong_curve() {
+        let private_key = Secret::ecdsa_keypair_from_file("test/fixtures/ecdsa_p256_private_key.der").unwrap();
+        let payload = "payload".to_string();
+        let payload_bytes = payload.as_bytes();
+
+        SignatureAlgorithm::ES384
+            .sign(payload_bytes, &private_key)
+            .unwrap();
+    }
+
     #[test]
     #[should_panic(expected = "UnsupportedOperation")]
-    fn sign_ecdsa() {
-        let private_key = Secret::Bytes("secret".to_string().into_bytes()); // irrelevant
+    fn sign_es512() {
+        let private_key = Secret::ecdsa_keypair_from_file("test/fixtures/ecdsa_p256_private_key.der").unwrap();
         let payload = "payload".to_string();
         let payload_bytes = payload.as_bytes();
 
-        SignatureAlgorithm::ES256
+        SignatureAlgorithm::ES512
             .sign(payload_bytes, &private_key)
             .unwrap();
     }
@@ -733,6 +1645,7 @@ mod tests {
         let signature = "3046022100EFD48B2AACB6A8FD1140DD9CD45E81D69D2C877B56AAF991C34D0EA84EAF3716022100F7CB1C942D657C\
                          41D436C7A1B6E29F65F3E900DBB9AFF4064DC4AB2F843ACDA8";
         let signature_bytes: Vec<u8> = not_err!(hex::decode(signature.as_bytes()));
+        let signature_bytes = not_err!(ecdsa_signature_der_to_fixed(SignatureAlgorithm::ES256, &signature_bytes));
         let valid = not_err!(SignatureAlgorithm::ES256.verify(signature_bytes.as_slice(), payload_bytes, &public_key));
         assert!(valid);
     }
@@ -752,10 +1665,102 @@ mod tests {
                          DD1E80FABE4602310099EF4AEB15F178CEA1FE40DB2603138F130E740A19624526203B6351D0A3A94FA329C145786E\
                          679E7B82C71A38628AC8";
         let signature_bytes: Vec<u8> = not_err!(hex::decode(signature.as_bytes()));
+        let signature_bytes = not_err!(ecdsa_signature_der_to_fixed(SignatureAlgorithm::ES384, &signature_bytes));
         let valid = not_err!(SignatureAlgorithm::ES384.verify(signature_bytes.as_slice(), payload_bytes, &public_key));
         assert!(valid);
     }
 
+    /// Test case from [RFC 7515 Appendix A.3](https://tools.ietf.org/html/rfc7515#appendix-A.3)
+    #[test]
+    fn verify_es256_rfc7515_example() {
+        use data_encoding::base64url;
+
+        let payload = "eyJhbGciOiJFUzI1NiJ9.\
+                       eyJpc3MiOiJqb2UiLA0KICJleHAiOjEzMDA4MTkzODAsDQogImh0dHA6Ly9leGFtcGxlLmNvbS9pc19yb290Ijp0cnVlfQ";
+        let x = not_err!(base64url::decode_nopad("f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU".as_bytes()));
+        let y = not_err!(base64url::decode_nopad("x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0".as_bytes()));
+        let mut public_key = vec![0x04];
+        public_key.extend_from_slice(&x);
+        public_key.extend_from_slice(&y);
+        let public_key = Secret::PublicKey(public_key);
+        let signature = "DtEhU3ljbEg8L38VWAfUAqOyKAM6-Xx-F4GawxaepmXFCgfTjDxw5djxLa8ISlSApmWQxfKTUJqPP3-Kg6NU1Q";
+        let signature_bytes = not_err!(base64url::decode_nopad(signature.as_bytes()));
+        assert_eq!(signature_bytes.len(), 64);
+
+        let valid = not_err!(SignatureAlgorithm::ES256.verify(&signature_bytes, payload.as_bytes(), &public_key));
+        assert!(valid);
+
+        // A DER encoded signature is not a valid JWS signature
+        let der_signature = not_err!(ecdsa_signature_fixed_to_der(SignatureAlgorithm::ES256, &signature_bytes));
+        let valid = not_err!(SignatureAlgorithm::ES256.verify(&der_signature, payload.as_bytes(), &public_key));
+        assert!(!valid);
+    }
+
+    #[test]
+    fn ecdsa_signature_der_fixed_round_trip() {
+        use data_encoding::hex;
+
+        let der = "3046022100EFD48B2AACB6A8FD1140DD9CD45E81D69D2C877B56AAF991C34D0EA84EAF3716022100F7CB1C942D657C\
+                   41D436C7A1B6E29F65F3E900DBB9AFF4064DC4AB2F843ACDA8";
+        let der = not_err!(hex::decode(der.as_bytes()));
+        let expected_fixed = "EFD48B2AACB6A8FD1140DD9CD45E81D69D2C877B56AAF991C34D0EA84EAF3716F7CB1C942D657C\
+                              41D436C7A1B6E29F65F3E900DBB9AFF4064DC4AB2F843ACDA8";
+        let expected_fixed = not_err!(hex::decode(expected_fixed.as_bytes()));
+
+        let fixed = not_err!(ecdsa_signature_der_to_fixed(SignatureAlgorithm::ES256, &der));
+        assert_eq!(fixed, expected_fixed);
+        let round_tripped = not_err!(ecdsa_signature_fixed_to_der(SignatureAlgorithm::ES256, &fixed));
+        assert_eq!(round_tripped, der);
+    }
+
+    #[test]
+    fn ecdsa_signature_conversion_pads_short_values() {
+        let mut fixed = vec![0; 96];
+        fixed[47] = 0x01;
+        fixed[48] = 0x80;
+
+        let der = not_err!(ecdsa_signature_fixed_to_der(SignatureAlgorithm::ES384, &fixed));
+        assert_eq!(&der[0..5], &[0x30, 0x36, 0x02, 0x01, 0x01]);
+        let round_tripped = not_err!(ecdsa_signature_der_to_fixed(SignatureAlgorithm::ES384, &der));
+        assert_eq!(round_tripped, fixed);
+    }
+
+    #[test]
+    fn ecdsa_signature_conversion_rejects_invalid_input() {
+        assert!(ecdsa_signature_fixed_to_der(SignatureAlgorithm::ES256, &[0; 63]).is_err());
+        assert!(ecdsa_signature_fixed_to_der(SignatureAlgorithm::RS256, &[0; 64]).is_err());
+        assert!(ecdsa_signature_der_to_fixed(SignatureAlgorithm::ES256, &[0x30, 0x00]).is_err());
+    }
+
+    /// Test case from [RFC 8037 Appendix A.4](https://tools.ietf.org/html/rfc8037#appendix-A.4)
+    #[test]
+    fn sign_and_verify_eddsa_rfc8037_example() {
+        use data_encoding::base64url;
+
+        let jwk: jwk::JWK<::Empty> = not_err!(::serde_json::from_str(r#"{
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "d": "nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A",
+            "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"
+        }"#));
+        let private_key = not_err!(Secret::ed25519_keypair_from_jwk(&jwk));
+        let payload = "eyJhbGciOiJFZERTQSJ9.RXhhbXBsZSBvZiBFZDI1NTE5IHNpZ25pbmc";
+        let expected_signature = "hgyY0il_MGCjP0JzlnLWG1PPOt7-09PGcvMg3AIbQR6dWbhijcNR4ki4iylGjg5BhVsPt9g7sVvpAr_MuM0KAg";
+
+        let actual_signature = not_err!(SignatureAlgorithm::EdDSA.sign(payload.as_bytes(), &private_key));
+        assert_eq!(&*base64url::encode_nopad(&actual_signature), expected_signature);
+
+        let public_key = match jwk.algorithm {
+            jwk::AlgorithmParameters::OctetKeyPair(ref parameters) => Secret::PublicKey(parameters.x.clone()),
+            _ => unreachable!(),
+        };
+        let valid = not_err!(SignatureAlgorithm::EdDSA.verify(&actual_signature, payload.as_bytes(), &public_key));
+        assert!(valid);
+
+        let valid = not_err!(SignatureAlgorithm::EdDSA.verify(&actual_signature, b"tampered", &public_key));
+        assert!(!valid);
+    }
+
     #[test]
     #[should_panic(expected = "UnsupportedOperation")]
     fn verify_es512() {
@@ -1055,4 +2060,371 @@ mod tests {
         let decrypted_payload = not_err!(enc_alg.decrypt(&encrypted_payload, &key));
         assert!(verify_slices_are_equal(payload.as_bytes(), &decrypted_payload).is_ok());
     }
+
+    #[cfg(feature = "aes192")]
+    #[test]
+    fn aes192gcmkw_key_encryption_round_trip() {
+        let mut key: Vec<u8> = vec![0; 192/8];
+        not_err!(rng().fill(&mut key));
+
+        let key = jwk::JWK::<::Empty> {
+            common: Default::default(),
+            additional: Default::default(),
+            algorithm: jwk::AlgorithmParameters::OctectKey {
+                key_type: Default::default(),
+                value: key,
+            },
+        };
+
+        let cek_alg = KeyManagementAlgorithm::A192GCMKW;
+        let enc_alg = jwa::ContentEncryptionAlgorithm::A192GCM; // determines the CEK
+        let cek = not_err!(cek_alg.cek(enc_alg, &key));
+        assert_eq!(cek.octect_key().unwrap().len(), 192 / 8);
+
+        let encrypted_cek = not_err!(cek_alg.encrypt(cek.octect_key().unwrap(), &key));
+        let decrypted_cek = not_err!(cek_alg.decrypt(&encrypted_cek, enc_alg, &key));
+
+        assert!(verify_slices_are_equal(cek.octect_key().unwrap(),
+                                        decrypted_cek.octect_key().unwrap())
+                        .is_ok());
+    }
+
+    #[cfg(feature = "aes192")]
+    #[test]
+    fn aes192gcm_encryption_round_trip() {
+        let enc_alg = jwa::ContentEncryptionAlgorithm::A192GCM;
+        let key = jwk::JWK::<::Empty>::new_octect_key(&not_err!(enc_alg.generate_key()), Default::default());
+        assert_eq!(key.octect_key().unwrap().len(), 192 / 8);
+
+        let payload = "狼よ、我が敵を食らえ！";
+        let aad = "My servants never die!";
+        let encrypted_payload = not_err!(enc_alg.encrypt(payload.as_bytes(), aad.as_bytes(), &key));
+
+        let decrypted_payload = not_err!(enc_alg.decrypt(&encrypted_payload, &key));
+        assert!(verify_slices_are_equal(payload.as_bytes(), &decrypted_payload).is_ok());
+    }
+
+    /// Test Case 10 from the GCM specification submitted to NIST by McGrew and Viega, which uses a 192 bit key
+    #[cfg(feature = "aes192")]
+    #[test]
+    fn aes192gcm_test_vector() {
+        use data_encoding::hex;
+
+        let key = not_err!(hex::decode(b"FEFFE9928665731C6D6A8F9467308308FEFFE9928665731C"));
+        let key = jwk::JWK::<::Empty>::new_octect_key(&key, Default::default());
+        let plaintext = "D9313225F88406E5A55909C5AFF5269A86A7A9531534F7DA2E4C303D8A318A721C3C0C95956809532FCF0E2449A6B5\
+                         25B16AEDF5AA0DE657BA637B39";
+        let ciphertext = "3980CA0B3C00E841EB06FAC4872A2757859E1CEAA6EFD984628593B40CA1E19C7D773D00C144C525AC619D18C84A\
+                          3F4718E2448B2FE324D9CCDA2710";
+        let encrypted = EncryptionResult {
+            nonce: not_err!(hex::decode(b"CAFEBABEFACEDBADDECAF888")),
+            encrypted: not_err!(hex::decode(ciphertext.as_bytes())),
+            tag: not_err!(hex::decode(b"2519498E80F1478F37BA55BD6D27618C")),
+            additional_data: not_err!(hex::decode(b"FEEDFACEDEADBEEFFEEDFACEDEADBEEFABADDAD2")),
+        };
+
+        let enc_alg = jwa::ContentEncryptionAlgorithm::A192GCM;
+        let decrypted = not_err!(enc_alg.decrypt(&encrypted, &key));
+        assert_eq!(decrypted, not_err!(hex::decode(plaintext.as_bytes())));
+
+        let mut tampered = encrypted.clone();
+        tampered.additional_data.pop();
+        assert_matches!(enc_alg.decrypt(&tampered, &key), Err(Error::UnspecifiedCryptographicError));
+
+        let mut tampered = encrypted.clone();
+        tampered.nonce.pop();
+        assert_matches!(enc_alg.decrypt(&tampered, &key), Err(Error::UnspecifiedCryptographicError));
+    }
+
+    #[cfg(not(feature = "aes192"))]
+    #[test]
+    fn aes192gcm_requires_aes192_feature() {
+        let key = jwk::JWK::<::Empty>::new_octect_key(&[0; 192 / 8], Default::default());
+
+        let enc_alg = jwa::ContentEncryptionAlgorithm::A192GCM;
+        assert_matches!(enc_alg.generate_key(), Err(Error::UnsupportedOperation));
+        assert_matches!(enc_alg.encrypt(b"payload", &[], &key), Err(Error::UnsupportedOperation));
+
+        let cek_alg = KeyManagementAlgorithm::A192GCMKW;
+        assert_matches!(cek_alg.encrypt(&[0; 128 / 8], &key), Err(Error::UnsupportedOperation));
+    }
+
+    /// `ContentEncryptionAlgorithm`s for AES CBC HMAC SHA2 generate CEKs of the right length
+    #[test]
+    fn aes_cbc_hmac_sha2_key_length() {
+        use self::ContentEncryptionAlgorithm::*;
+
+        assert_eq!(not_err!(A128CBC_HS256.generate_key()).len(), 256 / 8);
+        assert_eq!(not_err!(A192CBC_HS384.generate_key()).len(), 384 / 8);
+        assert_eq!(not_err!(A256CBC_HS512.generate_key()).len(), 512 / 8);
+    }
+
+    /// Test cases from [RFC7518 Appendix B](https://tools.ietf.org/html/rfc7518#appendix-B)
+    #[test]
+    fn aes_cbc_hmac_sha2_rfc7518_test_cases() {
+        use self::ContentEncryptionAlgorithm::*;
+
+        let payload = "A cipher system must not be required to be secret, and it must be able to fall into the \
+                       hands of the enemy without inconvenience";
+        let aad = "The second principle of Auguste Kerckhoffs";
+        let iv = hex("1a f3 8c 2d c2 b9 6f fd d8 66 94 09 23 41 bc 04");
+        let test_cases = [(A128CBC_HS256,
+                           "c8 0e df a3 2d df 39 d5 ef 00 c0 b4 68 83 42 79
+                            a2 e4 6a 1b 80 49 f7 92 f7 6b fe 54 b9 03 a9 c9
+                            a9 4a c9 b4 7a d2 65 5c 5f 10 f9 ae f7 14 27 e2
+                            fc 6f 9b 3f 39 9a 22 14 89 f1 63 62 c7 03 23 36
+                            09 d4 5a c6 98 64 e3 32 1c f8 29 35 ac 40 96 c8
+                            6e 13 33 14 c5 40 19 e8 ca 79 80 df a4 b9 cf 1b
+                            38 4c 48 6f 3a 54 c5 10 78 15 8e e5 d7 9d e5 9f
+                            bd 34 d8 48 b3 d6 95 50 a6 76 46 34 44 27 ad e5
+                            4b 88 51 ff b5 98 f7 f8 00 74 b9 47 3c 82 e2 db",
+                           "65 2c 3f a3 6b 0a 7c 5b 32 19 fa b3 a3 0b c1 c4"),
+                          (A192CBC_HS384,
+                           "ea 65 da 6b 59 e6 1e db 41 9b e6 2d 19 71 2a e5
+                            d3 03 ee b5 00 52 d0 df d6 69 7f 77 22 4c 8e db
+                            00 0d 27 9b dc 14 c1 07 26 54 bd 30 94 42 30 c6
+                            57 be d4 ca 0c 9f 4a 84 66 f2 2b 22 6d 17 46 21
+                            4b f8 cf c2 40 0a dd 9f 51 26 e4 79 66 3f c9 0b
+                            3b ed 78 7a 2f 0f fc bf 39 04 be 2a 64 1d 5c 21
+                            05 bf e5 91 ba e2 3b 1d 74 49 e5 32 ee f6 0a 9a
+                            c8 bb 6c 6b 01 d3 5d 49 78 7b cd 57 ef 48 49 27
+                            f2 80 ad c9 1a c0 c4 e7 9c 7b 11 ef c6 00 54 e3",
+                           "84 90 ac 0e 58 94 9b fe 51 87 5d 73 3f 93 ac 20 75 16 80 39 cc c7 33 d7"),
+                          (A256CBC_HS512,
+                           "4a ff aa ad b7 8c 31 c5 da 4b 1b 59 0d 10 ff bd
+                            3d d8 d5 d3 02 42 35 26 91 2d a0 37 ec bc c7 bd
+                            82 2c 30 1d d6 7c 37 3b cc b5 84 ad 3e 92 79 c2
+                            e6 d1 2a 13 74 b7 7f 07 75 53 df 82 94 10 44 6b
+                            36 eb d9 70 66 29 6a e6 42 7e a7 5c 2e 08 46 a1
+                            1a 09 cc f5 37 0d c8 0b fe cb ad 28 c7 3f 09 b3
+                            a3 b7 5e 66 2a 25 94 41 0a e4 96 b2 e2 e6 60 9e
+                            31 e6 e0 2c c8 37 f0 53 d2 1f 37 ff 4f 51 95 0b
+                            be 26 38 d0 9d d7 a4 93 09 30 80 6d 07 03 b1 f6",
+                           "4d d3 b4 c0 88 a7 f4 5c 21 68 39 64 5b 20 12 bf
+                            2e 62 69 a8 c5 6a 81 6d bc 1b 26 77 61 95 5b c5")];
+
+        for &(enc_alg, ciphertext, tag) in &test_cases {
+            let key_length = not_err!(enc_alg.key_length());
+            let key = jwk::JWK::<::Empty>::new_octect_key(&(0..key_length as u8).collect::<Vec<u8>>(),
+                                                           Default::default());
+
+            let encrypted = not_err!(enc_alg.aes_cbc_hmac_sha2_encrypt(payload.as_bytes(), aad.as_bytes(), &iv, &key));
+            assert_eq!(encrypted.encrypted, hex(ciphertext));
+            assert_eq!(encrypted.tag, hex(tag));
+
+            let decrypted = not_err!(enc_alg.decrypt(&encrypted, &key));
+            assert_eq!(decrypted, payload.as_bytes());
+        }
+    }
+
+    #[test]
+    fn aes_cbc_hmac_sha2_encryption_round_trip() {
+        use self::ContentEncryptionAlgorithm::*;
+
+        let payload = "狼よ、我が敵を食らえ！";
+        let aad = "My servants never die!";
+        for enc_alg in &[A128CBC_HS256, A192CBC_HS384, A256CBC_HS512] {
+            let key = jwk::JWK::<::Empty>::new_octect_key(&not_err!(enc_alg.generate_key()), Default::default());
+            let encrypted_payload = not_err!(enc_alg.encrypt(payload.as_bytes(), aad.as_bytes(), &key));
+            assert_eq!(encrypted_payload.nonce.len(), 128 / 8);
+
+            let decrypted_payload = not_err!(enc_alg.decrypt(&encrypted_payload, &key));
+            assert!(verify_slices_are_equal(payload.as_bytes(), &decrypted_payload).is_ok());
+        }
+    }
+
+    #[test]
+    fn aes_cbc_hmac_sha2_rejects_invalid_input() {
+        let enc_alg = ContentEncryptionAlgorithm::A128CBC_HS256;
+        let key = jwk::JWK::<::Empty>::new_octect_key(&not_err!(enc_alg.generate_key()), Default::default());
+        let encrypted_payload = not_err!(enc_alg.encrypt(b"payload", b"aad", &key));
+
+        let mut modified_tag = encrypted_payload.clone();
+        modified_tag.tag[0] ^= 0x01;
+        assert_matches!(enc_alg.decrypt(&modified_tag, &key), Err(Error::UnspecifiedCryptographicError));
+
+        let mut modified_aad = encrypted_payload.clone();
+        modified_aad.additional_data = b"aae".to_vec();
+        assert_matches!(enc_alg.decrypt(&modified_aad, &key), Err(Error::UnspecifiedCryptographicError));
+
+        let wrong_length_key = jwk::JWK::<::Empty>::new_octect_key(&[0; 16], Default::default());
+        assert_matches!(enc_alg.encrypt(b"payload", b"aad", &wrong_length_key), Err(Error::GenericError(_)));
+    }
+
+    /// The ephemeral public key of Alice in [RFC7518 Appendix C](https://tools.ietf.org/html/rfc7518#appendix-C)
+    fn rfc7518_ephemeral_public_key() -> jwk::JWK<::Empty> {
+        not_err!(::serde_json::from_str(r#"{
+            "kty": "EC",
+            "crv": "P-256",
+            "x": "gI0GAILBdu7T53akrFmMyGcsF3n5dO7MmwNBHKW5SV0",
+            "y": "SLW_xSffzlPWrHEVI30DHM_4egVwt3NQqeUD7nMFpps"
+        }"#))
+    }
+
+    /// The private key of Bob in [RFC7518 Appendix C](https://tools.ietf.org/html/rfc7518#appendix-C)
+    fn rfc7518_recipient_private_key() -> jwk::JWK<::Empty> {
+        not_err!(::serde_json::from_str(r#"{
+            "kty": "EC",
+            "crv": "P-256",
+            "x": "weNJy2HscCSM6AEDTDg04biOvhFhyyWvOHQfeF_PxMQ",
+            "y": "e8lnCO-AlStT-NJVX-crhB7QRYhiix03illJOVAOyck",
+            "d": "VEmDZpDXXK8p8N0Cndsxs924q6nS1RXFASRl6BfUqdw"
+        }"#))
+    }
+
+    /// Example from [RFC7518 Appendix C](https://tools.ietf.org/html/rfc7518#appendix-C)
+    #[test]
+    fn ecdh_es_rfc7518_example() {
+        use data_encoding::base64url;
+
+        let cek = not_err!(KeyManagementAlgorithm::ECDH_ES.agree_key_for_decryption(ContentEncryptionAlgorithm::A128GCM,
+                                                                                   &rfc7518_ephemeral_public_key(),
+                                                                                   &rfc7518_recipient_private_key(),
+                                                                                   b"Alice",
+                                                                                   b"Bob"));
+        assert_eq!(&*base64url::encode_nopad(not_err!(cek.octect_key())), "VqqN6vgjbSBcIijNcacQGg");
+    }
+
+    #[test]
+    fn ecdh_es_key_agreement_round_trip() {
+        let recipient_key = rfc7518_recipient_private_key();
+        let mut recipient_public_key = recipient_key.clone();
+        match recipient_public_key.algorithm {
+            jwk::AlgorithmParameters::EllipticCurve(ref mut parameters) => parameters.d = None,
+            _ => unreachable!(),
+        }
+
+        for &enc_alg in &[ContentEncryptionAlgorithm::A128GCM, ContentEncryptionAlgorithm::A256CBC_HS512] {
+            let (cek, ephemeral_public_key) =
+                not_err!(KeyManagementAlgorithm::ECDH_ES.agree_key_for_encryption(enc_alg,
+                                                                                  &recipient_public_key,
+                                                                                  b"Alice",
+                                                                                  b"Bob"));
+            assert_eq!(not_err!(cek.octect_key()).len(), not_err!(enc_alg.key_length()));
+            match ephemeral_public_key.algorithm {
+                jwk::AlgorithmParameters::EllipticCurve(ref parameters) => assert!(parameters.d.is_none()),
+                _ => panic!("The ephemeral public key should be an Elliptic Curve key"),
+            }
+
+            let agreed_cek = not_err!(KeyManagementAlgorithm::ECDH_ES.agree_key_for_decryption(enc_alg,
+                                                                                                &ephemeral_public_key,
+                                                                                                &recipient_key,
+                                                                                                b"Alice",
+                                                                                                b"Bob"));
+            assert_eq!(agreed_cek, cek);
+        }
+    }
+
+    /// Example from [RFC 8037 Appendix A.6](https://tools.ietf.org/html/rfc8037#appendix-A.6)
+    #[test]
+    fn x25519_rfc8037_example() {
+        let ephemeral_public_key: jwk::JWK<::Empty> = not_err!(::serde_json::from_str(r#"{
+            "kty": "OKP",
+            "crv": "X25519",
+            "x": "hSDwCYkwp1R0i33ctD73Wg2_Og0mOBr066SpjqqbTmo"
+        }"#));
+        let recipient_key: jwk::JWK<::Empty> = not_err!(::serde_json::from_str(r#"{
+            "kty": "OKP",
+            "crv": "X25519",
+            "kid": "Bob",
+            "x": "3p7bfXt9wbTTW2HC7OQ1Nz-DQ8hbeGdNrfx-FG-IK08",
+            "d": "XasIfmJKikt54X-Lg4AO5m87sSkmGLb9HC-LJ_-I4Os"
+        }"#));
+
+        let shared_secret = match (&ephemeral_public_key.algorithm, &recipient_key.algorithm) {
+            (&jwk::AlgorithmParameters::OctetKeyPair(ref ephemeral_parameters),
+             &jwk::AlgorithmParameters::OctetKeyPair(ref parameters)) => {
+                not_err!(x25519_static(ephemeral_parameters, parameters))
+            }
+            _ => unreachable!(),
+        };
+        assert_eq!(shared_secret,
+                   hex("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742"));
+    }
+
+    #[test]
+    fn ecdh_es_rejects_signature_keys() {
+        let key: jwk::JWK<::Empty> = not_err!(::serde_json::from_str(r#"{
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"
+        }"#));
+
+        let cek_alg = KeyManagementAlgorithm::ECDH_ES_A128KW;
+        let result = cek_alg.agree_key_for_encryption(ContentEncryptionAlgorithm::A128GCM, &key, &[], &[]);
+        assert_matches!(result, Err(Error::GenericError(_)));
+    }
+
+    #[test]
+    fn ecdh_es_rejects_point_not_on_curve() {
+        let mut ephemeral_public_key = rfc7518_ephemeral_public_key();
+        match ephemeral_public_key.algorithm {
+            jwk::AlgorithmParameters::EllipticCurve(ref mut parameters) => parameters.y[0] ^= 0x01,
+            _ => unreachable!(),
+        }
+
+        let result = KeyManagementAlgorithm::ECDH_ES.agree_key_for_decryption(ContentEncryptionAlgorithm::A128GCM,
+                                                                              &ephemeral_public_key,
+                                                                              &rfc7518_recipient_private_key(),
+                                                                              b"Alice",
+                                                                              b"Bob");
+        assert_matches!(result, Err(Error::GenericError(_)));
+    }
+
+    #[test]
+    fn ecdh_es_rejects_ephemeral_key_on_another_curve() {
+        let mut ephemeral_public_key = rfc7518_ephemeral_public_key();
+        match ephemeral_public_key.algorithm {
+            jwk::AlgorithmParameters::EllipticCurve(ref mut parameters) => {
+                parameters.curve = jwk::EllipticCurve::P384
+            }
+            _ => unreachable!(),
+        }
+
+        let result = KeyManagementAlgorithm::ECDH_ES.agree_key_for_decryption(ContentEncryptionAlgorithm::A128GCM,
+                                                                              &ephemeral_public_key,
+                                                                              &rfc7518_recipient_private_key(),
+                                                                              &[],
+                                                                              &[]);
+        assert_matches!(result, Err(Error::GenericError(_)));
+    }
+
+    /// Example from [RFC7517 Appendix C](https://tools.ietf.org/html/rfc7517#appendix-C)
+    #[test]
+    fn pbes2_hs256_a128kw_rfc7517_example() {
+        use data_encoding::base64url;
+
+        let password = jwk::JWK::<::Empty>::new_octect_key(b"Thus from my lips, by yours, my sin is purged.",
+                                                           Default::default());
+        let salt_input = not_err!(base64url::decode_nopad(b"2WCTcJZ1Rvd_CJuJripQ1w"));
+        let cek_alg = KeyManagementAlgorithm::PBES2_HS256_A128KW;
+
+        let derived_key = not_err!(cek_alg.pbes2_derive_key(&password, &salt_input, 4096));
+        assert_eq!(not_err!(derived_key.algorithm.octect_key()),
+                   &[110, 171, 169, 92, 129, 92, 109, 117, 233, 242, 116, 233, 170, 14, 24, 75]);
+
+        let cek = [111, 27, 25, 52, 66, 29, 20, 78, 92, 176, 56, 240, 65, 208, 82, 112, 161, 131, 36, 55, 202, 236,
+                   185, 172, 129, 23, 153, 194, 195, 48, 253, 182];
+        let encrypted_cek = not_err!(cek_alg.encrypt(&cek, &derived_key));
+        assert_eq!(encrypted_cek.encrypted,
+                   not_err!(base64url::decode_nopad(b"TrqXOwuNUfDV9VPTNbyGvEJ9JMjefAVn-TR1uIxR9p6hsRQh9Tk7BA")));
+
+        let decrypted_cek = not_err!(cek_alg.decrypt(&encrypted_cek,
+                                                     ContentEncryptionAlgorithm::A128CBC_HS256,
+                                                     &derived_key));
+        assert_eq!(not_err!(decrypted_cek.algorithm.octect_key()), &cek[..]);
+    }
+
+    #[test]
+    fn pbes2_rejects_short_salt_input_and_zero_iteration_count() {
+        let password = jwk::JWK::<::Empty>::new_octect_key(b"password", Default::default());
+        let cek_alg = KeyManagementAlgorithm::PBES2_HS384_A192KW;
+
+        assert_matches!(cek_alg.pbes2_derive_key(&password, &[0; 7], 1000),
+                        Err(Error::GenericError(_)));
+        assert_matches!(cek_alg.pbes2_derive_key(&password, &[0; 8], 0),
+                        Err(Error::GenericError(_)));
+        let derived_key = not_err!(cek_alg.pbes2_derive_key(&password, &[0; 8], 1000));
+        assert_eq!(not_err!(derived_key.algorithm.octect_key()).len(), 24);
+    }
 }
@@ -4,14 +4,21 @@
 //! Most commonly, JWE is used to encrypt a JWS payload, which is a signed JWT. For most common use,
 //! you will want to look at the  [`Compact`](enum.Compact.html) enum.
 use std::fmt;
+use std::io::{Read, Write};
+
+use data_encoding::base64url;
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
 
 use serde::{self, Serialize, Deserialize, Serializer, Deserializer};
 use serde::de::{self, DeserializeOwned};
 use serde_json;
 
 use {CompactJson, CompactPart, Empty};
+use critical;
 use errors::{Error, ValidationError};
-use jwa::{KeyManagementAlgorithm, ContentEncryptionAlgorithm, EncryptionResult};
+use jwa::{self, KeyManagementAlgorithm, KeyManagementAlgorithmType, ContentEncryptionAlgorithm, EncryptionResult};
 use jwk;
 use serde_custom;
 
@@ -65,6 +72,38 @@ impl<'de> Deserialize<'de> for CompressionAlgorithm {
     }
 }
 
+impl CompressionAlgorithm {
+    /// Compress the plaintext before encryption
+    fn compress(&self, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        match *self {
+            CompressionAlgorithm::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(payload)?;
+                Ok(encoder.finish()?)
+            }
+            CompressionAlgorithm::Other(_) => Err(Error::UnsupportedOperation),
+        }
+    }
+
+    /// Decompress the plaintext after decryption. Returns `Error::DecompressionLimitExceeded` if the decompressed
+    /// plaintext is larger than `limit` bytes.
+    fn decompress(&self, compressed: &[u8], limit: usize) -> Result<Vec<u8>, Error> {
+        match *self {
+            CompressionAlgorithm::Deflate => {
+                // Read one byte more than the limit to detect payloads exceeding it, without inflating them fully
+                let mut decoder = DeflateDecoder::new(compressed).take(limit as u64 + 1);
+                let mut payload = vec![];
+                decoder.read_to_end(&mut payload)?;
+                if payload.len() > limit {
+                    Err(Error::DecompressionLimitExceeded { limit: limit })?;
+                }
+                Ok(payload)
+            }
+            CompressionAlgorithm::Other(_) => Err(Error::UnsupportedOperation),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
 /// Registered JWE header fields.
 /// The fields are defined by [RFC 7516#4.1](https://tools.ietf.org/html/rfc7516#section-4.1)
@@ -79,7 +118,7 @@ pub struct RegisteredHeader {
     pub enc_algorithm: ContentEncryptionAlgorithm,
 
     /// Compression algorithm applied to plaintext before encryption, if any.
-    /// Compression is not supported at the moment.
+    /// Only `DEF` is supported.
     /// _Must only appear in integrity protected header._
     #[serde(rename = "zip", skip_serializing_if = "Option::is_none")]
     pub compression_algorithm: Option<CompressionAlgorithm>,
@@ -143,26 +182,117 @@ pub struct RegisteredHeader {
     pub x509_fingerprint: Option<String>,
 
     /// List of critical extended headers.
-    /// This is currently not implemented (correctly).
+    /// A JWE is rejected when decrypting if it lists extensions that are not in the
+    /// `understood_critical_headers` of the [`DecryptionOptions`].
     /// Serialized to `crit`.
     /// Defined in [RFC7515#4.1.11](https://tools.ietf.org/html/rfc7515#section-4.1.11).
     #[serde(rename = "crit", skip_serializing_if = "Option::is_none")]
     pub critical: Option<Vec<String>>,
 }
 
+/// Header parameters defined for JWE, which cannot be listed in `crit`
+const REGISTERED_HEADERS: &[&str] = &["alg", "enc", "zip", "jku", "jwk", "kid", "x5u", "x5c", "x5t", "x5t#S256",
+                                      "typ", "cty", "crit", "epk", "apu", "apv", "iv", "tag", "p2s", "p2c"];
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
 /// Headers specific to the Key management algorithm used. Users should typically not construct these fields as they
-/// will be filled in automatically when encrypting and stripped when decrypting
+/// will be filled in automatically when encrypting and stripped when decrypting. The exceptions are `apu` and `apv`,
+/// which can be provided by the producer for the ECDH-ES key agreement algorithms, and `p2c`, which can be provided
+/// by the producer for the PBES2 algorithms.
 pub struct CekAlgorithmHeader {
     /// Header for AES GCM Keywrap algorithm.
     /// The initialization vector, or nonce used in the encryption
-    #[serde(rename = "iv", skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "iv", with = "serde_custom::option_byte_sequence", skip_serializing_if = "Option::is_none",
+            default)]
     pub nonce: Option<Vec<u8>>,
 
     /// Header for AES GCM Keywrap algorithm.
     /// The authentication tag resulting from the encryption
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(with = "serde_custom::option_byte_sequence", skip_serializing_if = "Option::is_none", default)]
     pub tag: Option<Vec<u8>>,
+
+    /// Header for ECDH-ES key agreement algorithms.
+    /// The ephemeral public key created by the producer. Serialized to `epk`.
+    /// Defined in [RFC7518#4.6.1.1](https://tools.ietf.org/html/rfc7518#section-4.6.1.1).
+    #[serde(rename = "epk", skip_serializing_if = "Option::is_none", default)]
+    pub ephemeral_public_key: Option<jwk::JWK<Empty>>,
+
+    /// Header for ECDH-ES key agreement algorithms.
+    /// Agreement PartyUInfo, containing information about the producer. Serialized to `apu`.
+    /// Defined in [RFC7518#4.6.1.2](https://tools.ietf.org/html/rfc7518#section-4.6.1.2).
+    #[serde(rename = "apu", with = "serde_custom::option_byte_sequence", skip_serializing_if = "Option::is_none",
+            default)]
+    pub party_u_info: Option<Vec<u8>>,
+
+    /// Header for ECDH-ES key agreement algorithms.
+    /// Agreement PartyVInfo, containing information about the recipient. Serialized to `apv`.
+    /// Defined in [RFC7518#4.6.1.3](https://tools.ietf.org/html/rfc7518#section-4.6.1.3).
+    #[serde(rename = "apv", with = "serde_custom::option_byte_sequence", skip_serializing_if = "Option::is_none",
+            default)]
+    pub party_v_info: Option<Vec<u8>>,
+
+    /// Header for PBES2 algorithms.
+    /// The salt input, generated randomly when encrypting. Serialized to `p2s`.
+    /// Defined in [RFC7518#4.8.1.1](https://tools.ietf.org/html/rfc7518#section-4.8.1.1).
+    #[serde(rename = "p2s", with = "serde_custom::option_byte_sequence", skip_serializing_if = "Option::is_none",
+            default)]
+    pub salt_input: Option<Vec<u8>>,
+
+    /// Header for PBES2 algorithms.
+    /// The PBKDF2 iteration count. When encrypting, defaults to `DEFAULT_PBES2_ITERATION_COUNT` if not provided.
+    /// Serialized to `p2c`.
+    /// Defined in [RFC7518#4.8.1.2](https://tools.ietf.org/html/rfc7518#section-4.8.1.2).
+    #[serde(rename = "p2c", skip_serializing_if = "Option::is_none")]
+    pub iteration_count: Option<u32>,
+}
+
+/// Length of the PBES2 salt input generated when encrypting, in bytes
+const PBES2_SALT_INPUT_LENGTH: usize = 128 / 8;
+
+/// PBES2 iteration count used when encrypting if the `p2c` header parameter is not provided
+pub const DEFAULT_PBES2_ITERATION_COUNT: u32 = 100_000;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+/// Options for encrypting a JWE
+pub struct EncryptionOptions {
+    /// Whether the deprecated `RSA1_5` key management algorithm can be used. Defaults to `false`.
+    pub allow_rsa1_5: bool,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+/// Options for decrypting a JWE.
+///
+/// The PBES2 iteration count (`p2c`) is chosen by the producer, and a huge value could be used to make the consumer
+/// spend a lot of time deriving the key. JWEs with an iteration count outside of the configured bounds are rejected
+/// before deriving the key. Similarly, a small compressed payload can inflate to a huge plaintext, so decompression
+/// stops once the plaintext exceeds the configured size.
+pub struct DecryptionOptions<'a> {
+    /// Minimum PBES2 iteration count accepted. Defaults to 1000, as recommended by
+    /// [RFC7518#4.8.1.2](https://tools.ietf.org/html/rfc7518#section-4.8.1.2).
+    pub pbes2_min_iteration_count: u32,
+    /// Maximum PBES2 iteration count accepted. Defaults to 1,000,000.
+    pub pbes2_max_iteration_count: u32,
+    /// Maximum size of the decompressed plaintext, in bytes. Defaults to 1 MiB.
+    pub max_decompressed_size: usize,
+    /// Names of the extension header parameters understood by the application. A JWE is rejected with
+    /// `ValidationError::InvalidCriticalHeader` if its `crit` header parameter lists any other extension.
+    /// Defaults to none.
+    pub understood_critical_headers: &'a [&'a str],
+    /// Whether the deprecated `RSA1_5` key management algorithm can be used. Defaults to `false`.
+    /// Decryption with `RSA1_5` also requires the `rsa-decryption` feature.
+    pub allow_rsa1_5: bool,
+}
+
+impl<'a> Default for DecryptionOptions<'a> {
+    fn default() -> Self {
+        DecryptionOptions {
+            pbes2_min_iteration_count: 1000,
+            pbes2_max_iteration_count: 1_000_000,
+            max_decompressed_size: 1024 * 1024,
+            understood_critical_headers: &[],
+            allow_rsa1_5: false,
+        }
+    }
 }
 
 /// JWE Header, consisting of the registered fields and other custom fields
@@ -211,6 +341,167 @@ impl<T: Serialize + DeserializeOwned> Header<T> {
         self.cek_algorithm = Default::default();
         result
     }
+
+    /// Determine the Content Encryption Key (CEK) for the recipient with `key`, and encrypt it.
+    ///
+    /// The CEK is generated or agreed upon, unless `cek` is provided, which is used when encrypting to several
+    /// recipients. The header is updated with the key management algorithm specific header fields.
+    fn encrypt_cek<K: Serialize + DeserializeOwned>(&mut self,
+                                                    key: &jwk::JWK<K>,
+                                                    cek: Option<&jwk::JWK<Empty>>,
+                                                    options: EncryptionOptions)
+                                                    -> Result<(jwk::JWK<Empty>, EncryptionResult), Error> {
+        let cek_algorithm = self.registered.cek_algorithm;
+        let enc_algorithm = self.registered.enc_algorithm;
+        let algorithm_type = cek_algorithm.algorithm_type();
+        check_rsa1_5_allowed(cek_algorithm, options.allow_rsa1_5)?;
+        if cek.is_some() &&
+           (algorithm_type == KeyManagementAlgorithmType::DirectEncryption ||
+            algorithm_type == KeyManagementAlgorithmType::DirectKeyAgreement) {
+            Err(format!("{:?} can only be used with a single recipient", cek_algorithm))?;
+        }
+
+        let (cek, encrypted_cek) = match algorithm_type {
+            KeyManagementAlgorithmType::DirectKeyAgreement => {
+                // The CEK is agreed upon, and the JWE Encrypted Key is empty
+                let (cek, ephemeral_public_key) =
+                    cek_algorithm.agree_key_for_encryption(enc_algorithm,
+                                                           key,
+                                                           party_info(&self.cek_algorithm.party_u_info),
+                                                           party_info(&self.cek_algorithm.party_v_info))?;
+                self.cek_algorithm.ephemeral_public_key = Some(ephemeral_public_key);
+                (cek, Default::default())
+            }
+            KeyManagementAlgorithmType::KeyAgreementWithKeyWrapping => {
+                // A random CEK is wrapped with the agreed key
+                let (agreed_key, ephemeral_public_key) =
+                    cek_algorithm.agree_key_for_encryption(enc_algorithm,
+                                                           key,
+                                                           party_info(&self.cek_algorithm.party_u_info),
+                                                           party_info(&self.cek_algorithm.party_v_info))?;
+                self.cek_algorithm.ephemeral_public_key = Some(ephemeral_public_key);
+                let cek = match cek {
+                    Some(cek) => cek.clone(),
+                    None => cek_algorithm.cek(enc_algorithm, key)?,
+                };
+                let encrypted_cek = cek_algorithm.encrypt(cek.algorithm.octect_key()?, &agreed_key)?;
+                (cek, encrypted_cek)
+            }
+            KeyManagementAlgorithmType::SymmetricKeyWrapping if is_pbes2(cek_algorithm) => {
+                // A random CEK is wrapped with the key derived from the password and a new random salt
+                let mut salt_input = vec![0; PBES2_SALT_INPUT_LENGTH];
+                jwa::rng().fill(&mut salt_input)?;
+                let iteration_count = self.cek_algorithm
+                    .iteration_count
+                    .unwrap_or(DEFAULT_PBES2_ITERATION_COUNT);
+                let derived_key = cek_algorithm.pbes2_derive_key(key, &salt_input, iteration_count)?;
+                self.cek_algorithm.salt_input = Some(salt_input);
+                self.cek_algorithm.iteration_count = Some(iteration_count);
+
+                let cek = match cek {
+                    Some(cek) => cek.clone(),
+                    None => cek_algorithm.cek(enc_algorithm, key)?,
+                };
+                let encrypted_cek = cek_algorithm.encrypt(cek.algorithm.octect_key()?, &derived_key)?;
+                (cek, encrypted_cek)
+            }
+            _ => {
+                let cek = match cek {
+                    Some(cek) => cek.clone(),
+                    None => cek_algorithm.cek(enc_algorithm, key)?,
+                };
+                let encrypted_cek = cek_algorithm.encrypt(cek.algorithm.octect_key()?, key)?;
+                (cek, encrypted_cek)
+            }
+        };
+        self.update_cek_algorithm(&encrypted_cek);
+        Ok((cek, encrypted_cek))
+    }
+
+    /// Decrypt the Content Encryption Key (CEK) with `key`, and strip the key management algorithm specific header
+    /// fields from the header. The algorithms of the header must have been verified.
+    fn decrypt_cek<K: Serialize + DeserializeOwned>(&mut self,
+                                                    encrypted_cek: &[u8],
+                                                    key: &jwk::JWK<K>,
+                                                    options: DecryptionOptions)
+                                                    -> Result<jwk::JWK<Empty>, Error> {
+        let cek_algorithm = self.registered.cek_algorithm;
+        let enc_algorithm = self.registered.enc_algorithm;
+        let algorithm_type = cek_algorithm.algorithm_type();
+        check_rsa1_5_allowed(cek_algorithm, options.allow_rsa1_5)?;
+        match algorithm_type {
+            KeyManagementAlgorithmType::DirectKeyAgreement |
+            KeyManagementAlgorithmType::KeyAgreementWithKeyWrapping => {
+                // Step 10: the JWE Encrypted Key must be empty with Direct Key Agreement
+                if algorithm_type == KeyManagementAlgorithmType::DirectKeyAgreement &&
+                   !encrypted_cek.is_empty() {
+                    Err("The JWE Encrypted Key must be empty with Direct Key Agreement".to_string())?;
+                }
+                let ephemeral_public_key = self.cek_algorithm
+                    .ephemeral_public_key
+                    .clone()
+                    .ok_or_else(|| "The `epk` header parameter is missing".to_string())?;
+                let agreed_key =
+                    cek_algorithm.agree_key_for_decryption(enc_algorithm,
+                                                           &ephemeral_public_key,
+                                                           key,
+                                                           party_info(&self.cek_algorithm.party_u_info),
+                                                           party_info(&self.cek_algorithm.party_v_info))?;
+
+                let cek_encryption_result = self.extract_cek_encryption_result(encrypted_cek);
+                match algorithm_type {
+                    KeyManagementAlgorithmType::DirectKeyAgreement => Ok(agreed_key),
+                    _ => cek_algorithm.decrypt(&cek_encryption_result, enc_algorithm, &agreed_key),
+                }
+            }
+            KeyManagementAlgorithmType::SymmetricKeyWrapping if is_pbes2(cek_algorithm) => {
+                let salt_input = self.cek_algorithm
+                    .salt_input
+                    .clone()
+                    .ok_or_else(|| ValidationError::MissingRequired("p2s".to_string()))?;
+                let iteration_count = self.cek_algorithm
+                    .iteration_count
+                    .ok_or_else(|| ValidationError::MissingRequired("p2c".to_string()))?;
+                // Check the iteration count before deriving the key to prevent denial of service
+                if iteration_count < options.pbes2_min_iteration_count ||
+                   iteration_count > options.pbes2_max_iteration_count {
+                    Err(format!("The PBES2 iteration count {} is not between {} and {}",
+                                iteration_count,
+                                options.pbes2_min_iteration_count,
+                                options.pbes2_max_iteration_count))?;
+                }
+                let derived_key = cek_algorithm.pbes2_derive_key(key, &salt_input, iteration_count)?;
+
+                let cek_encryption_result = self.extract_cek_encryption_result(encrypted_cek);
+                cek_algorithm.decrypt(&cek_encryption_result, enc_algorithm, &derived_key)
+            }
+            _ => {
+                let cek_encryption_result = self.extract_cek_encryption_result(encrypted_cek);
+                cek_algorithm.decrypt(&cek_encryption_result, enc_algorithm, key)
+            }
+        }
+    }
+}
+
+/// Returns the value of the `apu` or `apv` header parameter, which is empty when the parameter is absent
+fn party_info(info: &Option<Vec<u8>>) -> &[u8] {
+    info.as_ref().map(|info| info.as_slice()).unwrap_or(&[])
+}
+
+/// Returns whether the key management algorithm derives its key encryption key from a password
+fn is_pbes2(algorithm: KeyManagementAlgorithm) -> bool {
+    matches!(algorithm,
+             KeyManagementAlgorithm::PBES2_HS256_A128KW |
+             KeyManagementAlgorithm::PBES2_HS384_A192KW |
+             KeyManagementAlgorithm::PBES2_HS512_A256KW)
+}
+
+/// Returns an error if the key management algorithm is the deprecated `RSA1_5`, unless the application allows it
+fn check_rsa1_5_allowed(algorithm: KeyManagementAlgorithm, allow_rsa1_5: bool) -> Result<(), Error> {
+    if algorithm == KeyManagementAlgorithm::RSA1_5 && !allow_rsa1_5 {
+        Err("RSA1_5 is deprecated and is only used when `allow_rsa1_5` is set in the options".to_string())?;
+    }
+    Ok(())
 }
 
 impl Header<Empty> {
@@ -316,6 +607,14 @@ impl<T: CompactPart, H: Serialize + DeserializeOwned + Clone> Compact<T, H> {
 
     /// Encrypt an Decrypted JWE
     pub fn encrypt<K: Serialize + DeserializeOwned>(&self, key: &jwk::JWK<K>) -> Result<Self, Error> {
+        self.encrypt_with_options(key, Default::default())
+    }
+
+    /// Encrypt an Decrypted JWE, like [`encrypt`](#method.encrypt), with the options set in `options`
+    pub fn encrypt_with_options<K: Serialize + DeserializeOwned>(&self,
+                                                                 key: &jwk::JWK<K>,
+                                                                 options: EncryptionOptions)
+                                                                 -> Result<Self, Error> {
         match *self {
             Compact::Encrypted(_) => Err(Error::UnsupportedOperation),
             Compact::Decrypted {
@@ -324,38 +623,31 @@ impl<T: CompactPart, H: Serialize + DeserializeOwned + Clone> Compact<T, H> {
             } => {
                 // RFC 7516 Section 5.1 describes the steps involved in encryption.
                 // From steps 1 to 8, we will first determine the CEK, and then encrypt the CEK.
-                let cek = header
-                    .registered
-                    .cek_algorithm
-                    .cek(header.registered.enc_algorithm, key)?;
-                let encrypted_cek = header
-                    .registered
-                    .cek_algorithm
-                    .encrypt(cek.algorithm.octect_key()?, key)?;
-                // Update header
                 let mut header = header.clone();
-                header.update_cek_algorithm(&encrypted_cek);
+                let (cek, encrypted_cek) = header.encrypt_cek(key, None, options)?;
 
                 // Steps 9 and 10 involves calculating an initialization vector (nonce) for content encryption. We do
                 // this as part of the encryption process later
 
-                // Step 11 involves compressing the payload, which we do not support at the moment
+                // Step 11 involves compressing the payload
                 let payload = payload.to_bytes()?;
-                if header.registered.compression_algorithm.is_some() {
-                    Err(Error::UnsupportedOperation)?
-                }
+                let payload = match header.registered.compression_algorithm {
+                    Some(ref compression_algorithm) => compression_algorithm.compress(&payload)?,
+                    None => payload,
+                };
 
                 // Steps 12 to 14 involves the calculation of `Additional Authenticated Data` for encryption. In
-                // our compact example, our header is the AAD.
+                // our compact example, the AAD is the ASCII of the base64url encoded protected header.
                 // Step 15 involves the actual encryption.
+                let protected_header = header.to_base64()?;
                 let encrypted_payload = header
                     .registered
                     .enc_algorithm
-                    .encrypt(&payload, &header.to_bytes()?, &cek)?;
+                    .encrypt(&payload, protected_header.as_ref(), &cek)?;
 
                 // Finally create the JWE
                 let mut compact = ::Compact::with_capacity(5);
-                compact.push(&header)?;
+                compact.push(&protected_header)?;
                 compact.push(&encrypted_cek.encrypted)?;
                 compact.push(&encrypted_payload.nonce)?;
                 compact.push(&encrypted_payload.encrypted)?;
@@ -381,11 +673,26 @@ impl<T: CompactPart, H: Serialize + DeserializeOwned + Clone> Compact<T, H> {
 
     /// Decrypt an encrypted JWE. Provide the expected algorithms to mitigate an attacker modifying the
     /// fields
+    ///
+    /// Decryption with `RSA-OAEP` and `RSA-OAEP-256` requires the `rsa-decryption` feature, because the `rsa` crate
+    /// is affected by the Marvin timing side channel
+    /// ([RUSTSEC-2023-0071](https://rustsec.org/advisories/RUSTSEC-2023-0071.html)). Without it, decryption fails
+    /// with an `Error::GenericError` naming the feature.
     pub fn decrypt<K: Serialize + DeserializeOwned>(&self,
                                                     key: &jwk::JWK<K>,
                                                     cek_alg: KeyManagementAlgorithm,
                                                     enc_alg: ContentEncryptionAlgorithm)
                                                     -> Result<Self, Error> {
+        self.decrypt_with_options(key, cek_alg, enc_alg, Default::default())
+    }
+
+    /// Decrypt an encrypted JWE, like [`decrypt`](#method.decrypt), with the limits set in `options`
+    pub fn decrypt_with_options<K: Serialize + DeserializeOwned>(&self,
+                                                                 key: &jwk::JWK<K>,
+                                                                 cek_alg: KeyManagementAlgorithm,
+                                                                 enc_alg: ContentEncryptionAlgorithm,
+                                                                 options: DecryptionOptions)
+                                                                 -> Result<Self, Error> {
         match *self {
             Compact::Encrypted(ref encrypted) => {
                 if encrypted.len() != 5 {
@@ -407,21 +714,22 @@ impl<T: CompactPart, H: Serialize + DeserializeOwned + Clone> Compact<T, H> {
                     Err(Error::ValidationError(ValidationError::WrongAlgorithmHeader))?;
                 }
 
-                // TODO: Steps 4-5 not implemented at the moment.
+                // Step 4 is not needed for the compact serialization, which only has a protected header.
+                // Step 5
+                let parameters: Vec<u8> = encrypted.part(0)?;
+                critical::validate(&serde_json::from_slice(&parameters)?,
+                                   REGISTERED_HEADERS,
+                                   options.understood_critical_headers)?;
 
                 // Steps 6-13 involve the computation of the cek
-                let cek_encryption_result = header.extract_cek_encryption_result(&encrypted_cek);
-                let cek = header
-                    .registered
-                    .cek_algorithm
-                    .decrypt(&cek_encryption_result, header.registered.enc_algorithm, key)?;
+                let cek = header.decrypt_cek(&encrypted_cek, key, options)?;
 
                 // Build encryption result as per steps 14-15
                 let encrypted_payload_result = EncryptionResult {
                     nonce: nonce,
                     tag: tag,
                     encrypted: encrypted_payload,
-                    additional_data: encrypted.part(0)?,
+                    additional_data: encrypted.parts[0].as_ref().to_vec(),
                 };
 
                 let payload = header
@@ -429,10 +737,13 @@ impl<T: CompactPart, H: Serialize + DeserializeOwned + Clone> Compact<T, H> {
                     .enc_algorithm
                     .decrypt(&encrypted_payload_result, &cek)?;
 
-                // Decompression is not supported at the moment
-                if header.registered.compression_algorithm.is_some() {
-                    Err(Error::UnsupportedOperation)?
-                }
+                // Step 17 involves decompressing the payload
+                let payload = match header.registered.compression_algorithm {
+                    Some(ref compression_algorithm) => {
+                        compression_algorithm.decompress(&payload, options.max_decompressed_size)?
+                    }
+                    None => payload,
+                };
 
                 let payload = T::from_bytes(&payload)?;
 
@@ -513,6 +824,603 @@ impl<T: CompactPart, H: Serialize + DeserializeOwned + Clone> Compact<T, H> {
     }
 }
 
+/// Names of the key management algorithm specific header parameters. They are always serialized to the
+/// per-recipient unprotected header of a [`Json`] JWE.
+const CEK_ALGORITHM_HEADERS: &[&str] = &["epk", "apu", "apv", "iv", "tag", "p2s", "p2c"];
+
+/// Header parameters of one of the parts of the header of a [`Json`] JWE
+type HeaderParameters = serde_json::Map<String, serde_json::Value>;
+
+/// JSON representation of a JWE, encrypted to one or more recipients
+///
+/// The payload is encrypted once with a Content Encryption Key (CEK), which is then encrypted to each recipient
+/// with its own key and key management algorithm. The encoded form can either be the General or the Flattened JWE
+/// JSON Serialization, as defined in [RFC7516#7.2](https://tools.ietf.org/html/rfc7516#section-7.2).
+///
+/// Serialization/deserialization is handled by serde. Before you transport the JWE, make sure you
+/// turn it into the encrypted form first.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Json<T, H> {
+    /// Decrypted form of the JWE.
+    /// This variant cannot be serialized or deserialized and will return an error.
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    Decrypted {
+        /// Payload, usually a signed/unsigned JWT
+        payload: T,
+        /// Headers of the recipients, in order. Once decrypted, only the header of the recipient whose key was
+        /// used is left.
+        recipients: Vec<JsonHeader<H>>,
+        /// Additional Authenticated Data, which is integrity protected but not encrypted. Serialized to `aad`.
+        additional_data: Option<Vec<u8>>,
+    },
+    /// Encrypted JWE. Use this form to send to your clients
+    Encrypted(JsonSerialization),
+}
+
+impl<T: CompactPart, H: Serialize + DeserializeOwned + Clone> Json<T, H> {
+    /// New decrypted JWE with one header for each recipient
+    pub fn new_decrypted(payload: T, recipients: Vec<JsonHeader<H>>, additional_data: Option<Vec<u8>>) -> Self {
+        Json::Decrypted {
+            payload: payload,
+            recipients: recipients,
+            additional_data: additional_data,
+        }
+    }
+
+    /// New encrypted JWE
+    pub fn new_encrypted(serialization: JsonSerialization) -> Self {
+        Json::Encrypted(serialization)
+    }
+
+    /// Encrypt the JWE in the General JWE JSON Serialization, encrypting the CEK once for each recipient.
+    ///
+    /// Each recipient uses the key management algorithm from its header and the key at the same position in `keys`.
+    /// The protected and shared unprotected header parameters must be the same for all recipients.
+    pub fn encrypt<K: Serialize + DeserializeOwned>(&self, keys: &[&jwk::JWK<K>]) -> Result<Self, Error> {
+        self.encrypt_with_options(keys, Default::default())
+    }
+
+    /// Encrypt the JWE in the General JWE JSON Serialization, like [`encrypt`](#method.encrypt), with the options
+    /// set in `options`
+    pub fn encrypt_with_options<K: Serialize + DeserializeOwned>(&self,
+                                                                 keys: &[&jwk::JWK<K>],
+                                                                 options: EncryptionOptions)
+                                                                 -> Result<Self, Error> {
+        match *self {
+            Json::Decrypted {
+                ref payload,
+                ref recipients,
+                ref additional_data,
+            } => {
+                if recipients.is_empty() {
+                    Err("A JWE must have at least one recipient".to_string())?;
+                }
+                if recipients.len() != keys.len() {
+                    Err(format!("{} keys were provided for {} recipients", keys.len(), recipients.len()))?;
+                }
+
+                // With more than one recipient, a random CEK is encrypted to each of them
+                let enc_algorithm = recipients[0].header.registered.enc_algorithm;
+                let mut cek = if recipients.len() > 1 {
+                    Some(jwk::JWK::new_octect_key(&enc_algorithm.generate_key()?, Default::default()))
+                } else {
+                    None
+                };
+
+                let mut shared_header = None;
+                let mut encrypted_recipients = vec![];
+                for (recipient, key) in recipients.iter().zip(keys) {
+                    let mut header = recipient.header.clone();
+                    let (recipient_cek, encrypted_cek) = header.encrypt_cek(*key, cek.as_ref(), options)?;
+                    cek = Some(recipient_cek);
+
+                    let (protected, unprotected, recipient_unprotected) = recipient.split(&header)?;
+                    match shared_header {
+                        None => shared_header = Some((header, protected, unprotected)),
+                        Some((_, ref shared_protected, ref shared_unprotected)) => {
+                            if *shared_protected != protected || *shared_unprotected != unprotected {
+                                Err("The protected and shared unprotected header parameters must be the same for \
+                                     all recipients"
+                                            .to_string())?;
+                            }
+                        }
+                    }
+
+                    encrypted_recipients.push(JsonRecipient {
+                                                  header: non_empty(recipient_unprotected),
+                                                  encrypted_key: if encrypted_cek.encrypted.is_empty() {
+                                                      None
+                                                  } else {
+                                                      Some(base64url::encode_nopad(&encrypted_cek.encrypted))
+                                                  },
+                                              });
+                }
+                let (header, protected, unprotected) = shared_header.ok_or(Error::UnsupportedOperation)?;
+                let cek = cek.ok_or(Error::UnsupportedOperation)?;
+
+                // The `zip` header parameter is integrity protected, so it is the same for all recipients
+                let payload = payload.to_bytes()?;
+                let payload = match header.registered.compression_algorithm {
+                    Some(ref compression_algorithm) => compression_algorithm.compress(&payload)?,
+                    None => payload,
+                };
+
+                let protected = if protected.is_empty() {
+                    None
+                } else {
+                    Some(base64url::encode_nopad(&serde_json::to_vec(&protected)?))
+                };
+                let aad = additional_data.as_ref().map(|aad| base64url::encode_nopad(aad));
+                let encrypted_payload =
+                    enc_algorithm.encrypt(&payload, &additional_authenticated_data(&protected, &aad), &cek)?;
+
+                Ok(Json::Encrypted(JsonSerialization::General {
+                                       protected: protected,
+                                       unprotected: non_empty(unprotected),
+                                       recipients: encrypted_recipients,
+                                       aad: aad,
+                                       iv: base64url::encode_nopad(&encrypted_payload.nonce),
+                                       ciphertext: base64url::encode_nopad(&encrypted_payload.encrypted),
+                                       tag: base64url::encode_nopad(&encrypted_payload.tag),
+                                   }))
+            }
+            Json::Encrypted(_) => Err(Error::UnsupportedOperation),
+        }
+    }
+
+    /// Encrypt the JWE in the Flattened JWE JSON Serialization. The JWE must have exactly one recipient.
+    pub fn encrypt_flattened<K: Serialize + DeserializeOwned>(&self, key: &jwk::JWK<K>) -> Result<Self, Error> {
+        self.encrypt_flattened_with_options(key, Default::default())
+    }
+
+    /// Encrypt the JWE in the Flattened JWE JSON Serialization, like [`encrypt_flattened`](#method.encrypt_flattened),
+    /// with the options set in `options`
+    pub fn encrypt_flattened_with_options<K: Serialize + DeserializeOwned>(&self,
+                                                                           key: &jwk::JWK<K>,
+                                                                           options: EncryptionOptions)
+                                                                           -> Result<Self, Error> {
+        match *self {
+            Json::Decrypted { ref recipients, .. } => {
+                if recipients.len() != 1 {
+                    Err(format!("The flattened serialization requires exactly one recipient, but there are {}",
+                                recipients.len()))?;
+                }
+
+                match self.encrypt_with_options(&[key], options)? {
+                    Json::Encrypted(JsonSerialization::General {
+                                        protected,
+                                        unprotected,
+                                        mut recipients,
+                                        aad,
+                                        iv,
+                                        ciphertext,
+                                        tag,
+                                    }) => {
+                        let recipient = recipients.remove(0);
+                        Ok(Json::Encrypted(JsonSerialization::Flattened {
+                                               protected: protected,
+                                               unprotected: unprotected,
+                                               header: recipient.header,
+                                               encrypted_key: recipient.encrypted_key,
+                                               aad: aad,
+                                               iv: iv,
+                                               ciphertext: ciphertext,
+                                               tag: tag,
+                                           }))
+                    }
+                    _ => Err(Error::UnsupportedOperation),
+                }
+            }
+            Json::Encrypted(_) => Err(Error::UnsupportedOperation),
+        }
+    }
+
+    /// Decrypt an encrypted JWE. Provide the expected algorithms to mitigate an attacker modifying the
+    /// fields
+    ///
+    /// The recipient is found among the recipients using the expected algorithms and the key ID (`kid`) of the
+    /// key, if any. If several recipients match, each of them is tried in order until one can be decrypted with the
+    /// key. Otherwise, the error for the first recipient that could not be decrypted is returned.
+    ///
+    /// Decryption with `RSA-OAEP` and `RSA-OAEP-256` requires the `rsa-decryption` feature, because the `rsa` crate
+    /// is affected by the Marvin timing side channel
+    /// ([RUSTSEC-2023-0071](https://rustsec.org/advisories/RUSTSEC-2023-0071.html)). Without it, decryption fails
+    /// with an `Error::GenericError` naming the feature.
+    pub fn decrypt<K: Serialize + DeserializeOwned>(&self,
+                                                    key: &jwk::JWK<K>,
+                                                    cek_alg: KeyManagementAlgorithm,
+                                                    enc_alg: ContentEncryptionAlgorithm)
+                                                    -> Result<Self, Error> {
+        self.decrypt_with_options(key, cek_alg, enc_alg, Default::default())
+    }
+
+    /// Decrypt an encrypted JWE, like [`decrypt`](#method.decrypt), with the limits set in `options`
+    pub fn decrypt_with_options<K: Serialize + DeserializeOwned>(&self,
+                                                                 key: &jwk::JWK<K>,
+                                                                 cek_alg: KeyManagementAlgorithm,
+                                                                 enc_alg: ContentEncryptionAlgorithm,
+                                                                 options: DecryptionOptions)
+                                                                 -> Result<Self, Error> {
+        match *self {
+            Json::Decrypted { .. } => Err(Error::UnsupportedOperation),
+            Json::Encrypted(ref encrypted) => {
+                let (protected, unprotected, aad) = encrypted.shared_members();
+                let recipients = encrypted.recipients();
+                if recipients.is_empty() {
+                    Err(ValidationError::InvalidToken)?;
+                }
+
+                let protected_parameters: HeaderParameters = match *protected {
+                    Some(ref protected) => serde_json::from_slice(&base64url::decode_nopad(protected.as_bytes())?)?,
+                    None => serde_json::Map::new(),
+                };
+                let additional_data = match *aad {
+                    Some(ref aad) => Some(base64url::decode_nopad(aad.as_bytes())?),
+                    None => None,
+                };
+
+                let mut algorithm_found = false;
+                let mut first_error = None;
+                for recipient in &recipients {
+                    let header = recipient.header(&protected_parameters, unprotected, options)?;
+                    if header.header.registered.cek_algorithm != cek_alg ||
+                       header.header.registered.enc_algorithm != enc_alg {
+                        continue;
+                    }
+                    algorithm_found = true;
+                    if key.common.key_id.is_some() && header.header.registered.key_id.is_some() &&
+                       key.common.key_id != header.header.registered.key_id {
+                        continue;
+                    }
+
+                    match recipient.decrypt(header, encrypted, key, options) {
+                        Ok((header, payload)) => {
+                            return Ok(Json::Decrypted {
+                                          payload: T::from_bytes(&payload)?,
+                                          recipients: vec![header],
+                                          additional_data: additional_data,
+                                      })
+                        }
+                        Err(e) => first_error = first_error.or(Some(e)),
+                    }
+                }
+
+                Err(match first_error {
+                        Some(e) => e,
+                        None if algorithm_found => {
+                            Error::ValidationError(ValidationError::KeyNotFound(key.common.key_id.clone()))
+                        }
+                        None => Error::ValidationError(ValidationError::WrongAlgorithmHeader),
+                    })
+            }
+        }
+    }
+
+    /// Convenience method to get a reference to the encrypted serialization from an encrypted JWE
+    pub fn encrypted(&self) -> Result<&JsonSerialization, Error> {
+        match *self {
+            Json::Decrypted { .. } => Err(Error::UnsupportedOperation),
+            Json::Encrypted(ref encrypted) => Ok(encrypted),
+        }
+    }
+
+    /// Convenience method to get a reference to the payload from a decrypted JWE
+    pub fn payload(&self) -> Result<&T, Error> {
+        match *self {
+            Json::Decrypted { ref payload, .. } => Ok(payload),
+            Json::Encrypted(_) => Err(Error::UnsupportedOperation),
+        }
+    }
+
+    /// Convenience method to get a reference to the headers of the recipients from a decrypted JWE
+    pub fn recipients(&self) -> Result<&[JsonHeader<H>], Error> {
+        match *self {
+            Json::Decrypted { ref recipients, .. } => Ok(recipients),
+            Json::Encrypted(_) => Err(Error::UnsupportedOperation),
+        }
+    }
+
+    /// Convenience method to get a reference to the Additional Authenticated Data from a decrypted JWE
+    pub fn additional_data(&self) -> Result<Option<&[u8]>, Error> {
+        match *self {
+            Json::Decrypted { ref additional_data, .. } => Ok(additional_data.as_ref().map(|aad| aad.as_slice())),
+            Json::Encrypted(_) => Err(Error::UnsupportedOperation),
+        }
+    }
+}
+
+/// The Additional Authenticated Data for content encryption, as described in step 14 of
+/// [RFC7516#5.1](https://tools.ietf.org/html/rfc7516#section-5.1)
+fn additional_authenticated_data(protected: &Option<String>, aad: &Option<String>) -> Vec<u8> {
+    let mut data = protected.clone().unwrap_or_default();
+    if let Some(ref aad) = *aad {
+        data.push('.');
+        data.push_str(aad);
+    }
+    data.into_bytes()
+}
+
+/// Returns `None` for empty header parameters, which are then omitted from the serialization
+fn non_empty(parameters: HeaderParameters) -> Option<HeaderParameters> {
+    if parameters.is_empty() {
+        None
+    } else {
+        Some(parameters)
+    }
+}
+
+/// Header of one of the recipients of a [`Json`] JWE
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JsonHeader<H> {
+    /// The JOSE header of the recipient, which is the union of the protected, the shared unprotected and the
+    /// per-recipient unprotected header parameters
+    pub header: Header<H>,
+    /// Names of the header parameters that are serialized to the shared unprotected header (the `unprotected`
+    /// member)
+    pub shared_unprotected: Vec<String>,
+    /// Names of the header parameters that are serialized to the per-recipient unprotected header (the `header`
+    /// member of the recipient). The key management algorithm specific header parameters are always per-recipient.
+    /// All other header parameters are serialized to the protected header.
+    pub unprotected: Vec<String>,
+}
+
+impl<H: Serialize + DeserializeOwned> JsonHeader<H> {
+    /// New header where only the named header parameters are unprotected
+    pub fn new(header: Header<H>, shared_unprotected: &[&str], unprotected: &[&str]) -> Self {
+        Self {
+            header: header,
+            shared_unprotected: shared_unprotected.iter().map(|name| name.to_string()).collect(),
+            unprotected: unprotected.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    /// Split the parameters of `header`, which is this header updated during encryption, into the protected, the
+    /// shared unprotected and the per-recipient unprotected header parameters
+    fn split(&self, header: &Header<H>) -> Result<(HeaderParameters, HeaderParameters, HeaderParameters), Error> {
+        let parameters = match serde_json::to_value(header)? {
+            serde_json::Value::Object(parameters) => parameters,
+            _ => Err("The header must be a JSON object".to_string())?,
+        };
+
+        if let Some(name) = self.shared_unprotected
+               .iter()
+               .chain(&self.unprotected)
+               .find(|name| *name == "zip" || *name == "crit") {
+            Err(format!("The {} header parameter must be integrity protected", name))?;
+        }
+
+        let mut protected = HeaderParameters::new();
+        let mut shared_unprotected = HeaderParameters::new();
+        let mut unprotected = HeaderParameters::new();
+        for (name, value) in parameters {
+            if CEK_ALGORITHM_HEADERS.contains(&name.as_str()) || self.unprotected.contains(&name) {
+                unprotected.insert(name, value);
+            } else if self.shared_unprotected.contains(&name) {
+                shared_unprotected.insert(name, value);
+            } else {
+                protected.insert(name, value);
+            }
+        }
+        Ok((protected, shared_unprotected, unprotected))
+    }
+}
+
+impl<H: Serialize + DeserializeOwned> From<Header<H>> for JsonHeader<H> {
+    fn from(header: Header<H>) -> Self {
+        Self {
+            header: header,
+            shared_unprotected: vec![],
+            unprotected: vec![],
+        }
+    }
+}
+
+/// Encrypted JWE in one of the JWE JSON Serializations
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JsonSerialization {
+    /// General JWE JSON Serialization, with any number of recipients.
+    /// Defined in [RFC7516#7.2.1](https://tools.ietf.org/html/rfc7516#section-7.2.1).
+    General {
+        /// Base64 URL encoded protected header, if any
+        #[serde(skip_serializing_if = "Option::is_none")]
+        protected: Option<String>,
+        /// Shared unprotected header parameters, if any
+        #[serde(skip_serializing_if = "Option::is_none")]
+        unprotected: Option<serde_json::Map<String, serde_json::Value>>,
+        /// Recipients of the JWE
+        recipients: Vec<JsonRecipient>,
+        /// Base64 URL encoded Additional Authenticated Data, if any
+        #[serde(skip_serializing_if = "Option::is_none")]
+        aad: Option<String>,
+        /// Base64 URL encoded initialization vector
+        iv: String,
+        /// Base64 URL encoded ciphertext
+        ciphertext: String,
+        /// Base64 URL encoded authentication tag
+        tag: String,
+    },
+    /// Flattened JWE JSON Serialization, with a single recipient whose members are part of the top-level object.
+    /// Defined in [RFC7516#7.2.2](https://tools.ietf.org/html/rfc7516#section-7.2.2).
+    Flattened {
+        /// Base64 URL encoded protected header, if any
+        #[serde(skip_serializing_if = "Option::is_none")]
+        protected: Option<String>,
+        /// Shared unprotected header parameters, if any
+        #[serde(skip_serializing_if = "Option::is_none")]
+        unprotected: Option<serde_json::Map<String, serde_json::Value>>,
+        /// Per-recipient unprotected header parameters, if any
+        #[serde(skip_serializing_if = "Option::is_none")]
+        header: Option<serde_json::Map<String, serde_json::Value>>,
+        /// Base64 URL encoded encrypted key, if any
+        #[serde(skip_serializing_if = "Option::is_none")]
+        encrypted_key: Option<String>,
+        /// Base64 URL encoded Additional Authenticated Data, if any
+        #[serde(skip_serializing_if = "Option::is_none")]
+        aad: Option<String>,
+        /// Base64 URL encoded initialization vector
+        iv: String,
+        /// Base64 URL encoded ciphertext
+        ciphertext: String,
+        /// Base64 URL encoded authentication tag
+        tag: String,
+    },
+}
+
+impl JsonSerialization {
+    /// The recipients of the JWE. The Flattened serialization always has exactly one.
+    pub fn recipients(&self) -> Vec<JsonRecipient> {
+        match *self {
+            JsonSerialization::General { ref recipients, .. } => recipients.clone(),
+            JsonSerialization::Flattened {
+                ref header,
+                ref encrypted_key,
+                ..
+            } => {
+                vec![JsonRecipient {
+                         header: header.clone(),
+                         encrypted_key: encrypted_key.clone(),
+                     }]
+            }
+        }
+    }
+
+    /// The protected header, the shared unprotected header parameters and the Additional Authenticated Data
+    fn shared_members(&self) -> (&Option<String>, &Option<HeaderParameters>, &Option<String>) {
+        match *self {
+            JsonSerialization::General {
+                ref protected,
+                ref unprotected,
+                ref aad,
+                ..
+            } |
+            JsonSerialization::Flattened {
+                ref protected,
+                ref unprotected,
+                ref aad,
+                ..
+            } => (protected, unprotected, aad),
+        }
+    }
+
+    /// The base64 URL encoded initialization vector, ciphertext and authentication tag
+    fn encrypted_payload(&self) -> (&str, &str, &str) {
+        match *self {
+            JsonSerialization::General {
+                ref iv,
+                ref ciphertext,
+                ref tag,
+                ..
+            } |
+            JsonSerialization::Flattened {
+                ref iv,
+                ref ciphertext,
+                ref tag,
+                ..
+            } => (iv, ciphertext, tag),
+        }
+    }
+}
+
+/// A recipient in the General JWE JSON Serialization
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct JsonRecipient {
+    /// Per-recipient unprotected header parameters, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Base64 URL encoded encrypted key, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encrypted_key: Option<String>,
+}
+
+impl JsonRecipient {
+    /// The JOSE header of the recipient, which is the union of the protected, the shared unprotected and the
+    /// per-recipient unprotected header parameters
+    fn header<H: Serialize + DeserializeOwned>(&self,
+                                               protected: &HeaderParameters,
+                                               shared_unprotected: &Option<HeaderParameters>,
+                                               options: DecryptionOptions)
+                                               -> Result<JsonHeader<H>, Error> {
+        let mut parameters = protected.clone();
+        let shared_unprotected = merge_unprotected(&mut parameters, shared_unprotected)?;
+        let unprotected = merge_unprotected(&mut parameters, &self.header)?;
+        critical::validate(&parameters, REGISTERED_HEADERS, options.understood_critical_headers)?;
+
+        Ok(JsonHeader {
+               header: serde_json::from_value(serde_json::Value::Object(parameters))?,
+               shared_unprotected: shared_unprotected,
+               unprotected: unprotected,
+           })
+    }
+
+    /// Decrypt the CEK of the recipient, and then the content of the JWE. Returns the header of the recipient, stripped
+    /// of the key management algorithm specific header parameters, and the decrypted payload.
+    fn decrypt<H, K>(&self,
+                     mut header: JsonHeader<H>,
+                     encrypted: &JsonSerialization,
+                     key: &jwk::JWK<K>,
+                     options: DecryptionOptions)
+                     -> Result<(JsonHeader<H>, Vec<u8>), Error>
+        where H: Serialize + DeserializeOwned,
+              K: Serialize + DeserializeOwned
+    {
+        let encrypted_cek = match self.encrypted_key {
+            Some(ref encrypted_key) => base64url::decode_nopad(encrypted_key.as_bytes())?,
+            None => vec![],
+        };
+        let cek = header.header.decrypt_cek(&encrypted_cek, key, options)?;
+        header.unprotected.retain(|name| !CEK_ALGORITHM_HEADERS.contains(&name.as_str()));
+
+        let (protected, _, aad) = encrypted.shared_members();
+        let (iv, ciphertext, tag) = encrypted.encrypted_payload();
+        let encrypted_payload = EncryptionResult {
+            nonce: base64url::decode_nopad(iv.as_bytes())?,
+            tag: base64url::decode_nopad(tag.as_bytes())?,
+            encrypted: base64url::decode_nopad(ciphertext.as_bytes())?,
+            additional_data: additional_authenticated_data(protected, aad),
+        };
+        let payload = header
+            .header
+            .registered
+            .enc_algorithm
+            .decrypt(&encrypted_payload, &cek)?;
+
+        let payload = match header.header.registered.compression_algorithm {
+            Some(ref compression_algorithm) => {
+                compression_algorithm.decompress(&payload, options.max_decompressed_size)?
+            }
+            None => payload,
+        };
+        Ok((header, payload))
+    }
+}
+
+/// Add unprotected header parameters to the other header parameters of a recipient, returning their names
+fn merge_unprotected(parameters: &mut HeaderParameters,
+                     unprotected: &Option<HeaderParameters>)
+                     -> Result<Vec<String>, Error> {
+    let mut names = vec![];
+    if let Some(ref unprotected) = *unprotected {
+        for (name, value) in unprotected {
+            // The protected and unprotected header parameters must be disjoint
+            if parameters.contains_key(name) {
+                Err(format!("Header parameter {} is present more than once", name))?;
+            }
+            if name == "zip" {
+                Err("The zip header parameter must be integrity protected".to_string())?;
+            }
+            if name == "crit" {
+                Err(ValidationError::InvalidCriticalHeader("crit must be integrity protected".to_string()))?;
+            }
+            parameters.insert(name.clone(), value.clone());
+            names.push(name.clone());
+        }
+    }
+    Ok(names)
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -673,6 +1581,1152 @@ mod tests {
         assert_eq!(decrypted_str, payload);
     }
 
+    #[cfg(feature = "aes192")]
+    #[test]
+    fn jwe_a192gcmkw_a192gcm_round_trip() {
+        let key = cek_oct_key(192 / 8);
+
+        let payload = "The true sign of intelligence is not knowledge but imagination.";
+        let jwe = Compact::new_decrypted(From::from(RegisteredHeader {
+                                                        cek_algorithm: KeyManagementAlgorithm::A192GCMKW,
+                                                        enc_algorithm: ContentEncryptionAlgorithm::A192GCM,
+                                                        ..Default::default()
+                                                    }),
+                                         payload.as_bytes().to_vec());
+
+        let encrypted_jwe = not_err!(jwe.encrypt(&key));
+        let token = not_err!(encrypted_jwe.encrypted()).to_string();
+
+        let encrypted_jwe = Compact::<Vec<u8>, ::Empty>::new_encrypted(&token);
+        let decrypted_jwe = not_err!(encrypted_jwe.into_decrypted(&key,
+                                                                  KeyManagementAlgorithm::A192GCMKW,
+                                                                  ContentEncryptionAlgorithm::A192GCM));
+        assert_eq!(jwe, decrypted_jwe);
+    }
+
+    /// The RSA key from [RFC 7516 Appendix A.1](https://tools.ietf.org/html/rfc7516#appendix-A.1)
+    fn rfc7516_rsa_private_key() -> jwk::JWK<::Empty> {
+        not_err!(serde_json::from_value(json!({
+            "kty": "RSA",
+            "n": "oahUIoWw0K0usKNuOR6H4wkf4oBUXHTxRvgb48E-BVvxkeDNjbC4he8rUWcJoZmds2h7M70imEVhRU5djINXtqllXI4DFqcI1DgjT\
+                  9LewND8MW2Krf3Spsk_ZkoFnilakGygTwpZ3uesH-PFABNIUYpOiN15dsQRkgr0vEhxN92i2asbOenSZeyaxziK72UwxrrKoExv6k\
+                  c5twXTq4h-QChLOln0_mtUZwfsRaMStPs6mS6XrgxnxbWhojf663tuEQueGC-FCMfra36C9knDFGzKsNa7LZK2djYgyD3JR_MB_4N\
+                  UJW_TqOQtwHYbxevoJArm-L5StowjzGy-_bq6Gw",
+            "e": "AQAB",
+            "d": "kLdtIj6GbDks_ApCSTYQtelcNttlKiOyPzMrXHeI-yk1F7-kpDxY4-WY5NWV5KntaEeXS1j82E375xxhWMHXyvjYecPT9fpwR_M9g\
+                  V8n9Hrh2anTpTD93Dt62ypW3yDsJzBnTnrYu1iwWRgBKrEYY46qAZIrA2xAwnm2X7uGR1hghkqDp0Vqj3kbSCz1XyfCs6_LehBwtx\
+                  HIyh8Ripy40p24moOAbgxVw3rxT_vlt3UVe4WO3JkJOzlpUf-KTVI2Ptgm-dARxTEtE-id-4OJr0h-K-VFs3VSndVTIznSxfyrj8I\
+                  LL6MG_Uv8YAu7VILSB3lOW085-4qE3DzgrTjgyQ",
+            "p": "1r52Xk46c-LsfB5P442p7atdPUrxQSy4mti_tZI3Mgf2EuFVbUoDBvaRQ-SWxkbkmoEzL7JXroSBjSrK3YIQgYdMgyAEPTPjXv_hI\
+                  2_1eTSPVZfzL0lffNn03IXqWF5MDFuoUYE0hzb2vhrlN_rKrbfDIwUbTrjjgieRbwC6Cl0",
+            "q": "wLb35x7hmQWZsWJmB_vle87ihgZ19S8lBEROLIsZG4ayZVe9Hi9gDVCOBmUDdaDYVTSNx_8Fyw1YYa9XGrGnDew00J28cRUoeBB_j\
+                  KI1oma0Orv1T9aXIWxKwd4gvxFImOWr3QRL9KEBRzk2RatUBnmDZJTIAfwTs0g68UZHvtc",
+            "dp": "ZK-YwE7diUh0qR1tR7w8WHtolDx3MZ_OTowiFvgfeQ3SiresXjm9gZ5KLhMXvo-uz-KUJWDxS5pFQ_M0evdo1dKiRTjVw_x4Nyqy\
+                   XPM5nULPkcpU827rnpZzAJKpdhWAgqrXGKAECQH0Xt4taznjnd_zVpAmZZq60WPMBMfKcuE",
+            "dq": "Dq0gfgJ1DdFGXiLvQEZnuKEN0UUmsJBxkjydc3j4ZYdBiMRAy86x0vHCjywcMlYYg4yoC4YZa9hNVcsjqA3FeiL19rk8g6Qn29Tt\
+                   0cj8qqyFpz9vNDBUfCAiJVeESOjJDZPYHdHY8v1b-o-Z2X5tvLx-TCekf7oxyeKDUqKWjis",
+            "qi": "VIMpMYbPf47dT1w_zDUXfPimsSegnMOA1zTaX7aGk_8urY6R8-ZW1FxU7AlWAyLWybqq6t16VFd7hQd0y6flUK4SlOydB61gwanO\
+                   sXGOAOv82cHq0E3eL4HrtZkUuKvnPrMnsUUFlfUdybVzxyjz9JF_XyaY14ardLSjf4L_FNY"
+        })))
+    }
+
+    /// The public part of `rfc7516_rsa_private_key`
+    fn rfc7516_rsa_public_key() -> jwk::JWK<::Empty> {
+        let mut key = rfc7516_rsa_private_key();
+        match key.algorithm {
+            jwk::AlgorithmParameters::RSA(ref mut parameters) => {
+                *parameters = jwk::RSAKeyParameters {
+                    n: parameters.n.clone(),
+                    e: parameters.e.clone(),
+                    ..Default::default()
+                }
+            }
+            _ => unreachable!(),
+        }
+        key
+    }
+
+    /// Example from [RFC 7516 Appendix A.1](https://tools.ietf.org/html/rfc7516#appendix-A.1)
+    #[cfg(feature = "rsa-decryption")]
+    #[test]
+    fn rsa_oaep_a256gcm_rfc7516_example() {
+        let token = "eyJhbGciOiJSU0EtT0FFUCIsImVuYyI6IkEyNTZHQ00ifQ.OKOawDo13gRp2ojaHV7LFpZcgV7T6DVZKTyKOMTYUmKoTCVJRgc\
+                     kCL9kiMT03JGeipsEdY3mx_etLbbWSrFr05kLzcSr4qKAq7YN7e9jwQRb23nfa6c9d-StnImGyFDbSv04uVuxIp5Zms1gNxKKK\
+                     2Da14B8S4rzVRltdYwam_lDp5XnZAYpQdb76FdIKLaVmqgfwX7XWRxv2322i-vDxRfqNzo_tETKzpVLzfiwQyeyPGLBIO56YJ7\
+                     eObdv0je81860ppamavo35UgoRdbYaBcoh9QcfylQr66oc6vFWXRcZ_ZT2LawVCWTIy3brGPi6UklfCpIMfIjf7iGdXKHzg.48\
+                     V1_ALb6US04U3b.5eym8TW_c8SuK0ltJ3rpYIzOeDQz7TALvtu6UG9oMo4vpzs9tX_EFShS8iB7j6jiSdiwkIr3ajwQzaBtQD_\
+                     A.XFBoMYUZodetZdvTiFvSkQ";
+        let jwe: Compact<Vec<u8>, ::Empty> = Compact::new_encrypted(token);
+
+        let decrypted_jwe = not_err!(jwe.decrypt(&rfc7516_rsa_private_key(),
+                                                 KeyManagementAlgorithm::RSA_OAEP,
+                                                 ContentEncryptionAlgorithm::A256GCM));
+        let decrypted_payload: &Vec<u8> = not_err!(decrypted_jwe.payload());
+        assert_eq!(decrypted_payload.as_slice(),
+                   &b"The true sign of intelligence is not knowledge but imagination."[..]);
+    }
+
+    #[cfg(feature = "rsa-decryption")]
+    #[test]
+    fn rsa_oaep_round_trip() {
+        let payload = "The true sign of intelligence is not knowledge but imagination.";
+
+        for cek_alg in &[KeyManagementAlgorithm::RSA_OAEP, KeyManagementAlgorithm::RSA_OAEP_256] {
+            let jwe = Compact::new_decrypted(From::from(RegisteredHeader {
+                                                            cek_algorithm: *cek_alg,
+                                                            enc_algorithm: ContentEncryptionAlgorithm::A128GCM,
+                                                            ..Default::default()
+                                                        }),
+                                             payload.as_bytes().to_vec());
+
+            let encrypted_jwe = not_err!(jwe.encrypt(&rfc7516_rsa_public_key()));
+            let decrypted_jwe = not_err!(encrypted_jwe.decrypt(&rfc7516_rsa_private_key(),
+                                                               *cek_alg,
+                                                               ContentEncryptionAlgorithm::A128GCM));
+            assert_eq!(jwe, decrypted_jwe);
+        }
+    }
+
+    #[cfg(feature = "rsa-decryption")]
+    #[test]
+    fn rsa_oaep_decrypt_requires_private_key() {
+        let jwe = Compact::new_decrypted(From::from(RegisteredHeader {
+                                                        cek_algorithm: KeyManagementAlgorithm::RSA_OAEP_256,
+                                                        enc_algorithm: ContentEncryptionAlgorithm::A256GCM,
+                                                        ..Default::default()
+                                                    }),
+                                         b"payload".to_vec());
+        let encrypted_jwe = not_err!(jwe.encrypt(&rfc7516_rsa_public_key()));
+
+        let result = encrypted_jwe.decrypt(&rfc7516_rsa_public_key(),
+                                           KeyManagementAlgorithm::RSA_OAEP_256,
+                                           ContentEncryptionAlgorithm::A256GCM);
+        assert!(result.is_err());
+
+        let result = encrypted_jwe.decrypt(&cek_oct_key(256 / 8),
+                                           KeyManagementAlgorithm::RSA_OAEP_256,
+                                           ContentEncryptionAlgorithm::A256GCM);
+        assert_matches!(result, Err(Error::WrongKeyType { .. }));
+    }
+
+    #[cfg(not(feature = "rsa-decryption"))]
+    #[test]
+    fn rsa_oaep_decryption_requires_rsa_decryption_feature() {
+        let jwe = Compact::new_decrypted(From::from(RegisteredHeader {
+                                                        cek_algorithm: KeyManagementAlgorithm::RSA_OAEP_256,
+                                                        enc_algorithm: ContentEncryptionAlgorithm::A256GCM,
+                                                        ..Default::default()
+                                                    }),
+                                         b"payload".to_vec());
+        let encrypted_jwe = not_err!(jwe.encrypt(&rfc7516_rsa_public_key()));
+
+        let result = encrypted_jwe.decrypt(&rfc7516_rsa_private_key(),
+                                           KeyManagementAlgorithm::RSA_OAEP_256,
+                                           ContentEncryptionAlgorithm::A256GCM);
+        assert_matches!(result,
+                        Err(Error::GenericError(message)),
+                        assert!(message.contains("rsa-decryption")));
+    }
+
+    /// The RSA key from [RFC 7516 Appendix A.2](https://tools.ietf.org/html/rfc7516#appendix-A.2)
+    fn rfc7516_a2_rsa_private_key() -> jwk::JWK<::Empty> {
+        not_err!(serde_json::from_value(json!({
+            "kty": "RSA",
+            "n": "sXchDaQebHnPiGvyDOAT4saGEUetSyo9MKLOoWFsueri23bOdgWp4Dy1WlUzewbgBHod5pcM9H95GQRV3JDXboIRROSBigeC5yjU1\
+                  hGzHHyXss8UDprecbAYxknTcQkhslANGRUZmdTOQ5qTRsLAt6BTYuyvVRdhS8exSZEy_c4gs_7svlJJQ4H9_NxsiIoLwAEk7-Q3UX\
+                  ERGYw_75IDrGA84-lA_-Ct4eTlXHBIY2EaV7t7LjJaynVJCpkv4LKjTTAumiGUIuQhrNhZLuF_RJLqHpM2kgWFLU7-VTdL1VbC2te\
+                  jvcI2BlMkEpk1BzBZI0KQB0GaDWFLN-aEAw3vRw",
+            "e": "AQAB",
+            "d": "VFCWOqXr8nvZNyaaJLXdnNPXZKRaWCjkU5Q2egQQpTBMwhprMzWzpR8Sxq1OPThh_J6MUD8Z35wky9b8eEO0pwNS8xlh1lOFRRBoN\
+                  qDIKVOku0aZb-rynq8cxjDTLZQ6Fz7jSjR1Klop-YKaUHc9GsEofQqYruPhzSA-QgajZGPbE_0ZaVDJHfyd7UUBUKunFMScbflYAA\
+                  OYJqVIVwaYR5zWEEceUjNnTNo_CVSj-VvXLO5VZfCUAVLgW4dpf1SrtZjSt34YLsRarSb127reG_DUwg9Ch-KyvjT1SkHgUWRVGcy\
+                  ly7uvVGRSDwsXypdrNinPA4jlhoNdizK2zF2CWQ",
+            "p": "9gY2w6I6S6L0juEKsbeDAwpd9WMfgqFoeA9vEyEUuk4kLwBKcoe1x4HG68ik918hdDSE9vDQSccA3xXHOAFOPJ8R9EeIAbTi1VwBY\
+                  nbTp87X-xcPWlEPkrdoUKW60tgs1aNd_Nnc9LEVVPMS390zbFxt8TN_biaBgelNgbC95sM",
+            "q": "uKlCKvKv_ZJMVcdIs5vVSU_6cPtYI1ljWytExV_skstvRSNi9r66jdd9-yBhVfuG4shsp2j7rGnIio901RBeHo6TPKWVVykPu1iYh\
+                  QXw1jIABfw-MVsN-3bQ76WLdt2SDxsHs7q7zPyUyHXmps7ycZ5c72wGkUwNOjYelmkiNS0",
+            "dp": "w0kZbV63cVRvVX6yk3C8cMxo2qCM4Y8nsq1lmMSYhG4EcL6FWbX5h9yuvngs4iLEFk6eALoUS4vIWEwcL4txw9LsWH_zKI-hwoRe\
+                   oP77cOdSL4AVcraHawlkpyd2TWjE5evgbhWtOxnZee3cXJBkAi64Ik6jZxbvk-RR3pEhnCs",
+            "dq": "o_8V14SezckO6CNLKs_btPdFiO9_kC1DsuUTd2LAfIIVeMZ7jn1Gus_Ff7B7IVx3p5KuBGOVF8L-qifLb6nQnLysgHDh132NDioZ\
+                   khH7mI7hPG-PYE_odApKdnqECHWw0J-F0JWnUd6D2B_1TvF9mXA2Qx-iGYn8OVV1Bsmp6qU",
+            "qi": "eNho5yRBEBxhGBtQRww9QirZsB66TrfFReG_CcteI1aCneT0ELGhYlRlCtUkTRclIfuEPmNsNDPbLoLqqCVznFbvdB7x-Tl-m0l_\
+                   eFTj2KiqwGqE9PZB9nNTwMVvH3VRRSLWACvPnSiwP8N5Usy-WRXS-V7TbpxIhvepTfE0NNo"
+        })))
+    }
+
+    /// The token of the example in [RFC 7516 Appendix A.2](https://tools.ietf.org/html/rfc7516#appendix-A.2)
+    const RSA1_5_A128CBC_HS256_TOKEN: &str = "eyJhbGciOiJSU0ExXzUiLCJlbmMiOiJBMTI4Q0JDLUhTMjU2In0.UGhIOguC7IuEvf_NPVaXs\
+                                              GMoLOmwvc1GyqlIKOK1nN94nHPoltGRhWhw7Zx0-kFm1NJn8LE9XShH59_i8J0PH5ZZyNfGy2\
+                                              xGdULU7sHNF6Gp2vPLgNZ__deLKxGHZ7PcHALUzoOegEI-8E66jX2E4zyJKx-YxzZIItRzC5h\
+                                              lRirb6Y5Cl_p-ko3YvkkysZIFNPccxRU7qve1WYPxqbb2Yw8kZqa2rMWI5ng8OtvzlV7elprC\
+                                              buPhcCdZ6XDP0_F8rkXds2vE4X-ncOIM8hAYHHi29NX0mcKiRaD0-D-ljQTP-cFPgwCp6X-nZ\
+                                              Zd9OHBv-B3oWh2TbqmScqXMR4gp_A.AxY8DCtDaGlsbGljb3RoZQ.KDlTtXchhZTGufMYmOYG\
+                                              S4HffxPSUrfmqCHXaI9wOGY.9hH0vgRfYgPnAHOd8stkvw";
+
+    /// Example from [RFC 7516 Appendix A.2](https://tools.ietf.org/html/rfc7516#appendix-A.2)
+    #[cfg(feature = "rsa-decryption")]
+    #[test]
+    fn rsa1_5_a128cbc_hs256_rfc7516_example() {
+        let jwe: Compact<Vec<u8>, ::Empty> = Compact::new_encrypted(RSA1_5_A128CBC_HS256_TOKEN);
+        let options = DecryptionOptions {
+            allow_rsa1_5: true,
+            ..Default::default()
+        };
+        let decrypted_jwe = not_err!(jwe.decrypt_with_options(&rfc7516_a2_rsa_private_key(),
+                                                              KeyManagementAlgorithm::RSA1_5,
+                                                              ContentEncryptionAlgorithm::A128CBC_HS256,
+                                                              options));
+        let decrypted_payload: &Vec<u8> = not_err!(decrypted_jwe.payload());
+        assert_eq!(decrypted_payload.as_slice(), &b"Live long and prosper."[..]);
+    }
+
+    #[cfg(feature = "rsa-decryption")]
+    #[test]
+    fn rsa1_5_round_trip() {
+        let jwe = Compact::new_decrypted(From::from(RegisteredHeader {
+                                                        cek_algorithm: KeyManagementAlgorithm::RSA1_5,
+                                                        enc_algorithm: ContentEncryptionAlgorithm::A256GCM,
+                                                        ..Default::default()
+                                                    }),
+                                         b"Live long and prosper.".to_vec());
+
+        let encryption_options = EncryptionOptions { allow_rsa1_5: true };
+        let encrypted_jwe = not_err!(jwe.encrypt_with_options(&rfc7516_rsa_public_key(), encryption_options));
+        let decryption_options = DecryptionOptions {
+            allow_rsa1_5: true,
+            ..Default::default()
+        };
+        let decrypted_jwe = not_err!(encrypted_jwe.decrypt_with_options(&rfc7516_rsa_private_key(),
+                                                                        KeyManagementAlgorithm::RSA1_5,
+                                                                        ContentEncryptionAlgorithm::A256GCM,
+                                                                        decryption_options));
+        assert_eq!(jwe, decrypted_jwe);
+    }
+
+    /// An invalid encrypted key is replaced by a random CEK, so it fails in the same way as an invalid authentication
+    /// tag, as described in [RFC7516#11.5](https://tools.ietf.org/html/rfc7516#section-11.5)
+    #[cfg(feature = "rsa-decryption")]
+    #[test]
+    fn rsa1_5_invalid_encrypted_key_fails_authentication() {
+        let key = rfc7516_a2_rsa_private_key();
+        let cek_alg = KeyManagementAlgorithm::RSA1_5;
+        let enc_alg = ContentEncryptionAlgorithm::A128CBC_HS256;
+        let options = DecryptionOptions {
+            allow_rsa1_5: true,
+            ..Default::default()
+        };
+
+        // A CEK with a valid padding but the wrong length, a modified encrypted key and a ciphertext larger than the
+        // modulus
+        let short_cek = not_err!(cek_alg.encrypt(&[0; 128 / 8], &key)).encrypted;
+        let original: Vec<u8> = not_err!(::Compact::decode(RSA1_5_A128CBC_HS256_TOKEN).part(1));
+        let mut modified = original.clone();
+        modified[10] ^= 0x01;
+        let invalid_encrypted_keys = [short_cek, modified, vec![0xff; original.len()]];
+
+        for encrypted_key in &invalid_encrypted_keys {
+            let mut compact = ::Compact::decode(RSA1_5_A128CBC_HS256_TOKEN);
+            compact.parts[1] = not_err!(encrypted_key.to_base64());
+            let jwe: Compact<Vec<u8>, ::Empty> = Compact::Encrypted(compact);
+            let result = jwe.decrypt_with_options(&key, cek_alg, enc_alg, options);
+            assert_matches!(result, Err(Error::UnspecifiedCryptographicError));
+
+            let encrypted = EncryptionResult {
+                encrypted: encrypted_key.clone(),
+                ..Default::default()
+            };
+            let first = not_err!(cek_alg.decrypt(&encrypted, enc_alg, &key));
+            let second = not_err!(cek_alg.decrypt(&encrypted, enc_alg, &key));
+            assert_eq!(not_err!(first.algorithm.octect_key()).len(), 256 / 8);
+            assert_ne!(first, second);
+        }
+    }
+
+    #[cfg(not(feature = "rsa-decryption"))]
+    #[test]
+    fn rsa1_5_decryption_requires_rsa_decryption_feature() {
+        let jwe: Compact<Vec<u8>, ::Empty> = Compact::new_encrypted(RSA1_5_A128CBC_HS256_TOKEN);
+        let options = DecryptionOptions {
+            allow_rsa1_5: true,
+            ..Default::default()
+        };
+        let result = jwe.decrypt_with_options(&rfc7516_a2_rsa_private_key(),
+                                              KeyManagementAlgorithm::RSA1_5,
+                                              ContentEncryptionAlgorithm::A128CBC_HS256,
+                                              options);
+        assert_matches!(result, Err(Error::UnsupportedOperation));
+    }
+
+    #[test]
+    fn rsa1_5_requires_opt_in() {
+        let jwe = Compact::new_decrypted(From::from(RegisteredHeader {
+                                                        cek_algorithm: KeyManagementAlgorithm::RSA1_5,
+                                                        enc_algorithm: ContentEncryptionAlgorithm::A128GCM,
+                                                        ..Default::default()
+                                                    }),
+                                         b"Live long and prosper.".to_vec());
+        let result = jwe.encrypt(&rfc7516_rsa_public_key());
+        assert_matches!(result, Err(Error::GenericError(_)));
+
+        let header = Header::from(RegisteredHeader {
+                                      cek_algorithm: KeyManagementAlgorithm::RSA1_5,
+                                      enc_algorithm: ContentEncryptionAlgorithm::A128GCM,
+                                      ..Default::default()
+                                  });
+        let jwe = Json::new_decrypted(b"Live long and prosper.".to_vec(), vec![From::from(header)], None);
+        let result = jwe.encrypt_flattened(&rfc7516_rsa_public_key());
+        assert_matches!(result, Err(Error::GenericError(_)));
+
+        let jwe: Compact<Vec<u8>, ::Empty> = Compact::new_encrypted(RSA1_5_A128CBC_HS256_TOKEN);
+        let result = jwe.decrypt(&rfc7516_a2_rsa_private_key(),
+                                 KeyManagementAlgorithm::RSA1_5,
+                                 ContentEncryptionAlgorithm::A128CBC_HS256);
+        assert_matches!(result, Err(Error::GenericError(_)));
+    }
+
+    /// The plaintext of the examples in [RFC 7520 Section 5](https://tools.ietf.org/html/rfc7520#section-5)
+    const RFC7520_PLAINTEXT: &str = "You can trust us to stick with you through thick and thin\u{2013}to the bitter \
+                                     end. And you can trust us to keep any secret of yours\u{2013}closer than you \
+                                     keep it yourself. But you cannot trust us to let you face trouble alone, and go \
+                                     off without a word. We are your friends, Frodo.";
+
+    /// RSA key from [RFC 7520 Section 5.1](https://tools.ietf.org/html/rfc7520#section-5.1)
+    fn rfc7520_rsa_private_key() -> jwk::JWK<::Empty> {
+        not_err!(serde_json::from_value(json!({
+            "kty": "RSA",
+            "kid": "frodo.baggins@hobbiton.example",
+            "use": "enc",
+            "n": "maxhbsmBtdQ3CNrKvprUE6n9lYcregDMLYNeTAWcLj8NnPU9XIYegTHVHQjxKDSHP2l-F5jS7sppG1wgdAqZyhnWvXhYNvcM7RfgK\
+                  xqNx_xAHx6f3yy7s-M9PSNCwPC2lh6UAkR4I00EhV9lrypM9Pi4lBUop9t5fS9W5UNwaAllhrd-osQGPjIeI1deHTwx-ZTHu3C60P\
+                  u_LJIl6hKn9wbwaUmA4cR5Bd2pgbaY7ASgsjCUbtYJaNIHSoHXprUdJZKUMAzV0WOKPfA6OPI4oypBadjvMZ4ZAj3BnXaSYsEZhau\
+                  eTXvZB4eZOAjIyh2e_VOIKVMsnDrJYAVotGlvMQ",
+            "e": "AQAB",
+            "d": "Kn9tgoHfiTVi8uPu5b9TnwyHwG5dK6RE0uFdlpCGnJN7ZEi963R7wybQ1PLAHmpIbNTztfrheoAniRV1NCIqXaW_qS461xiDTp4nt\
+                  EPnqcKsyO5jMAji7-CL8vhpYYowNFvIesgMoVaPRYMYT9TW63hNM0aWs7USZ_hLg6Oe1mY0vHTI3FucjSM86Nff4oIENt43r2fspg\
+                  EPGRrdE6fpLc9Oaq-qeP1GFULimrRdndm-P8q8kvN3KHlNAtEgrQAgTTgz80S-3VD0FgWfgnb1PNmiuPUxO8OpI9KDIfu_acc6fg1\
+                  4nsNaJqXe6RESvhGPH2afjHqSy_Fd2vpzj85bQQ",
+            "p": "2DwQmZ43FoTnQ8IkUj3BmKRf5Eh2mizZA5xEJ2MinUE3sdTYKSLtaEoekX9vbBZuWxHdVhM6UnKCJ_2iNk8Z0ayLYHL0_G21aXf9-\
+                  unynEpUsH7HHTklLpYAzOOx1ZgVljoxAdWNn3hiEFrjZLZGS7lOH-a3QQlDDQoJOJ2VFmU",
+            "q": "te8LY4-W7IyaqH1ExujjMqkTAlTeRbv0VLQnfLY2xINnrWdwiQ93_VF099aP1ESeLja2nw-6iKIe-qT7mtCPozKfVtUYfz5HrJ_XY\
+                  2kfexJINb9lhZHMv5p1skZpeIS-GPHCC6gRlKo1q-idn_qxyusfWv7WAxlSVfQfk8d6Et0",
+            "dp": "UfYKcL_or492vVc0PzwLSplbg4L3-Z5wL48mwiswbpzOyIgd2xHTHQmjJpFAIZ8q-zf9RmgJXkDrFs9rkdxPtAsL1WYdeCT5c125\
+                   Fkdg317JVRDo1inX7x2Kdh8ERCreW8_4zXItuTl_KiXZNU5lvMQjWbIw2eTx1lpsflo0rYU",
+            "dq": "iEgcO-QfpepdH8FWd7mUFyrXdnOkXJBCogChY6YKuIHGc_p8Le9MbpFKESzEaLlN1Ehf3B6oGBl5Iz_ayUlZj2IoQZ82znoUrpa9\
+                   fVYNot87ACfzIG7q9Mv7RiPAderZi03tkVXAdaBau_9vs5rS-7HMtxkVrxSUvJY14TkXlHE",
+            "qi": "kC-lzZOqoFaZCr5l0tOVtREKoVqaAYhQiqIRGL-MzS4sCmRkxm5vZlXYx6RtE1n_AagjqajlkjieGlxTTThHD8Iga6foGBMaAr5u\
+                   R1hGQpSc7Gl7CF1DZkBJMTQN6EshYzZfxW08mIO8M6Rzuh0beL6fG9mkDcIyPrBXx2bQ_mM"
+        })))
+    }
+
+    /// Example from [RFC 7520 Section 5.1](https://tools.ietf.org/html/rfc7520#section-5.1)
+    #[cfg(feature = "rsa-decryption")]
+    #[test]
+    fn rsa1_5_a128cbc_hs256_rfc7520_example() {
+        let key = rfc7520_rsa_private_key();
+        let token = "eyJhbGciOiJSU0ExXzUiLCJraWQiOiJmcm9kby5iYWdnaW5zQGhvYmJpdG9uLmV4YW1wbGUiLCJlbmMiOiJBMTI4Q0JDLUhTMj\
+                     U2In0.\
+                     laLxI0j-nLH-_BgLOXMozKxmy9gffy2gTdvqzfTihJBuuzxg0V7yk1WClnQePFvG2K-pvSlWc9BRIazDrn50RcRai__3TDON39\
+                     5H3c62tIouJJ4XaRvYHFjZTZ2GXfz8YAImcc91Tfk0WXC2F5Xbb71ClQ1DDH151tlpH77f2ff7xiSxh9oSewYrcGTSLUeeCt36\
+                     r1Kt3OSj7EyBQXoZlN7IxbyhMAfgIe7Mv1rOTOI5I8NQqeXXW8VlzNmoxaGMny3YnGir5Wf6Qt2nBq4qDaPdnaAuuGUGEecelI\
+                     O1wx1BpyIfgvfjOhMBs9M8XL223Fg47xlGsMXdfuY-4jaqVw.\
+                     bbd5sTkYwhAIqfHsx8DayA.\
+                     0fys_TY_na7f8dwSfXLiYdHaA2DxUjD67ieF7fcVbIR62JhJvGZ4_FNVSiGc_raa0HnLQ6s1P2sv3Xzl1p1l_o5wR_RsSzrS8Z\
+                     -wnI3Jvo0mkpEEnlDmZvDu_k8OWzJv7eZVEqiWKdyVzFhPpiyQU28GLOpRc2VbVbK4dQKPdNTjPPEmRqcaGeTWZVyeSUvf5k59\
+                     yJZxRuSvWFf6KrNtmRdZ8R4mDOjHSrM_s8uwIFcqt4r5GX8TKaI0zT5CbL5Qlw3sRc7u_hg0yKVOiRytEAEs3vZkcfLkP6nbXd\
+                     C_PkMdNS-ohP78T2O6_7uInMGhFeX4ctHG7VelHGiT93JfWDEQi5_V9UN1rhXNrYu-0fVMkZAKX3VWi7lzA6BP430m.\
+                     kvKuFBXHe5mQr4lqgobAUg";
+
+        let jwe: Compact<Vec<u8>, ::Empty> = Compact::new_encrypted(token);
+        let result = jwe.decrypt(&key, KeyManagementAlgorithm::RSA1_5, ContentEncryptionAlgorithm::A128CBC_HS256);
+        assert_matches!(result, Err(Error::GenericError(_)));
+
+        let options = DecryptionOptions {
+            allow_rsa1_5: true,
+            ..Default::default()
+        };
+        let decrypted_jwe = not_err!(jwe.decrypt_with_options(&key,
+                                                              KeyManagementAlgorithm::RSA1_5,
+                                                              ContentEncryptionAlgorithm::A128CBC_HS256,
+                                                              options));
+        let decrypted_payload: &Vec<u8> = not_err!(decrypted_jwe.payload());
+        assert_eq!(decrypted_payload.as_slice(), RFC7520_PLAINTEXT.as_bytes());
+    }
+
+    /// Example from [RFC 7520 Section 5.8](https://tools.ietf.org/html/rfc7520#section-5.8)
+    #[test]
+    fn a128kw_a128gcm_rfc7520_example() {
+        let key: jwk::JWK<::Empty> = not_err!(serde_json::from_value(json!({
+            "kty": "oct",
+            "kid": "81b20965-8332-43d9-a468-82160ad91ac8",
+            "use": "enc",
+            "alg": "A128KW",
+            "k": "GZy6sIZ6wl9NJOKB-jnmVQ"
+        })));
+        let token = "eyJhbGciOiJBMTI4S1ciLCJraWQiOiI4MWIyMDk2NS04MzMyLTQzZDktYTQ2OC04MjE2MGFkOTFhYzgiLCJlbmMiOiJBMTI4R0\
+                     NNIn0.\
+                     CBI6oDw8MydIx1IBntf_lQcw2MmJKIQx.\
+                     Qx0pmsDa8KnJc9Jo.\
+                     AwliP-KmWgsZ37BvzCefNen6VTbRK3QMA4TkvRkH0tP1bTdhtFJgJxeVmJkLD61A1hnWGetdg11c9ADsnWgL56NyxwSYjU1ZEH\
+                     cGkd3EkU0vjHi9gTlb90qSYFfeF0LwkcTtjbYKCsiNJQkcIp1yeM03OmuiYSoYJVSpf7ej6zaYcMv3WwdxDFl8REwOhNImk2Xl\
+                     d2JXq6BR53TSFkyT7PwVLuq-1GwtGHlQeg7gDT6xW0JqHDPn_H-puQsmthc9Zg0ojmJfqqFvETUxLAF-KjcBTS5dNy6egwkYtO\
+                     t8EIHK-oEsKYtZRaa8Z7MOZ7UGxGIMvEmxrGCPeJa14slv2-gaqK0kEThkaSqdYw0FkQZF.\
+                     ER7MWJZ1FBI_NKvn7Zb1Lw";
+
+        // The CEK is wrapped to the encrypted key of the example
+        let cek: Vec<u8> = not_err!(CompactPart::from_base64(&"aY5_Ghmk9KxWPBLu_glx1w"));
+        let encrypted_cek = not_err!(KeyManagementAlgorithm::A128KW.encrypt(&cek, &key));
+        assert_eq!(not_err!(encrypted_cek.encrypted.to_base64()).str(), "CBI6oDw8MydIx1IBntf_lQcw2MmJKIQx");
+
+        let jwe: Compact<Vec<u8>, ::Empty> = Compact::new_encrypted(token);
+        let decrypted_jwe = not_err!(jwe.decrypt(&key,
+                                                 KeyManagementAlgorithm::A128KW,
+                                                 ContentEncryptionAlgorithm::A128GCM));
+        let decrypted_payload: &Vec<u8> = not_err!(decrypted_jwe.payload());
+        assert_eq!(decrypted_payload.as_slice(), RFC7520_PLAINTEXT.as_bytes());
+    }
+
+    #[test]
+    fn aes_key_wrap_round_trip() {
+        let payload = "The true sign of intelligence is not knowledge but imagination.";
+        let algorithms = [(KeyManagementAlgorithm::A128KW, 128 / 8),
+                          (KeyManagementAlgorithm::A192KW, 192 / 8),
+                          (KeyManagementAlgorithm::A256KW, 256 / 8)];
+
+        for &(cek_alg, key_length) in &algorithms {
+            let key = cek_oct_key(key_length);
+            let jwe = Compact::new_decrypted(From::from(RegisteredHeader {
+                                                            cek_algorithm: cek_alg,
+                                                            enc_algorithm: ContentEncryptionAlgorithm::A256GCM,
+                                                            ..Default::default()
+                                                        }),
+                                             payload.as_bytes().to_vec());
+
+            let encrypted_jwe = not_err!(jwe.encrypt(&key));
+            let decrypted_jwe = not_err!(encrypted_jwe.decrypt(&key, cek_alg, ContentEncryptionAlgorithm::A256GCM));
+            assert_eq!(jwe, decrypted_jwe);
+
+            let result = encrypted_jwe.decrypt(&cek_oct_key(key_length), cek_alg, ContentEncryptionAlgorithm::A256GCM);
+            assert_matches!(result, Err(Error::UnspecifiedCryptographicError));
+        }
+    }
+
+    #[test]
+    fn aes_key_wrap_with_wrong_key_length() {
+        let jwe = Compact::new_decrypted(From::from(RegisteredHeader {
+                                                        cek_algorithm: KeyManagementAlgorithm::A256KW,
+                                                        enc_algorithm: ContentEncryptionAlgorithm::A128GCM,
+                                                        ..Default::default()
+                                                    }),
+                                         b"payload".to_vec());
+        assert_matches!(jwe.encrypt(&cek_oct_key(128 / 8)), Err(Error::GenericError(_)));
+    }
+
+    /// Example from [RFC 7520 Section 5.5](https://tools.ietf.org/html/rfc7520#section-5.5)
+    #[test]
+    fn ecdh_es_a128cbc_hs256_rfc7520_example() {
+        let key: jwk::JWK<::Empty> = not_err!(serde_json::from_value(json!({
+            "kty": "EC",
+            "kid": "meriadoc.brandybuck@buckland.example",
+            "use": "enc",
+            "crv": "P-256",
+            "x": "Ze2loSV3wrroKUN_4zhwGhCqo3Xhu1td4QjeQ5wIVR0",
+            "y": "HlLtdXARY_f55A3fnzQbPcm6hgr34Mp8p-nuzQCE0Zw",
+            "d": "r_kHyZ-a06rmxM3yESK84r1otSg-aQcVStkRhA-iCM8"
+        })));
+        let token = "eyJhbGciOiJFQ0RILUVTIiwia2lkIjoibWVyaWFkb2MuYnJhbmR5YnVja0BidWNrbGFuZC5leGFtcGxlIiwiZXBrIjp7Imt0\
+                     eSI6IkVDIiwiY3J2IjoiUC0yNTYiLCJ4IjoibVBVS1RfYkFXR0hJaGcwVHBqanFWc1AxclhXUXVfdndWT0hIdE5rZFlvQSIsIn\
+                     kiOiI4QlFBc0ltR2VBUzQ2ZnlXdzVNaFlmR1RUMElqQnBGdzJTUzM0RHY0SXJzIn0sImVuYyI6IkExMjhDQkMtSFMyNTYifQ.\
+                     .\
+                     yc9N8v5sYyv3iGQT926IUg.\
+                     BoDlwPnTypYq-ivjmQvAYJLb5Q6l-F3LIgQomlz87yW4OPKbWE1zSTEFjDfhU9IPIOSA9Bml4m7iDFwA-1ZXvHteLDtw4R1XRG\
+                     MEsDIqAYtskTTmzmzNa-_q4F_evAPUmwlO-ZG45Mnq4uhM1fm_D9rBtWolqZSF3xGNNkpOMQKF1Cl8i8wjzRli7-IXgyirlKQs\
+                     bhhqRzkv8IcY6aHl24j03C-AR2le1r7URUhArM79BY8soZU0lzwI-sD5PZ3l4NDCCei9XkoIAfsXJWmySPoeRb2Ni5UZL4mYpv\
+                     KDiwmyzGd65KqVw7MsFfI_K767G9C9Azp73gKZD0DyUn1mn0WW5LmyX_yJ-3AROq8p1WZBfG-ZyJ6195_JGG2m9Csg.\
+                     WCCkNa-x4BeB9hIDIfFuhg";
+
+        let jwe: Compact<Vec<u8>, ::Empty> = Compact::new_encrypted(token);
+        let decrypted_jwe = not_err!(jwe.decrypt(&key,
+                                                 KeyManagementAlgorithm::ECDH_ES,
+                                                 ContentEncryptionAlgorithm::A128CBC_HS256));
+        let decrypted_payload: &Vec<u8> = not_err!(decrypted_jwe.payload());
+        assert_eq!(decrypted_payload.as_slice(), RFC7520_PLAINTEXT.as_bytes());
+        let decrypted_header = not_err!(decrypted_jwe.header());
+        assert_eq!(decrypted_header.registered.key_id,
+                   Some("meriadoc.brandybuck@buckland.example".to_string()));
+        assert_eq!(decrypted_header.cek_algorithm, Default::default());
+    }
+
+    #[test]
+    fn ecdh_es_round_trip() {
+        let payload = "The true sign of intelligence is not knowledge but imagination.";
+        let p256_key: jwk::JWK<::Empty> = not_err!(serde_json::from_value(json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": "Ze2loSV3wrroKUN_4zhwGhCqo3Xhu1td4QjeQ5wIVR0",
+            "y": "HlLtdXARY_f55A3fnzQbPcm6hgr34Mp8p-nuzQCE0Zw",
+            "d": "r_kHyZ-a06rmxM3yESK84r1otSg-aQcVStkRhA-iCM8"
+        })));
+        let p384_key: jwk::JWK<::Empty> = not_err!(serde_json::from_value(json!({
+            "kty": "EC",
+            "crv": "P-384",
+            "x": "YU4rRUzdmVqmRtWOs2OpDE_T5fsNIodcG8G5FWPrTPMyxpzsSOGaQLpe2FpxBmu2",
+            "y": "A8-yxCHxkfBz3hKZfI1jUYMjUhsEveZ9THuwFjH2sCNdtksRJU7D5-SkgaFL1ETP",
+            "d": "iTx2pk7wW-GqJkHcEkFQb2EFyYcO7RugmaW3mRrQVAOUiPommT0IdnYK2xDlZh-j"
+        })));
+
+        for key in &[p256_key, p384_key] {
+            let mut header = Header::from(RegisteredHeader {
+                                              cek_algorithm: KeyManagementAlgorithm::ECDH_ES,
+                                              enc_algorithm: ContentEncryptionAlgorithm::A256GCM,
+                                              ..Default::default()
+                                          });
+            header.cek_algorithm.party_u_info = Some(b"Alice".to_vec());
+            header.cek_algorithm.party_v_info = Some(b"Bob".to_vec());
+            let jwe = Compact::new_decrypted(header.clone(), payload.as_bytes().to_vec());
+
+            let encrypted_jwe = not_err!(jwe.encrypt(key));
+            {
+                let compact = not_err!(encrypted_jwe.encrypted());
+                let encrypted_header: Header<::Empty> = not_err!(compact.part(0));
+                let ephemeral_public_key = encrypted_header.cek_algorithm.ephemeral_public_key.unwrap();
+                match ephemeral_public_key.algorithm {
+                    jwk::AlgorithmParameters::EllipticCurve(ref parameters) => assert!(parameters.d.is_none()),
+                    _ => panic!("The ephemeral public key should be an Elliptic Curve key"),
+                }
+                assert_eq!(encrypted_header.cek_algorithm.party_u_info, Some(b"Alice".to_vec()));
+                assert_eq!(encrypted_header.cek_algorithm.party_v_info, Some(b"Bob".to_vec()));
+                let encrypted_cek: Vec<u8> = not_err!(compact.part(1));
+                assert!(encrypted_cek.is_empty());
+            }
+
+            let decrypted_jwe = not_err!(encrypted_jwe.decrypt(key,
+                                                               KeyManagementAlgorithm::ECDH_ES,
+                                                               ContentEncryptionAlgorithm::A256GCM));
+            assert_eq!(not_err!(decrypted_jwe.header()).registered, header.registered);
+            assert_eq!(not_err!(jwe.payload()), not_err!(decrypted_jwe.payload()));
+        }
+    }
+
+    #[test]
+    fn ecdh_es_decrypt_requires_ephemeral_public_key() {
+        let key: jwk::JWK<::Empty> = not_err!(serde_json::from_value(json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": "Ze2loSV3wrroKUN_4zhwGhCqo3Xhu1td4QjeQ5wIVR0",
+            "y": "HlLtdXARY_f55A3fnzQbPcm6hgr34Mp8p-nuzQCE0Zw",
+            "d": "r_kHyZ-a06rmxM3yESK84r1otSg-aQcVStkRhA-iCM8"
+        })));
+        let jwe = Compact::new_decrypted(From::from(RegisteredHeader {
+                                                        cek_algorithm: KeyManagementAlgorithm::ECDH_ES,
+                                                        enc_algorithm: ContentEncryptionAlgorithm::A128GCM,
+                                                        ..Default::default()
+                                                    }),
+                                         b"payload".to_vec());
+        let encrypted_jwe = not_err!(jwe.encrypt(&key));
+
+        // Replace the header with one without `epk`
+        let mut compact = encrypted_jwe.unwrap_encrypted();
+        let mut header: Header<::Empty> = not_err!(compact.part(0));
+        header.cek_algorithm.ephemeral_public_key = None;
+        compact.parts[0] = not_err!(header.to_base64());
+        let jwe: Compact<Vec<u8>, ::Empty> = Compact::Encrypted(compact);
+
+        let result = jwe.decrypt(&key, KeyManagementAlgorithm::ECDH_ES, ContentEncryptionAlgorithm::A128GCM);
+        assert_matches!(result, Err(Error::GenericError(_)));
+    }
+
+    /// P-384 key from [RFC 7520 Section 5.4](https://tools.ietf.org/html/rfc7520#section-5.4)
+    fn rfc7520_p384_private_key() -> jwk::JWK<::Empty> {
+        not_err!(serde_json::from_value(json!({
+            "kty": "EC",
+            "kid": "peregrin.took@tuckborough.example",
+            "use": "enc",
+            "crv": "P-384",
+            "x": "YU4rRUzdmVqmRtWOs2OpDE_T5fsNIodcG8G5FWPrTPMyxpzsSOGaQLpe2FpxBmu2",
+            "y": "A8-yxCHxkfBz3hKZfI1jUYMjUhsEveZ9THuwFjH2sCNdtksRJU7D5-SkgaFL1ETP",
+            "d": "iTx2pk7wW-GqJkHcEkFQb2EFyYcO7RugmaW3mRrQVAOUiPommT0IdnYK2xDlZh-j"
+        })))
+    }
+
+    /// Example from [RFC 7520 Section 5.4](https://tools.ietf.org/html/rfc7520#section-5.4)
+    #[test]
+    fn ecdh_es_a128kw_a128gcm_rfc7520_example() {
+        let key = rfc7520_p384_private_key();
+        let token = "eyJhbGciOiJFQ0RILUVTK0ExMjhLVyIsImtpZCI6InBlcmVncmluLnRvb2tAdHVja2Jvcm91Z2guZXhhbXBsZSIsImVwayI6ey\
+                     JrdHkiOiJFQyIsImNydiI6IlAtMzg0IiwieCI6InVCbzRrSFB3Nmtiang1bDB4b3dyZF9vWXpCbWF6LUdLRlp1NHhBRkZrYllp\
+                     V2d1dEVLNml1RURzUTZ3TmROZzMiLCJ5Ijoic3AzcDVTR2haVkMyZmFYdW1JLWU5SlUyTW84S3BvWXJGRHI1eVBOVnRXNFBnRX\
+                     daT3lRVEEtSmRhWTh0YjdFMCJ9LCJlbmMiOiJBMTI4R0NNIn0.\
+                     0DJjBXri_kBcC46IkU5_Jk9BqaQeHdv2.\
+                     mH-G2zVqgztUtnW_.\
+                     tkZuOO9h95OgHJmkkrfLBisku8rGf6nzVxhRM3sVOhXgz5NJ76oID7lpnAi_cPWJRCjSpAaUZ5dOR3Spy7QuEkmKx8-3RCMhSY\
+                     MzsXaEwDdXta9Mn5B7cCBoJKB0IgEnj_qfo1hIi-uEkUpOZ8aLTZGHfpl05jMwbKkTe2yK3mjF6SBAsgicQDVCkcY9BLluzx1R\
+                     mC3ORXaM0JaHPB93YcdSDGgpgBWMVrNU1ErkjcMqMoT_wtCex3w03XdLkjXIuEr2hWgeP-nkUZTPU9EoGSPj6fAS-bSz87RCPr\
+                     xZdj_iVyC6QWcqAu07WNhjzJEPc4jVntRJ6K53NgPQ5p99l3Z408OUqj4ioYezbS6vTPlQ.\
+                     WuGzxmcreYjpHGJoa17EBg";
+
+        let jwe: Compact<Vec<u8>, ::Empty> = Compact::new_encrypted(token);
+        let decrypted_jwe = not_err!(jwe.decrypt(&key,
+                                                 KeyManagementAlgorithm::ECDH_ES_A128KW,
+                                                 ContentEncryptionAlgorithm::A128GCM));
+        let decrypted_payload: &Vec<u8> = not_err!(decrypted_jwe.payload());
+        assert_eq!(decrypted_payload.as_slice(), RFC7520_PLAINTEXT.as_bytes());
+        assert_eq!(not_err!(decrypted_jwe.header()).cek_algorithm, Default::default());
+    }
+
+    #[test]
+    fn ecdh_es_key_agreement_round_trip() {
+        let payload = "The true sign of intelligence is not knowledge but imagination.";
+        let p256_key: jwk::JWK<::Empty> = not_err!(serde_json::from_value(json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": "Ze2loSV3wrroKUN_4zhwGhCqo3Xhu1td4QjeQ5wIVR0",
+            "y": "HlLtdXARY_f55A3fnzQbPcm6hgr34Mp8p-nuzQCE0Zw",
+            "d": "r_kHyZ-a06rmxM3yESK84r1otSg-aQcVStkRhA-iCM8"
+        })));
+        // Key from [RFC 8037 Appendix A.6](https://tools.ietf.org/html/rfc8037#appendix-A.6)
+        let x25519_key: jwk::JWK<::Empty> = not_err!(serde_json::from_value(json!({
+            "kty": "OKP",
+            "crv": "X25519",
+            "x": "3p7bfXt9wbTTW2HC7OQ1Nz-DQ8hbeGdNrfx-FG-IK08",
+            "d": "XasIfmJKikt54X-Lg4AO5m87sSkmGLb9HC-LJ_-I4Os"
+        })));
+        let algorithms = [KeyManagementAlgorithm::ECDH_ES,
+                          KeyManagementAlgorithm::ECDH_ES_A128KW,
+                          KeyManagementAlgorithm::ECDH_ES_A192KW,
+                          KeyManagementAlgorithm::ECDH_ES_A256KW];
+
+        for key in &[p256_key, x25519_key] {
+            for &cek_alg in &algorithms {
+                let jwe = Compact::new_decrypted(From::from(RegisteredHeader {
+                                                                cek_algorithm: cek_alg,
+                                                                enc_algorithm: ContentEncryptionAlgorithm::A128GCM,
+                                                                ..Default::default()
+                                                            }),
+                                                 payload.as_bytes().to_vec());
+
+                let encrypted_jwe = not_err!(jwe.encrypt(key));
+                let encrypted_cek: Vec<u8> = not_err!(not_err!(encrypted_jwe.encrypted()).part(1));
+                assert_eq!(encrypted_cek.is_empty(), cek_alg == KeyManagementAlgorithm::ECDH_ES);
+
+                let decrypted_jwe = not_err!(encrypted_jwe.decrypt(key, cek_alg, ContentEncryptionAlgorithm::A128GCM));
+                assert_eq!(jwe, decrypted_jwe);
+            }
+        }
+    }
+
+    /// Example from [RFC 7520 Section 5.3](https://tools.ietf.org/html/rfc7520#section-5.3)
+    #[test]
+    fn pbes2_hs512_a256kw_a128cbc_hs256_rfc7520_example() {
+        let password = jwk::JWK::<::Empty>::new_octect_key("entrap_o\u{2013}peter_long\u{2013}credit_tun".as_bytes(),
+                                                           Default::default());
+        let token = "eyJhbGciOiJQQkVTMi1IUzUxMitBMjU2S1ciLCJwMnMiOiI4UTFTemluYXNSM3hjaFl6NlpaY0hBIiwicDJjIjo4MTkyLCJjdH\
+                     kiOiJqd2stc2V0K2pzb24iLCJlbmMiOiJBMTI4Q0JDLUhTMjU2In0.\
+                     d3qNhUWfqheyPp4H8sjOWsDYajoej4c5Je6rlUtFPWdgtURtmeDV1g.\
+                     VBiCzVHNoLiR3F4V82uoTQ.\
+                     23i-Tb1AV4n0WKVSSgcQrdg6GRqsUKxjruHXYsTHAJLZ2nsnGIX86vMXqIi6IRsfywCRFzLxEcZBRnTvG3nhzPk0GDD7FMyXhU\
+                     HpDjEYCNA_XOmzg8yZR9oyjo6lTF6si4q9FZ2EhzgFQCLO_6h5EVg3vR75_hkBsnuoqoM3dwejXBtIodN84PeqMb6asmas_dpS\
+                     sz7H10fC5ni9xIz424givB1YLldF6exVmL93R3fOoOJbmk2GBQZL_SEGllv2cQsBgeprARsaQ7Bq99tT80coH8ItBjgV08AtzX\
+                     FFsx9qKvC982KLKdPQMTlVJKkqtV4Ru5LEVpBZXBnZrtViSOgyg6AiuwaS-rCrcD_ePOGSuxvgtrokAKYPqmXUeRdjFJwafkYE\
+                     kiuDCV9vWGAi1DH2xTafhJwcmywIyzi4BqRpmdn_N-zl5tuJYyuvKhjKv6ihbsV_k1hJGPGAxJ6wUpmwC4PTQ2izEm0TuSE8oM\
+                     KdTw8V3kobXZ77ulMwDs4p.\
+                     0HlwodAhOCILG5SQ2LQ9dg";
+
+        let jwe: Compact<Vec<u8>, ::Empty> = Compact::new_encrypted(token);
+        let decrypted_jwe = not_err!(jwe.decrypt(&password,
+                                                 KeyManagementAlgorithm::PBES2_HS512_A256KW,
+                                                 ContentEncryptionAlgorithm::A128CBC_HS256));
+        let decrypted_payload: &Vec<u8> = not_err!(decrypted_jwe.payload());
+        let key_set: jwk::JWKSet<::Empty> = not_err!(serde_json::from_slice(decrypted_payload));
+        assert_eq!(key_set.keys.len(), 3);
+        assert_eq!(key_set.keys[1].common.key_id,
+                   Some("81b20965-8332-43d9-a468-82160ad91ac8".to_string()));
+        let decrypted_header = not_err!(decrypted_jwe.header());
+        assert_eq!(decrypted_header.registered.content_type, Some("jwk-set+json".to_string()));
+        assert_eq!(decrypted_header.cek_algorithm, Default::default());
+    }
+
+    #[test]
+    fn pbes2_round_trip() {
+        let payload = "The true sign of intelligence is not knowledge but imagination.";
+        let password = jwk::JWK::<::Empty>::new_octect_key(b"correct horse battery staple", Default::default());
+
+        let algorithms = [(KeyManagementAlgorithm::PBES2_HS256_A128KW, ContentEncryptionAlgorithm::A128GCM),
+                          (KeyManagementAlgorithm::PBES2_HS384_A192KW, ContentEncryptionAlgorithm::A192CBC_HS384),
+                          (KeyManagementAlgorithm::PBES2_HS512_A256KW, ContentEncryptionAlgorithm::A256GCM)];
+
+        for &(cek_alg, enc_alg) in &algorithms {
+            let mut header = Header::from(RegisteredHeader {
+                                              cek_algorithm: cek_alg,
+                                              enc_algorithm: enc_alg,
+                                              ..Default::default()
+                                          });
+            header.cek_algorithm.iteration_count = Some(2048);
+            let jwe = Compact::new_decrypted(header, payload.as_bytes().to_vec());
+            let encrypted_jwe = not_err!(jwe.encrypt(&password));
+
+            // The salt input is generated, and the iteration count is kept
+            let encrypted_header: Header<::Empty> = not_err!(not_err!(encrypted_jwe.encrypted()).part(0));
+            assert_eq!(encrypted_header.cek_algorithm.salt_input.map(|salt| salt.len()), Some(16));
+            assert_eq!(encrypted_header.cek_algorithm.iteration_count, Some(2048));
+
+            let decrypted_jwe = not_err!(encrypted_jwe.decrypt(&password, cek_alg, enc_alg));
+            let decrypted_payload: &Vec<u8> = not_err!(decrypted_jwe.payload());
+            assert_eq!(decrypted_payload.as_slice(), payload.as_bytes());
+
+            let wrong_password = jwk::JWK::<::Empty>::new_octect_key(b"Tr0ub4dor&3", Default::default());
+            assert!(encrypted_jwe.decrypt(&wrong_password, cek_alg, enc_alg).is_err());
+        }
+    }
+
+    #[test]
+    fn pbes2_decrypt_enforces_iteration_count_bounds() {
+        let password = jwk::JWK::<::Empty>::new_octect_key(b"correct horse battery staple", Default::default());
+        let cek_alg = KeyManagementAlgorithm::PBES2_HS256_A128KW;
+        let enc_alg = ContentEncryptionAlgorithm::A128GCM;
+        let encrypt = |iteration_count| {
+            let mut header = Header::from(RegisteredHeader {
+                                              cek_algorithm: cek_alg,
+                                              enc_algorithm: enc_alg,
+                                              ..Default::default()
+                                          });
+            header.cek_algorithm.iteration_count = Some(iteration_count);
+            let jwe: Compact<Vec<u8>, ::Empty> = Compact::new_decrypted(header, b"payload".to_vec());
+            not_err!(jwe.encrypt(&password))
+        };
+
+        let too_few_iterations = encrypt(999);
+        assert_matches!(too_few_iterations.decrypt(&password, cek_alg, enc_alg),
+                        Err(Error::GenericError(_)));
+
+        let options = DecryptionOptions {
+            pbes2_max_iteration_count: 4096,
+            ..Default::default()
+        };
+        let too_many_iterations = encrypt(4097);
+        assert_matches!(too_many_iterations.decrypt_with_options(&password, cek_alg, enc_alg, options),
+                        Err(Error::GenericError(_)));
+        let allowed_iterations = encrypt(4096);
+        not_err!(allowed_iterations.decrypt_with_options(&password, cek_alg, enc_alg, options));
+    }
+
+    #[test]
+    fn pbes2_decrypt_requires_salt_input() {
+        let password = jwk::JWK::<::Empty>::new_octect_key(b"correct horse battery staple", Default::default());
+        let mut header = Header::from(RegisteredHeader {
+                                          cek_algorithm: KeyManagementAlgorithm::PBES2_HS256_A128KW,
+                                          enc_algorithm: ContentEncryptionAlgorithm::A128GCM,
+                                          ..Default::default()
+                                      });
+        header.cek_algorithm.iteration_count = Some(1000);
+        let jwe = Compact::new_decrypted(header, b"payload".to_vec());
+        let encrypted_jwe = not_err!(jwe.encrypt(&password));
+
+        // Replace the header with one without `p2s`
+        let mut compact = encrypted_jwe.unwrap_encrypted();
+        let mut header: Header<::Empty> = not_err!(compact.part(0));
+        header.cek_algorithm.salt_input = None;
+        compact.parts[0] = not_err!(header.to_base64());
+        let jwe: Compact<Vec<u8>, ::Empty> = Compact::Encrypted(compact);
+
+        let result = jwe.decrypt(&password,
+                                 KeyManagementAlgorithm::PBES2_HS256_A128KW,
+                                 ContentEncryptionAlgorithm::A128GCM);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::MissingRequired(_))));
+    }
+
+    /// Example from [RFC 7520 Section 5.9](https://tools.ietf.org/html/rfc7520#section-5.9)
+    #[test]
+    fn deflate_a128kw_a128gcm_rfc7520_example() {
+        let key: jwk::JWK<::Empty> = not_err!(serde_json::from_value(json!({
+            "kty": "oct",
+            "kid": "81b20965-8332-43d9-a468-82160ad91ac8",
+            "use": "enc",
+            "alg": "A128KW",
+            "k": "GZy6sIZ6wl9NJOKB-jnmVQ"
+        })));
+        let token = "eyJhbGciOiJBMTI4S1ciLCJraWQiOiI4MWIyMDk2NS04MzMyLTQzZDktYTQ2OC04MjE2MGFkOTFhYzgiLCJlbmMiOiJBMTI4R0\
+                     NNIiwiemlwIjoiREVGIn0.\
+                     5vUT2WOtQxKWcekM_IzVQwkGgzlFDwPi.\
+                     p9pUq6XHY0jfEZIl.\
+                     HbDtOsdai1oYziSx25KEeTxmwnh8L8jKMFNc1k3zmMI6VB8hry57tDZ61jXyezSPt0fdLVfe6Jf5y5-JaCap_JQBcb5opbmT60\
+                     uWGml8blyiMQmOn9J--XhhlYg0m-BHaqfDO5iTOWxPxFMUedx7WCy8mxgDHj0aBMG6152PsM-w5E_o2B3jDbrYBKhpYA7qi3Ay\
+                     ijnCJ7BP9rr3U8kxExCpG3mK420TjOw.\
+                     VILuUwuIxaLVmh5X-T7kmA";
+
+        let jwe: Compact<Vec<u8>, ::Empty> = Compact::new_encrypted(token);
+        let decrypted_jwe = not_err!(jwe.decrypt(&key,
+                                                 KeyManagementAlgorithm::A128KW,
+                                                 ContentEncryptionAlgorithm::A128GCM));
+        let decrypted_payload: &Vec<u8> = not_err!(decrypted_jwe.payload());
+        assert_eq!(decrypted_payload.as_slice(), RFC7520_PLAINTEXT.as_bytes());
+        let decrypted_header = not_err!(decrypted_jwe.header());
+        assert_eq!(decrypted_header.registered.compression_algorithm,
+                   Some(CompressionAlgorithm::Deflate));
+    }
+
+    #[test]
+    fn deflate_round_trip() {
+        let payload = RFC7520_PLAINTEXT.repeat(10);
+        let key = cek_oct_key(256 / 8);
+        let header = RegisteredHeader {
+            cek_algorithm: KeyManagementAlgorithm::A256GCMKW,
+            enc_algorithm: ContentEncryptionAlgorithm::A256GCM,
+            ..Default::default()
+        };
+        let compressed_header = RegisteredHeader {
+            compression_algorithm: Some(CompressionAlgorithm::Deflate),
+            ..header.clone()
+        };
+
+        let jwe = Compact::new_decrypted(From::from(header), payload.as_bytes().to_vec());
+        let encrypted_jwe = not_err!(jwe.encrypt(&key));
+        let compressed_jwe = Compact::new_decrypted(From::from(compressed_header), payload.as_bytes().to_vec());
+        let encrypted_compressed_jwe = not_err!(compressed_jwe.encrypt(&key));
+
+        // The ciphertext is smaller than the uncompressed one
+        let ciphertext_length = |jwe: &Compact<Vec<u8>, ::Empty>| not_err!(jwe.encrypted()).parts[3].len();
+        assert!(ciphertext_length(&encrypted_compressed_jwe) < ciphertext_length(&encrypted_jwe) / 2);
+
+        let decrypted_jwe = not_err!(encrypted_compressed_jwe.decrypt(&key,
+                                                                      KeyManagementAlgorithm::A256GCMKW,
+                                                                      ContentEncryptionAlgorithm::A256GCM));
+        let decrypted_payload: &Vec<u8> = not_err!(decrypted_jwe.payload());
+        assert_eq!(decrypted_payload.as_slice(), payload.as_bytes());
+    }
+
+    #[test]
+    fn deflate_decompression_limit() {
+        let key = cek_oct_key(256 / 8);
+        let jwe = Compact::new_decrypted(From::from(RegisteredHeader {
+                                                        cek_algorithm: KeyManagementAlgorithm::A256GCMKW,
+                                                        enc_algorithm: ContentEncryptionAlgorithm::A256GCM,
+                                                        compression_algorithm: Some(CompressionAlgorithm::Deflate),
+                                                        ..Default::default()
+                                                    }),
+                                         vec![0; 4096]);
+        let encrypted_jwe = not_err!(jwe.encrypt(&key));
+
+        let options = DecryptionOptions {
+            max_decompressed_size: 4095,
+            ..Default::default()
+        };
+        let result = encrypted_jwe.decrypt_with_options(&key,
+                                                        KeyManagementAlgorithm::A256GCMKW,
+                                                        ContentEncryptionAlgorithm::A256GCM,
+                                                        options);
+        assert_matches!(result, Err(Error::DecompressionLimitExceeded { limit: 4095 }));
+
+        let options = DecryptionOptions {
+            max_decompressed_size: 4096,
+            ..Default::default()
+        };
+        let decrypted_jwe = not_err!(encrypted_jwe.decrypt_with_options(&key,
+                                                                        KeyManagementAlgorithm::A256GCMKW,
+                                                                        ContentEncryptionAlgorithm::A256GCM,
+                                                                        options));
+        assert_eq!(not_err!(decrypted_jwe.payload()), &vec![0; 4096]);
+    }
+
+    /// Example from [RFC 7520 Section 5.13](https://tools.ietf.org/html/rfc7520#section-5.13)
+    #[test]
+    fn json_multiple_recipients_rfc7520_example() {
+        let serialization: JsonSerialization = not_err!(serde_json::from_value(json!({
+            "recipients": [
+                {
+                    "encrypted_key": "dYOD28kab0Vvf4ODgxVAJXgHcSZICSOp8M51zjwj4w6Y5G4XJQsNNIBiqyvUUAOcpL7S7-cFe7Pio7gV_\
+                                      Q06WmCSa-vhW6me4bWrBf7cHwEQJdXihidAYWVajJIaKMXMvFRMV6iDlRr076DFthg2_AV0_tSiV6xSEI\
+                                      Fqt1xnYPpmP91tc5WJDOGb-wqjw0-b-S1laS11QVbuP78dQ7Fa0zAVzzjHX-xvyM2wxj_otxr9clN1LnZ\
+                                      MbeYSrRicJK5xodvWgkpIdkMHo4LvdhRRvzoKzlic89jFWPlnBq_V4n5trGuExtp_-dbHcGlihqc_wGgh\
+                                      o9fLMK8JOArYLcMDNQ",
+                    "header": {
+                        "alg": "RSA1_5",
+                        "kid": "frodo.baggins@hobbiton.example"
+                    }
+                },
+                {
+                    "encrypted_key": "ExInT0io9BqBMYF6-maw5tZlgoZXThD1zWKsHixJuw_elY4gSSId_w",
+                    "header": {
+                        "alg": "ECDH-ES+A256KW",
+                        "kid": "peregrin.took@tuckborough.example",
+                        "epk": {
+                            "kty": "EC",
+                            "crv": "P-384",
+                            "x": "Uzdvk3pi5wKCRc1izp5_r0OjeqT-I68i8g2b8mva8diRhsE2xAn2DtMRb25Ma2CX",
+                            "y": "VDrRyFJh-Kwd1EjAgmj5Eo-CTHAZ53MC7PjjpLioy3ylEjI1pOMbw91fzZ84pbfm"
+                        }
+                    }
+                },
+                {
+                    "encrypted_key": "a7CclAejo_7JSuPB8zeagxXRam8dwCfmkt9-WyTpS1E",
+                    "header": {
+                        "alg": "A256GCMKW",
+                        "kid": "18ec08e1-bfa9-4d95-b205-2b4dd1d4321d",
+                        "tag": "59Nqh1LlYtVIhfD3pgRGvw",
+                        "iv": "AvpeoPZ9Ncn9mkBn"
+                    }
+                }
+            ],
+            "unprotected": {
+                "cty": "text/plain"
+            },
+            "protected": "eyJlbmMiOiJBMTI4Q0JDLUhTMjU2In0",
+            "iv": "VgEIHY20EnzUtZFl2RpB1g",
+            "ciphertext": "ajm2Q-OpPXCr7-MHXicknb1lsxLdXxK_yLds0KuhJzfWK04SjdxQeSw2L9mu3a_k1C55kCQ_3xlkcVKC5yr__Is48VOo\
+                           K0k63_QRM9tBURMFqLByJ8vOYQX0oJW4VUHJLmGhF-tVQWB7Kz8mr8zeE7txF0MSaP6ga7-siYxStR7_G07Thd1jh-zG\
+                           T0wxM5g-VRORtq0K6AXpLlwEqRp7pkt2zRM0ZAXqSpe1O6FJ7FHLDyEFnD-zDIZukLpCbzhzMDLLw2-8I14FQrgi-iEu\
+                           zHgIJFIJn2wh9Tj0cg_kOZy9BqMRZbmYXMY9YQjorZ_P_JYG3ARAIF3OjDNqpdYe-K_5Q5crGJSDNyij_ygEiItR5jss\
+                           QVH2ofDQdLChtazE",
+            "tag": "BESYyFN7T09KY7i8zKs5_g"
+        })));
+        let aes_key: jwk::JWK<::Empty> = not_err!(serde_json::from_value(json!({
+            "kty": "oct",
+            "kid": "18ec08e1-bfa9-4d95-b205-2b4dd1d4321d",
+            "use": "enc",
+            "alg": "A256GCMKW",
+            "k": "qC57l_uxcm7Nm3K-ct4GFjx8tM1U8CZ0NLBvdQstiS8"
+        })));
+        #[allow(unused_mut)]
+        let mut recipients = vec![(rfc7520_p384_private_key(), KeyManagementAlgorithm::ECDH_ES_A256KW),
+                                  (aes_key, KeyManagementAlgorithm::A256GCMKW)];
+        #[cfg(feature = "rsa-decryption")]
+        recipients.push((rfc7520_rsa_private_key(), KeyManagementAlgorithm::RSA1_5));
+
+        let jwe: Json<Vec<u8>, ::Empty> = Json::new_encrypted(serialization);
+        let result = jwe.decrypt(&rfc7520_rsa_private_key(),
+                                 KeyManagementAlgorithm::RSA1_5,
+                                 ContentEncryptionAlgorithm::A128CBC_HS256);
+        assert_matches!(result, Err(Error::GenericError(_)));
+
+        let options = DecryptionOptions {
+            allow_rsa1_5: true,
+            ..Default::default()
+        };
+        for &(ref key, cek_alg) in &recipients {
+            let decrypted_jwe = not_err!(jwe.decrypt_with_options(key,
+                                                                  cek_alg,
+                                                                  ContentEncryptionAlgorithm::A128CBC_HS256,
+                                                                  options));
+            let decrypted_payload: &Vec<u8> = not_err!(decrypted_jwe.payload());
+            assert_eq!(decrypted_payload.as_slice(), RFC7520_PLAINTEXT.as_bytes());
+
+            let decrypted_recipients = not_err!(decrypted_jwe.recipients());
+            assert_eq!(decrypted_recipients.len(), 1);
+            let header = &decrypted_recipients[0];
+            assert_eq!(header.header.registered.cek_algorithm, cek_alg);
+            assert_eq!(header.header.registered.key_id, key.common.key_id);
+            assert_eq!(header.header.registered.content_type, Some("text/plain".to_string()));
+            assert_eq!(header.header.cek_algorithm, Default::default());
+            assert_eq!(header.shared_unprotected, vec!["cty".to_string()]);
+            assert_eq!(header.unprotected, vec!["alg".to_string(), "kid".to_string()]);
+        }
+    }
+
+    /// Example from [RFC 7520 Section 5.10](https://tools.ietf.org/html/rfc7520#section-5.10)
+    #[test]
+    fn json_additional_authenticated_data_rfc7520_example() {
+        let key: jwk::JWK<::Empty> = not_err!(serde_json::from_value(json!({
+            "kty": "oct",
+            "kid": "81b20965-8332-43d9-a468-82160ad91ac8",
+            "use": "enc",
+            "alg": "A128KW",
+            "k": "GZy6sIZ6wl9NJOKB-jnmVQ"
+        })));
+        let general = json!({
+            "recipients": [
+                {
+                    "encrypted_key": "4YiiQ_ZzH76TaIkJmYfRFgOV9MIpnx4X"
+                }
+            ],
+            "protected": "eyJhbGciOiJBMTI4S1ciLCJraWQiOiI4MWIyMDk2NS04MzMyLTQzZDktYTQ2OC04MjE2MGFkOTFhYzgiLCJlbmMiOiJBM\
+                          TI4R0NNIn0",
+            "iv": "veCx9ece2orS7c_N",
+            "aad": "WyJ2Y2FyZCIsW1sidmVyc2lvbiIse30sInRleHQiLCI0LjAiXSxbImZuIix7fSwidGV4dCIsIk1lcmlhZG9jIEJyYW5keWJ1Y2s\
+                    iXSxbIm4iLHt9LCJ0ZXh0IixbIkJyYW5keWJ1Y2siLCJNZXJpYWRvYyIsIk1yLiIsIiJdXSxbImJkYXkiLHt9LCJ0ZXh0IiwiVE\
+                    EgMjk4MiJdLFsiZ2VuZGVyIix7fSwidGV4dCIsIk0iXV1d",
+            "ciphertext": "Z_3cbr0k3bVM6N3oSNmHz7Lyf3iPppGf3Pj17wNZqteJ0Ui8p74SchQP8xygM1oFRWCNzeIa6s6BcEtp8qEFiqTUEyiN\
+                           kOWDNoF14T_4NFqF-p2Mx8zkbKxI7oPK8KNarFbyxIDvICNqBLba-v3uzXBdB89fzOI-Lv4PjOFAQGHrgv1rjXAmKbgk\
+                           ft9cB4WeyZw8MldbBhc-V_KWZslrsLNygon_JJWd_ek6LQn5NRehvApqf9ZrxB4aq3FXBxOxCys35PhCdaggy2kfUfl2\
+                           OkwKnWUbgXVD1C6HxLIlqHhCwXDG59weHrRDQeHyMRoBljoV3X_bUTJDnKBFOod7nLz-cj48JMx3SnCZTpbQAkFV",
+            "tag": "vOaH_Rajnpy_3hOtqvZHRA"
+        });
+        let flattened = json!({
+            "protected": "eyJhbGciOiJBMTI4S1ciLCJraWQiOiI4MWIyMDk2NS04MzMyLTQzZDktYTQ2OC04MjE2MGFkOTFhYzgiLCJlbmMiOiJBM\
+                          TI4R0NNIn0",
+            "encrypted_key": "4YiiQ_ZzH76TaIkJmYfRFgOV9MIpnx4X",
+            "aad": "WyJ2Y2FyZCIsW1sidmVyc2lvbiIse30sInRleHQiLCI0LjAiXSxbImZuIix7fSwidGV4dCIsIk1lcmlhZG9jIEJyYW5keWJ1Y2s\
+                    iXSxbIm4iLHt9LCJ0ZXh0IixbIkJyYW5keWJ1Y2siLCJNZXJpYWRvYyIsIk1yLiIsIiJdXSxbImJkYXkiLHt9LCJ0ZXh0IiwiVE\
+                    EgMjk4MiJdLFsiZ2VuZGVyIix7fSwidGV4dCIsIk0iXV1d",
+            "iv": "veCx9ece2orS7c_N",
+            "ciphertext": "Z_3cbr0k3bVM6N3oSNmHz7Lyf3iPppGf3Pj17wNZqteJ0Ui8p74SchQP8xygM1oFRWCNzeIa6s6BcEtp8qEFiqTUEyiN\
+                           kOWDNoF14T_4NFqF-p2Mx8zkbKxI7oPK8KNarFbyxIDvICNqBLba-v3uzXBdB89fzOI-Lv4PjOFAQGHrgv1rjXAmKbgk\
+                           ft9cB4WeyZw8MldbBhc-V_KWZslrsLNygon_JJWd_ek6LQn5NRehvApqf9ZrxB4aq3FXBxOxCys35PhCdaggy2kfUfl2\
+                           OkwKnWUbgXVD1C6HxLIlqHhCwXDG59weHrRDQeHyMRoBljoV3X_bUTJDnKBFOod7nLz-cj48JMx3SnCZTpbQAkFV",
+            "tag": "vOaH_Rajnpy_3hOtqvZHRA"
+        });
+
+        for serialization in &[general, flattened] {
+            let serialization: JsonSerialization = not_err!(serde_json::from_value(serialization.clone()));
+            let jwe: Json<Vec<u8>, ::Empty> = Json::new_encrypted(serialization.clone());
+            let decrypted_jwe = not_err!(jwe.decrypt(&key,
+                                                     KeyManagementAlgorithm::A128KW,
+                                                     ContentEncryptionAlgorithm::A128GCM));
+            let decrypted_payload: &Vec<u8> = not_err!(decrypted_jwe.payload());
+            assert_eq!(decrypted_payload.as_slice(), RFC7520_PLAINTEXT.as_bytes());
+            let additional_data: serde_json::Value =
+                not_err!(serde_json::from_slice(not_err!(decrypted_jwe.additional_data()).unwrap()));
+            assert_eq!(additional_data[1][1][3], "Meriadoc Brandybuck");
+
+            // The Additional Authenticated Data is integrity protected
+            let mut tampered = not_err!(serde_json::to_value(&serialization));
+            tampered["aad"] = From::from("WyJ2Y2FyZCJd");
+            let jwe: Json<Vec<u8>, ::Empty> = Json::new_encrypted(not_err!(serde_json::from_value(tampered)));
+            let result = jwe.decrypt(&key, KeyManagementAlgorithm::A128KW, ContentEncryptionAlgorithm::A128GCM);
+            assert_matches!(result, Err(Error::UnspecifiedCryptographicError));
+        }
+    }
+
+    #[test]
+    fn json_multiple_recipients_round_trip() {
+        let payload = "The true sign of intelligence is not knowledge but imagination.";
+        let mut aes_key = cek_oct_key(128 / 8);
+        aes_key.common.key_id = Some("aes".to_string());
+        let mut ec_key: jwk::JWK<::Empty> = not_err!(serde_json::from_value(json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": "Ze2loSV3wrroKUN_4zhwGhCqo3Xhu1td4QjeQ5wIVR0",
+            "y": "HlLtdXARY_f55A3fnzQbPcm6hgr34Mp8p-nuzQCE0Zw",
+            "d": "r_kHyZ-a06rmxM3yESK84r1otSg-aQcVStkRhA-iCM8"
+        })));
+        ec_key.common.key_id = Some("ec".to_string());
+        let password = jwk::JWK::<::Empty>::new_octect_key(b"correct horse battery staple", Default::default());
+        let recipients = [(&aes_key, KeyManagementAlgorithm::A128KW),
+                          (&ec_key, KeyManagementAlgorithm::ECDH_ES_A128KW),
+                          (&password, KeyManagementAlgorithm::PBES2_HS256_A128KW)];
+
+        let headers = recipients
+            .iter()
+            .map(|&(key, cek_alg)| {
+                let mut header = Header::from(RegisteredHeader {
+                                                  cek_algorithm: cek_alg,
+                                                  enc_algorithm: ContentEncryptionAlgorithm::A256GCM,
+                                                  compression_algorithm: Some(CompressionAlgorithm::Deflate),
+                                                  content_type: Some("text/plain".to_string()),
+                                                  key_id: key.common.key_id.clone(),
+                                                  ..Default::default()
+                                              });
+                header.cek_algorithm.iteration_count = Some(1000);
+                JsonHeader::new(header, &["cty"], &["alg", "kid"])
+            })
+            .collect();
+        let jwe = Json::new_decrypted(payload.as_bytes().to_vec(), headers, Some(b"metadata".to_vec()));
+        let keys: Vec<_> = recipients.iter().map(|&(key, _)| key).collect();
+        let encrypted_jwe = not_err!(jwe.encrypt(&keys));
+
+        // Only `enc` and `zip` are shared and protected
+        let serialized = not_err!(serde_json::to_value(not_err!(encrypted_jwe.encrypted())));
+        assert_eq!(serialized["recipients"].as_array().map(|recipients| recipients.len()), Some(3));
+        assert_eq!(serialized["unprotected"], json!({ "cty": "text/plain" }));
+        let protected = not_err!(base64url::decode_nopad(serialized["protected"].as_str().unwrap().as_bytes()));
+        assert_eq!(not_err!(serde_json::from_slice::<serde_json::Value>(&protected)),
+                   json!({ "enc": "A256GCM", "zip": "DEF" }));
+
+        let encrypted_jwe: Json<Vec<u8>, ::Empty> = Json::new_encrypted(not_err!(serde_json::from_value(serialized)));
+        for &(key, cek_alg) in &recipients {
+            let decrypted_jwe = not_err!(encrypted_jwe.decrypt(key, cek_alg, ContentEncryptionAlgorithm::A256GCM));
+            let decrypted_payload: &Vec<u8> = not_err!(decrypted_jwe.payload());
+            assert_eq!(decrypted_payload.as_slice(), payload.as_bytes());
+            assert_eq!(not_err!(decrypted_jwe.additional_data()), Some(&b"metadata"[..]));
+            assert_eq!(not_err!(decrypted_jwe.recipients())[0].header.registered.key_id,
+                       key.common.key_id);
+        }
+
+        // No recipient uses the key or the algorithms
+        let mut other_key = cek_oct_key(128 / 8);
+        other_key.common.key_id = Some("other".to_string());
+        let result = encrypted_jwe.decrypt(&other_key,
+                                           KeyManagementAlgorithm::A128KW,
+                                           ContentEncryptionAlgorithm::A256GCM);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::KeyNotFound(_))));
+        let result = encrypted_jwe.decrypt(&aes_key,
+                                           KeyManagementAlgorithm::A256KW,
+                                           ContentEncryptionAlgorithm::A256GCM);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::WrongAlgorithmHeader)));
+    }
+
+    #[test]
+    fn json_flattened_round_trip() {
+        let payload = "The true sign of intelligence is not knowledge but imagination.";
+        let key = cek_oct_key(256 / 8);
+        let header = Header::from(RegisteredHeader {
+                                      cek_algorithm: KeyManagementAlgorithm::DirectSymmetricKey,
+                                      enc_algorithm: ContentEncryptionAlgorithm::A256GCM,
+                                      ..Default::default()
+                                  });
+        let jwe = Json::new_decrypted(payload.as_bytes().to_vec(), vec![From::from(header)], None);
+        let encrypted_jwe = not_err!(jwe.encrypt_flattened(&key));
+        assert_matches!(not_err!(encrypted_jwe.encrypted()),
+                        &JsonSerialization::Flattened {
+                             header: None,
+                             encrypted_key: None,
+                             aad: None,
+                             ..
+                         });
+
+        let serialized = not_err!(serde_json::to_string(&encrypted_jwe));
+        let encrypted_jwe: Json<Vec<u8>, ::Empty> = not_err!(serde_json::from_str(&serialized));
+        let decrypted_jwe = not_err!(encrypted_jwe.decrypt(&key,
+                                                           KeyManagementAlgorithm::DirectSymmetricKey,
+                                                           ContentEncryptionAlgorithm::A256GCM));
+        let decrypted_payload: &Vec<u8> = not_err!(decrypted_jwe.payload());
+        assert_eq!(decrypted_payload.as_slice(), payload.as_bytes());
+    }
+
+    #[test]
+    fn json_encryption_errors() {
+        let key = cek_oct_key(256 / 8);
+        let header = |cek_alg, key_id: &str| {
+            Header::from(RegisteredHeader {
+                             cek_algorithm: cek_alg,
+                             enc_algorithm: ContentEncryptionAlgorithm::A256GCM,
+                             key_id: Some(key_id.to_string()),
+                             ..Default::default()
+                         })
+        };
+
+        // Direct encryption can only be used with a single recipient
+        let jwe = Json::new_decrypted(b"payload".to_vec(),
+                                      vec![JsonHeader::new(header(KeyManagementAlgorithm::DirectSymmetricKey, "a"),
+                                                           &[],
+                                                           &["alg", "kid"]),
+                                           JsonHeader::new(header(KeyManagementAlgorithm::A256KW, "b"),
+                                                           &[],
+                                                           &["alg", "kid"])],
+                                      None);
+        assert_matches!(jwe.encrypt(&[&key, &key]), Err(Error::GenericError(_)));
+
+        // The protected header parameters must be the same for all recipients
+        let jwe = Json::new_decrypted(b"payload".to_vec(),
+                                      vec![JsonHeader::new(header(KeyManagementAlgorithm::A256KW, "a"), &[], &["alg"]),
+                                           JsonHeader::new(header(KeyManagementAlgorithm::A256KW, "b"), &[], &["alg"])],
+                                      None);
+        assert_matches!(jwe.encrypt(&[&key, &key]), Err(Error::GenericError(_)));
+
+        // `zip` must be integrity protected
+        let mut compressed_header = header(KeyManagementAlgorithm::A256KW, "a");
+        compressed_header.registered.compression_algorithm = Some(CompressionAlgorithm::Deflate);
+        let jwe = Json::new_decrypted(b"payload".to_vec(),
+                                      vec![JsonHeader::new(compressed_header, &["zip"], &[])],
+                                      None);
+        assert_matches!(jwe.encrypt(&[&key]), Err(Error::GenericError(_)));
+
+        // Every recipient needs a key
+        let jwe = Json::new_decrypted(b"payload".to_vec(),
+                                      vec![From::from(header(KeyManagementAlgorithm::A256KW, "a"))],
+                                      None);
+        assert_matches!(jwe.encrypt(&[&key, &key]), Err(Error::GenericError(_)));
+        assert_matches!(jwe.encrypt_flattened(&key), Ok(_));
+    }
+
     #[test]
     fn jwe_a256gcmkw_a256gcm_jws_round_trip() {
         // Construct the JWS
@@ -732,6 +2786,64 @@ mod tests {
         assert_eq!(jws, *decrypted_jws);
     }
 
+    #[derive(Debug, Eq, PartialEq, Clone, Default, Serialize, Deserialize)]
+    struct ExtensionHeader {
+        #[serde(rename = "http://example.com/jwe-extension", skip_serializing_if = "Option::is_none")]
+        extension: Option<bool>,
+    }
+
+    fn encrypt_and_decrypt_with_critical(critical: &[&str],
+                                         extension: Option<bool>,
+                                         understood: &[&str])
+                                         -> Result<Compact<Vec<u8>, ExtensionHeader>, Error> {
+        let key = cek_oct_key(256 / 8);
+        let jwe = Compact::new_decrypted(Header {
+                                             registered: RegisteredHeader {
+                                                 cek_algorithm: KeyManagementAlgorithm::A256GCMKW,
+                                                 enc_algorithm: ContentEncryptionAlgorithm::A256GCM,
+                                                 critical: Some(critical
+                                                                    .iter()
+                                                                    .map(|name| name.to_string())
+                                                                    .collect()),
+                                                 ..Default::default()
+                                             },
+                                             cek_algorithm: Default::default(),
+                                             private: ExtensionHeader { extension: extension },
+                                         },
+                                         b"payload".to_vec());
+        let encrypted_jwe = not_err!(jwe.encrypt(&key));
+        let options = DecryptionOptions {
+            understood_critical_headers: understood,
+            ..Default::default()
+        };
+        encrypted_jwe.decrypt_with_options(&key,
+                                           KeyManagementAlgorithm::A256GCMKW,
+                                           ContentEncryptionAlgorithm::A256GCM,
+                                           options)
+    }
+
+    #[test]
+    fn decrypt_with_understood_critical_header() {
+        let extension = ["http://example.com/jwe-extension"];
+        let result = encrypt_and_decrypt_with_critical(&extension, Some(true), &[]);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::InvalidCriticalHeader(_))));
+
+        let decrypted_jwe = not_err!(encrypt_and_decrypt_with_critical(&extension, Some(true), &extension));
+        assert_eq!(not_err!(decrypted_jwe.header()).private.extension, Some(true));
+
+        // The extension must be present
+        let result = encrypt_and_decrypt_with_critical(&extension, None, &extension);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::InvalidCriticalHeader(_))));
+    }
+
+    #[test]
+    fn decrypt_with_invalid_critical_header() {
+        for critical in &[&[][..], &["enc"][..]] {
+            let result = encrypt_and_decrypt_with_critical(critical, None, &["enc"]);
+            assert_matches!(result, Err(Error::ValidationError(ValidationError::InvalidCriticalHeader(_))));
+        }
+    }
+
     #[test]
     #[should_panic(expected = "WrongAlgorithmHeader")]
     fn decrypt_with_mismatch_cek_algorithm() {
@@ -2,15 +2,27 @@
 //!
 //! This module implements code for JWK as described in [RFC7517](https://tools.ietf.org/html/rfc7517).
 use std::fmt;
+use std::str::FromStr;
 
+use data_encoding::base64url;
 use num::BigUint;
+use p256;
+use p256::elliptic_curve::{AffinePoint, CurveArithmetic, FieldBytesSize, SecretKey};
+use p256::elliptic_curve::sec1::{FromEncodedPoint, ModulusSize, ToEncodedPoint};
+use p384;
+use rand::rngs::OsRng;
+use ring::{digest, signature};
+use rsa::{self, RsaPrivateKey};
+use rsa::traits::{PrivateKeyParts, PublicKeyParts};
 use serde::{self, Serialize, Deserialize, Serializer, Deserializer};
 use serde::de::{self, DeserializeOwned};
 use serde_json;
+use x25519_dalek;
 
+use StringOrUri;
 use errors::Error;
 use serde_custom;
-use jwa::Algorithm;
+use jwa::{self, Algorithm, KeyManagementAlgorithm, SignatureAlgorithm};
 
 /// Type of Key as specified in RFC 7518.
 #[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Copy, Clone)]
@@ -240,6 +252,9 @@ pub enum AlgorithmParameters {
         #[serde(rename = "k", with = "serde_custom::byte_sequence")]
         value: Vec<u8>,
     },
+
+    /// An Octet Key Pair, as defined in [RFC 8037](https://tools.ietf.org/html/rfc8037)
+    OctetKeyPair(OctetKeyPairParameters),
 }
 
 impl fmt::Debug for AlgorithmParameters {
@@ -248,6 +263,7 @@ impl fmt::Debug for AlgorithmParameters {
             AlgorithmParameters::EllipticCurve(_) => "EllipticCurve",
             AlgorithmParameters::RSA(_) => "RSA",
             AlgorithmParameters::OctectKey { .. } => "OctectKey",
+            AlgorithmParameters::OctetKeyPair(_) => "OctetKeyPair",
         };
         write!(f, "{} {{ .. }}", algo_type)
     }
@@ -261,6 +277,7 @@ impl AlgorithmParameters {
             AlgorithmParameters::EllipticCurve(_) => KeyType::EllipticCurve,
             AlgorithmParameters::RSA(_) => KeyType::RSA,
             AlgorithmParameters::OctectKey { .. } => KeyType::Octect,
+            AlgorithmParameters::OctetKeyPair(_) => KeyType::OctectKeyPair,
         }
     }
 
@@ -271,6 +288,67 @@ impl AlgorithmParameters {
             _ => Err(unexpected_key_type_error!(KeyType::Octect, self.key_type())),
         }
     }
+
+    /// Returns the JSON object hashed to compute the [JWK Thumbprint](https://tools.ietf.org/html/rfc7638) of the
+    /// key. As described in [RFC 7638 Section 3.2](https://tools.ietf.org/html/rfc7638#section-3.2), it only
+    /// contains the required members of the key type, in lexicographic order and without whitespace.
+    pub fn thumbprint_json(&self) -> Result<String, Error> {
+        let members: &[&str] = match *self {
+            AlgorithmParameters::EllipticCurve(_) => &["crv", "kty", "x", "y"],
+            AlgorithmParameters::RSA(_) => &["e", "kty", "n"],
+            AlgorithmParameters::OctectKey { .. } => &["k", "kty"],
+            AlgorithmParameters::OctetKeyPair(_) => &["crv", "kty", "x"],
+        };
+
+        let value = serde_json::to_value(self)?;
+        let required: serde_json::Map<String, serde_json::Value> = members
+            .iter()
+            .map(|member| (member.to_string(), value[*member].clone()))
+            .collect();
+        Ok(serde_json::to_string(&required)?)
+    }
+
+    /// Returns whether the parameters contain private key material. Symmetric keys are always private.
+    pub fn is_private(&self) -> bool {
+        match *self {
+            AlgorithmParameters::EllipticCurve(ref parameters) => parameters.d.is_some(),
+            AlgorithmParameters::RSA(ref parameters) => {
+                parameters.d.is_some() || parameters.p.is_some() || parameters.q.is_some() ||
+                parameters.dp.is_some() || parameters.dq.is_some() || parameters.qi.is_some() ||
+                parameters.other_primes_info.is_some()
+            }
+            AlgorithmParameters::OctectKey { .. } => true,
+            AlgorithmParameters::OctetKeyPair(ref parameters) => parameters.d.is_some(),
+        }
+    }
+
+    /// Returns the parameters of the public key, without any of the private members. Symmetric keys have no public
+    /// key, and return an error.
+    pub fn to_public(&self) -> Result<Self, Error> {
+        match *self {
+            AlgorithmParameters::EllipticCurve(ref parameters) => {
+                Ok(AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                                                          d: None,
+                                                          ..parameters.clone()
+                                                      }))
+            }
+            AlgorithmParameters::RSA(ref parameters) => {
+                Ok(AlgorithmParameters::RSA(RSAKeyParameters {
+                                                key_type: parameters.key_type,
+                                                n: parameters.n.clone(),
+                                                e: parameters.e.clone(),
+                                                ..Default::default()
+                                            }))
+            }
+            AlgorithmParameters::OctectKey { .. } => Err("Symmetric keys do not have a public key".to_string())?,
+            AlgorithmParameters::OctetKeyPair(ref parameters) => {
+                Ok(AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                                                         d: None,
+                                                         ..parameters.clone()
+                                                     }))
+            }
+        }
+    }
 }
 
 /// Parameters for an Elliptic Curve Key
@@ -390,6 +468,23 @@ pub struct OtherPrimesInfo {
     pub t: BigUint,
 }
 
+/// Parameters for an Octet Key Pair, as defined in [RFC 8037](https://tools.ietf.org/html/rfc8037)
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Default)]
+pub struct OctetKeyPairParameters {
+    /// Key type value for an Octet Key Pair
+    #[serde(rename = "kty")]
+    pub key_type: OctetKeyPairType,
+    /// The "crv" (curve) parameter identifies the subtype of the key.
+    #[serde(rename = "crv")]
+    pub curve: OctetKeyPairCurve,
+    /// The "x" parameter contains the public key. Serialized to base64 URL encoded
+    #[serde(with = "serde_custom::byte_sequence")]
+    pub x: Vec<u8>,
+    /// The "d" parameter contains the private key. Serialized to base64 URL encoded
+    #[serde(with = "serde_custom::option_byte_sequence", skip_serializing_if = "Option::is_none", default)]
+    pub d: Option<Vec<u8>>,
+}
+
 /// Key type value for an Elliptic Curve Key.
 /// This single value enum is a workaround for Rust not supporting associated constants.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
@@ -433,6 +528,21 @@ impl Default for OctectKeyType {
     }
 }
 
+/// Key type value for an Octet Key Pair.
+/// This single value enum is a workaround for Rust not supporting associated constants.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum OctetKeyPairType {
+    /// Key type value for an Octet Key Pair.
+    #[serde(rename = "OKP")]
+    OctetKeyPair,
+}
+
+impl Default for OctetKeyPairType {
+    fn default() -> Self {
+        OctetKeyPairType::OctetKeyPair
+    }
+}
+
 /// Type of cryptographic curve used by a key. This is defined in
 /// [RFC 7518 #7.6](https://tools.ietf.org/html/rfc7518#section-7.6)
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
@@ -454,6 +564,124 @@ impl Default for EllipticCurve {
     }
 }
 
+/// Subtype of an Octet Key Pair. This is defined in
+/// [RFC 8037 #2](https://tools.ietf.org/html/rfc8037#section-2)
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum OctetKeyPairCurve {
+    /// Ed25519 signature algorithm key pairs
+    Ed25519,
+    /// Ed448 signature algorithm key pairs -- unsupported by `ring`.
+    Ed448,
+    /// X25519 function key pairs
+    X25519,
+    /// X448 function key pairs -- unsupported by `ring`.
+    X448,
+}
+
+impl Default for OctetKeyPairCurve {
+    fn default() -> Self {
+        OctetKeyPairCurve::Ed25519
+    }
+}
+
+/// Hash function used to compute a [JWK Thumbprint](https://tools.ietf.org/html/rfc7638)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ThumbprintHash {
+    /// SHA-256
+    SHA256,
+    /// SHA-384
+    SHA384,
+    /// SHA-512
+    SHA512,
+}
+
+impl ThumbprintHash {
+    /// Returns the name of the hash function in the
+    /// [Named Information Hash Algorithm Registry](https://www.iana.org/assignments/named-information), as used
+    /// in [JWK Thumbprint URIs](https://tools.ietf.org/html/rfc9278)
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ThumbprintHash::SHA256 => "sha-256",
+            ThumbprintHash::SHA384 => "sha-384",
+            ThumbprintHash::SHA512 => "sha-512",
+        }
+    }
+
+    fn digest_algorithm(&self) -> &'static digest::Algorithm {
+        match *self {
+            ThumbprintHash::SHA256 => &digest::SHA256,
+            ThumbprintHash::SHA384 => &digest::SHA384,
+            ThumbprintHash::SHA512 => &digest::SHA512,
+        }
+    }
+}
+
+/// Prefix of a JWK Thumbprint URI, which is followed by the name of the hash function and the thumbprint
+const THUMBPRINT_URI_PREFIX: &str = "urn:ietf:params:oauth:jwk-thumbprint:";
+
+/// A [JWK Thumbprint URI](https://tools.ietf.org/html/rfc9278), such as
+/// `urn:ietf:params:oauth:jwk-thumbprint:sha-256:NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs`. It identifies a key
+/// by its [JWK Thumbprint](https://tools.ietf.org/html/rfc7638), and can be parsed from and formatted to a string.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ThumbprintUri {
+    /// Hash function used to compute the thumbprint
+    pub hash: ThumbprintHash,
+    /// The thumbprint of the key
+    pub thumbprint: Vec<u8>,
+}
+
+impl ThumbprintUri {
+    /// Returns whether the URI identifies the key
+    pub fn matches<T: Serialize + DeserializeOwned>(&self, jwk: &JWK<T>) -> Result<bool, Error> {
+        Ok(jwk.thumbprint(self.hash)? == self.thumbprint)
+    }
+
+    /// Converts the URI into a `StringOrUri`, for use in the registered claims of a JWT, such as `sub`
+    pub fn to_string_or_uri(&self) -> Result<StringOrUri, Error> {
+        StringOrUri::from_str(&self.to_string())
+    }
+}
+
+impl FromStr for ThumbprintUri {
+    type Err = Error;
+
+    /// Parses a JWK Thumbprint URI. The hash function must be one of `ThumbprintHash`, and the thumbprint must
+    /// have the length of its output.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        if !input.starts_with(THUMBPRINT_URI_PREFIX) {
+            Err(format!("{} is not a JWK Thumbprint URI", input))?;
+        }
+
+        let mut parts = input[THUMBPRINT_URI_PREFIX.len()..].splitn(2, ':');
+        let name = parts.next().unwrap_or_default();
+        let hash = [ThumbprintHash::SHA256, ThumbprintHash::SHA384, ThumbprintHash::SHA512]
+            .iter()
+            .find(|hash| hash.name() == name)
+            .cloned()
+            .ok_or_else(|| format!("Unsupported JWK Thumbprint hash function {}", name))?;
+
+        let thumbprint = base64url::decode_nopad(parts.next().unwrap_or_default().as_bytes())?;
+        if thumbprint.len() != hash.digest_algorithm().output_len {
+            Err(format!("Invalid {} JWK Thumbprint length of {} bytes", name, thumbprint.len()))?;
+        }
+
+        Ok(ThumbprintUri {
+               hash: hash,
+               thumbprint: thumbprint,
+           })
+    }
+}
+
+impl fmt::Display for ThumbprintUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{}{}:{}",
+               THUMBPRINT_URI_PREFIX,
+               self.hash.name(),
+               base64url::encode_nopad(&self.thumbprint))
+    }
+}
+
 /// A JSON object that represents a cryptographic key.
 /// The members of the object represent properties of the key, including its value.
 /// Type `T` is a struct representing additional JWK properties
@@ -501,6 +729,235 @@ impl<T: Serialize + DeserializeOwned> JWK<T> {
     pub fn octect_key(&self) -> Result<&[u8], Error> {
         self.algorithm.octect_key()
     }
+
+    /// Returns whether the key contains private key material. Symmetric keys are always private.
+    pub fn is_private(&self) -> bool {
+        self.algorithm.is_private()
+    }
+
+    /// Returns the public key of an asymmetric key, which can be published. The private members of the key are
+    /// removed, while the common parameters such as `kid`, `use`, `alg` and `x5c` are kept. Symmetric keys have no
+    /// public key, and return an error.
+    pub fn to_public(&self) -> Result<Self, Error>
+        where T: Clone
+    {
+        Ok(JWK {
+               common: self.common.clone(),
+               algorithm: self.algorithm.to_public()?,
+               additional: self.additional.clone(),
+           })
+    }
+
+    /// Returns the JSON object hashed to compute the [JWK Thumbprint](https://tools.ietf.org/html/rfc7638) of the
+    /// key. See `AlgorithmParameters::thumbprint_json`.
+    pub fn thumbprint_json(&self) -> Result<String, Error> {
+        self.algorithm.thumbprint_json()
+    }
+
+    /// Computes the [JWK Thumbprint](https://tools.ietf.org/html/rfc7638) of the key with the given hash function
+    pub fn thumbprint(&self, hash: ThumbprintHash) -> Result<Vec<u8>, Error> {
+        let json = self.thumbprint_json()?;
+        Ok(digest::digest(hash.digest_algorithm(), json.as_bytes()).as_ref().to_vec())
+    }
+
+    /// Computes the [JWK Thumbprint](https://tools.ietf.org/html/rfc7638) of the key with the given hash function,
+    /// encoded with base64url without padding, which is the form typically used as a `kid`
+    pub fn thumbprint_base64(&self, hash: ThumbprintHash) -> Result<String, Error> {
+        Ok(base64url::encode_nopad(&self.thumbprint(hash)?))
+    }
+
+    /// Returns the [JWK Thumbprint URI](https://tools.ietf.org/html/rfc9278) of the key with the given hash function
+    pub fn thumbprint_uri(&self, hash: ThumbprintHash) -> Result<ThumbprintUri, Error> {
+        Ok(ThumbprintUri {
+               hash: hash,
+               thumbprint: self.thumbprint(hash)?,
+           })
+    }
+
+    /// Generates a new RSA key pair with a modulus of 2048, 3072 or 4096 bits, for use with one of the RSA
+    /// signature or key management algorithms.
+    ///
+    /// Like all generated keys, its `alg` and `use` are set from `algorithm`, and its `kid` is its SHA-256
+    /// JWK Thumbprint.
+    pub fn generate_rsa(bits: usize, algorithm: Algorithm, additional: T) -> Result<Self, Error> {
+        match bits {
+            2048 | 3072 | 4096 => {}
+            _ => Err(format!("Unsupported RSA key size of {} bits. Use 2048, 3072 or 4096 bits.", bits))?,
+        }
+        match algorithm {
+            Algorithm::Signature(SignatureAlgorithm::RS256) |
+            Algorithm::Signature(SignatureAlgorithm::RS384) |
+            Algorithm::Signature(SignatureAlgorithm::RS512) |
+            Algorithm::Signature(SignatureAlgorithm::PS256) |
+            Algorithm::Signature(SignatureAlgorithm::PS384) |
+            Algorithm::Signature(SignatureAlgorithm::PS512) |
+            Algorithm::KeyManagement(KeyManagementAlgorithm::RSA1_5) |
+            Algorithm::KeyManagement(KeyManagementAlgorithm::RSA_OAEP) |
+            Algorithm::KeyManagement(KeyManagementAlgorithm::RSA_OAEP_256) => {}
+            _ => Err(incompatible_algorithm_error(algorithm, KeyType::RSA))?,
+        }
+
+        let key = RsaPrivateKey::new(&mut OsRng, bits)?;
+        // Order the primes so that `q < p`, which some implementations, including `ring`, require
+        let mut primes = key.primes().to_vec();
+        primes.sort_by(|a, b| b.cmp(a));
+        let mut key = RsaPrivateKey::from_components(key.n().clone(), key.e().clone(), key.d().clone(), primes)?;
+        key.precompute()?;
+        let qi = key.qinv()
+            .and_then(|qi| qi.to_biguint())
+            .ok_or(Error::UnspecifiedCryptographicError)?;
+        let parameters = RSAKeyParameters {
+            n: rsa_integer(key.n()),
+            e: rsa_integer(key.e()),
+            d: Some(rsa_integer(key.d())),
+            p: Some(rsa_integer(&key.primes()[0])),
+            q: Some(rsa_integer(&key.primes()[1])),
+            dp: key.dp().map(rsa_integer),
+            dq: key.dq().map(rsa_integer),
+            qi: Some(rsa_integer(&qi)),
+            ..Default::default()
+        };
+        Self::new_generated(AlgorithmParameters::RSA(parameters), algorithm, additional)
+    }
+
+    /// Generates a new Elliptic Curve key pair on P-256 or P-384, for use with the ECDSA signature algorithm of
+    /// the curve or with the ECDH-ES key management algorithms.
+    pub fn generate_elliptic_curve(curve: EllipticCurve, algorithm: Algorithm, additional: T) -> Result<Self, Error> {
+        let compatible = match (&curve, algorithm) {
+            (&EllipticCurve::P256, Algorithm::Signature(SignatureAlgorithm::ES256)) |
+            (&EllipticCurve::P384, Algorithm::Signature(SignatureAlgorithm::ES384)) => true,
+            (_, Algorithm::KeyManagement(key_management)) => is_ecdh_es(key_management),
+            _ => false,
+        };
+        if !compatible {
+            Err(incompatible_algorithm_error(algorithm, KeyType::EllipticCurve))?;
+        }
+
+        let parameters = match curve {
+            EllipticCurve::P256 => generate_elliptic_curve_parameters::<p256::NistP256>(curve),
+            EllipticCurve::P384 => generate_elliptic_curve_parameters::<p384::NistP384>(curve),
+            EllipticCurve::P521 => Err(Error::UnsupportedOperation)?,
+        };
+        Self::new_generated(AlgorithmParameters::EllipticCurve(parameters), algorithm, additional)
+    }
+
+    /// Generates a new Octet Key Pair, either on Ed25519 for use with `EdDSA`, or on X25519 for use with the
+    /// ECDH-ES key management algorithms.
+    pub fn generate_octet_key_pair(curve: OctetKeyPairCurve,
+                                   algorithm: Algorithm,
+                                   additional: T)
+                                   -> Result<Self, Error> {
+        let compatible = match (curve, algorithm) {
+            (OctetKeyPairCurve::Ed25519, Algorithm::Signature(SignatureAlgorithm::EdDSA)) => true,
+            (OctetKeyPairCurve::X25519, Algorithm::KeyManagement(key_management)) => is_ecdh_es(key_management),
+            (OctetKeyPairCurve::Ed448, _) | (OctetKeyPairCurve::X448, _) => Err(Error::UnsupportedOperation)?,
+            _ => false,
+        };
+        if !compatible {
+            Err(incompatible_algorithm_error(algorithm, KeyType::OctectKeyPair))?;
+        }
+
+        let (x, d) = match curve {
+            OctetKeyPairCurve::Ed25519 => {
+                let (_, bytes) = signature::Ed25519KeyPair::generate_serializable(jwa::rng())?;
+                (bytes.public_key.to_vec(), bytes.private_key.to_vec())
+            }
+            _ => {
+                let secret = x25519_dalek::StaticSecret::random_from_rng(OsRng);
+                let public_key = x25519_dalek::PublicKey::from(&secret);
+                (public_key.as_bytes().to_vec(), secret.to_bytes().to_vec())
+            }
+        };
+        let parameters = OctetKeyPairParameters {
+            key_type: Default::default(),
+            curve: curve,
+            x: x,
+            d: Some(d),
+        };
+        Self::new_generated(AlgorithmParameters::OctetKeyPair(parameters), algorithm, additional)
+    }
+
+    /// Generates a new random symmetric key with the size required by `algorithm`, which is one of the HMAC
+    /// signature algorithms, an AES key management algorithm, or a content encryption algorithm for use with
+    /// direct encryption.
+    pub fn generate_octect_key(algorithm: Algorithm, additional: T) -> Result<Self, Error> {
+        let length = match algorithm {
+            Algorithm::Signature(SignatureAlgorithm::HS256) => 256 / 8,
+            Algorithm::Signature(SignatureAlgorithm::HS384) => 384 / 8,
+            Algorithm::Signature(SignatureAlgorithm::HS512) => 512 / 8,
+            Algorithm::KeyManagement(KeyManagementAlgorithm::A128KW) |
+            Algorithm::KeyManagement(KeyManagementAlgorithm::A128GCMKW) => 128 / 8,
+            Algorithm::KeyManagement(KeyManagementAlgorithm::A192KW) |
+            Algorithm::KeyManagement(KeyManagementAlgorithm::A192GCMKW) => 192 / 8,
+            Algorithm::KeyManagement(KeyManagementAlgorithm::A256KW) |
+            Algorithm::KeyManagement(KeyManagementAlgorithm::A256GCMKW) => 256 / 8,
+            Algorithm::ContentEncryption(content_encryption) => content_encryption.generate_key()?.len(),
+            _ => Err(incompatible_algorithm_error(algorithm, KeyType::Octect))?,
+        };
+
+        let mut key: Vec<u8> = vec![0; length];
+        jwa::rng().fill(&mut key)?;
+        let parameters = AlgorithmParameters::OctectKey {
+            key_type: Default::default(),
+            value: key,
+        };
+        Self::new_generated(parameters, algorithm, additional)
+    }
+
+    /// Creates a generated key, with its `alg`, `use` and `kid` set
+    fn new_generated(parameters: AlgorithmParameters, algorithm: Algorithm, additional: T) -> Result<Self, Error> {
+        let public_key_use = match algorithm {
+            Algorithm::Signature(_) => PublicKeyUse::Signature,
+            Algorithm::KeyManagement(_) |
+            Algorithm::ContentEncryption(_) => PublicKeyUse::Encryption,
+        };
+        let mut jwk = JWK {
+            common: CommonParameters {
+                public_key_use: Some(public_key_use),
+                algorithm: Some(algorithm),
+                ..Default::default()
+            },
+            algorithm: parameters,
+            additional: additional,
+        };
+        jwk.common.key_id = Some(jwk.thumbprint_base64(ThumbprintHash::SHA256)?);
+        Ok(jwk)
+    }
+}
+
+fn incompatible_algorithm_error(algorithm: Algorithm, key_type: KeyType) -> Error {
+    Error::GenericError(format!("{:?} cannot be used with a key of type: {}", algorithm, key_type))
+}
+
+/// Returns whether the algorithm is one of the ECDH-ES key management algorithms
+fn is_ecdh_es(algorithm: KeyManagementAlgorithm) -> bool {
+    matches!(algorithm,
+             KeyManagementAlgorithm::ECDH_ES |
+             KeyManagementAlgorithm::ECDH_ES_A128KW |
+             KeyManagementAlgorithm::ECDH_ES_A192KW |
+             KeyManagementAlgorithm::ECDH_ES_A256KW)
+}
+
+fn rsa_integer(value: &rsa::BigUint) -> BigUint {
+    BigUint::from_bytes_be(&value.to_bytes_be())
+}
+
+/// Generates the parameters of a new Elliptic Curve key pair on the curve `C`
+fn generate_elliptic_curve_parameters<C>(curve: EllipticCurve) -> EllipticCurveKeyParameters
+    where C: CurveArithmetic,
+          AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+          FieldBytesSize<C>: ModulusSize
+{
+    let secret_key = SecretKey::<C>::random(&mut OsRng);
+    let point = secret_key.public_key().to_encoded_point(false);
+    let (x, y) = point.as_bytes()[1..].split_at((point.len() - 1) / 2);
+    EllipticCurveKeyParameters {
+        key_type: Default::default(),
+        curve: curve,
+        x: x.to_vec(),
+        y: y.to_vec(),
+        d: Some(secret_key.to_bytes().to_vec()),
+    }
 }
 
 /// A JSON object that represents a set of JWKs.
@@ -510,6 +967,28 @@ pub struct JWKSet<T> {
     pub keys: Vec<JWK<T>>,
 }
 
+impl<T> JWKSet<T> {
+    /// Find the first key with the given key ID (`kid`)
+    pub fn find(&self, key_id: &str) -> Option<&JWK<T>> {
+        self.keys
+            .iter()
+            .find(|jwk| jwk.common.key_id.as_ref().map(|id| id == key_id).unwrap_or(false))
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Clone> JWKSet<T> {
+    /// Returns whether any of the keys contains private key material
+    pub fn is_private(&self) -> bool {
+        self.keys.iter().any(JWK::is_private)
+    }
+
+    /// Returns the set of the public keys of the keys, which can be published. Returns an error if the set contains
+    /// a symmetric key. See `JWK::to_public`.
+    pub fn to_public(&self) -> Result<Self, Error> {
+        Ok(JWKSet { keys: self.keys.iter().map(JWK::to_public).collect::<Result<_, _>>()? })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::str;
@@ -966,4 +1445,299 @@ mod tests {
         let expected_json = include_str!("../test/fixtures/jwk_private_key.json");
         assert_serde_json(&test_value, Some(&expected_json));
     }
+
+    #[test]
+    fn jwk_set_find() {
+        let jwks: JWKSet<::Empty> =
+            not_err!(::serde_json::from_str(include_str!("../test/fixtures/jwk_public_key.json")));
+
+        let jwk = jwks.find("2011-04-29").expect("key to be found");
+        assert_eq!(jwk.key_type(), KeyType::RSA);
+        assert!(jwks.find("unknown").is_none());
+    }
+
+    /// Example from [RFC 8037 Appendix A.1](https://tools.ietf.org/html/rfc8037#appendix-A.1)
+    #[test]
+    fn octet_key_pair_serde_test() {
+        let test_value: JWK<::Empty> = JWK {
+            common: Default::default(),
+            algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: Default::default(),
+                curve: OctetKeyPairCurve::Ed25519,
+                x: vec![215, 90, 152, 1, 130, 177, 10, 183, 213, 75, 254, 211, 201, 100, 7, 58, 14, 225, 114, 243,
+                        218, 166, 35, 37, 175, 2, 26, 104, 247, 7, 81, 26],
+                d: Some(vec![157, 97, 177, 157, 239, 253, 90, 96, 186, 132, 74, 244, 146, 236, 44, 196, 68, 73, 197,
+                             105, 123, 50, 105, 25, 112, 59, 172, 3, 28, 174, 127, 96]),
+            }),
+            additional: Default::default(),
+        };
+
+        let expected_json = r#"{
+  "kty": "OKP",
+  "crv": "Ed25519",
+  "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo",
+  "d": "nWGxne_9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A"
+}"#;
+
+        assert_serde_json(&test_value, Some(&expected_json));
+        assert_eq!(test_value.key_type(), KeyType::OctectKeyPair);
+    }
+
+    /// Example from [RFC 7638 Section 3.1](https://tools.ietf.org/html/rfc7638#section-3.1)
+    #[test]
+    fn thumbprint_rfc7638_example() {
+        let jwks: JWKSet<::Empty> =
+            not_err!(::serde_json::from_str(include_str!("../test/fixtures/jwk_public_key.json")));
+        let jwk = jwks.find("2011-04-29").expect("key to be found");
+
+        let expected_json = "{\"e\":\"AQAB\",\"kty\":\"RSA\",\"n\":\"0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhW\
+                             x4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXA\
+                             rwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5ha\
+                             jrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csF\
+                             Cur-kEgU8awapJzKnqDKgw\"}";
+        assert_eq!(not_err!(jwk.thumbprint_json()), expected_json);
+        assert_eq!(not_err!(jwk.thumbprint(ThumbprintHash::SHA256)),
+                   vec![55, 54, 203, 177, 120, 124, 184, 48, 156, 119, 238, 140, 55, 5, 197, 225, 111, 251, 158, 133,
+                        151, 21, 144, 31, 30, 76, 89, 177, 17, 130, 245, 123]);
+        assert_eq!(not_err!(jwk.thumbprint_base64(ThumbprintHash::SHA256)),
+                   "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs");
+        assert_eq!(not_err!(jwk.thumbprint(ThumbprintHash::SHA384)).len(), 384 / 8);
+        assert_eq!(not_err!(jwk.thumbprint(ThumbprintHash::SHA512)).len(), 512 / 8);
+    }
+
+    /// Only the required public members of each key type are used to compute the thumbprint
+    #[test]
+    fn thumbprint_required_members() {
+        let public_keys: JWKSet<::Empty> =
+            not_err!(::serde_json::from_str(include_str!("../test/fixtures/jwk_public_key.json")));
+        let private_keys: JWKSet<::Empty> =
+            not_err!(::serde_json::from_str(include_str!("../test/fixtures/jwk_private_key.json")));
+        for (public_key, private_key) in public_keys.keys.iter().zip(private_keys.keys.iter()) {
+            assert_eq!(not_err!(private_key.thumbprint_base64(ThumbprintHash::SHA256)),
+                       not_err!(public_key.thumbprint_base64(ThumbprintHash::SHA256)));
+        }
+
+        let ec_key = public_keys.find("1").expect("key to be found");
+        assert_eq!(not_err!(ec_key.thumbprint_json()),
+                   "{\"crv\":\"P-256\",\"kty\":\"EC\",\"x\":\"MKBCTNIcKUSDii11ySs3526iDZ8AiTo7Tu6KPAqv7D4\",\
+                    \"y\":\"4Etl6SRW2YiLUrN5vfvVHuhp7x8PxltmWWlbbM4IFyM\"}");
+
+        let mut oct_key = JWK::new_octect_key(b"secret", ::Empty {});
+        oct_key.common.key_id = Some("kid".to_string());
+        oct_key.common.algorithm = Some(Algorithm::Signature(jwa::SignatureAlgorithm::HS256));
+        assert_eq!(not_err!(oct_key.thumbprint_json()), r#"{"k":"c2VjcmV0","kty":"oct"}"#);
+    }
+
+    /// Example from [RFC 8037 Appendix A.3](https://tools.ietf.org/html/rfc8037#appendix-A.3)
+    #[test]
+    fn thumbprint_rfc8037_example() {
+        let jwk: JWK<::Empty> = not_err!(::serde_json::from_str(r#"{
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"
+        }"#));
+
+        assert_eq!(not_err!(jwk.thumbprint_json()),
+                   r#"{"crv":"Ed25519","kty":"OKP","x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"}"#);
+        assert_eq!(not_err!(jwk.thumbprint_base64(ThumbprintHash::SHA256)),
+                   "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k");
+    }
+
+    /// Example from [RFC 9278 Section 3](https://tools.ietf.org/html/rfc9278#section-3)
+    #[test]
+    fn thumbprint_uri_rfc9278_example() {
+        let jwks: JWKSet<::Empty> =
+            not_err!(::serde_json::from_str(include_str!("../test/fixtures/jwk_public_key.json")));
+        let jwk = jwks.find("2011-04-29").expect("key to be found");
+        let expected_uri = "urn:ietf:params:oauth:jwk-thumbprint:sha-256:NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs";
+
+        let uri = not_err!(jwk.thumbprint_uri(ThumbprintHash::SHA256));
+        assert_eq!(uri.to_string(), expected_uri);
+        assert_eq!(not_err!(ThumbprintUri::from_str(expected_uri)), uri);
+        assert!(not_err!(uri.matches(jwk)));
+        assert!(!not_err!(uri.matches(jwks.find("1").expect("key to be found"))));
+
+        let string_or_uri = not_err!(uri.to_string_or_uri());
+        assert_matches!(string_or_uri, StringOrUri::Uri(_));
+        assert_eq!(string_or_uri.as_ref(), expected_uri);
+        assert_eq!(not_err!(ThumbprintUri::from_str(string_or_uri.as_ref())), uri);
+
+        let uri = not_err!(jwk.thumbprint_uri(ThumbprintHash::SHA512));
+        assert!(uri.to_string().starts_with("urn:ietf:params:oauth:jwk-thumbprint:sha-512:"));
+        assert_eq!(not_err!(ThumbprintUri::from_str(&uri.to_string())), uri);
+        assert!(not_err!(uri.matches(jwk)));
+    }
+
+    #[test]
+    fn thumbprint_uri_parsing_errors() {
+        let invalid_uris = ["NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs",
+                            "urn:ietf:params:oauth:jwk-thumbprint",
+                            "urn:ietf:params:oauth:jwk-thumbprint:sha-256",
+                            "urn:ietf:params:oauth:jwk-thumbprint:sha-1:NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs",
+                            "urn:ietf:params:oauth:jwk-thumbprint:sha-384:NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs",
+                            "urn:ietf:params:oauth:jwk-thumbprint:sha-256:NzbLsXh8uDCcd+6MNwXF4W/7noWXFZAfHkxZsRGC9Xs"];
+        for uri in &invalid_uris {
+            assert!(ThumbprintUri::from_str(uri).is_err(), "{} should be invalid", uri);
+        }
+    }
+
+    /// Checks the parameters set on a generated key
+    fn assert_generated(jwk: &JWK<::Empty>, algorithm: Algorithm, public_key_use: PublicKeyUse) {
+        assert_eq!(jwk.common.algorithm, Some(algorithm));
+        assert_eq!(jwk.common.public_key_use, Some(public_key_use));
+        assert_eq!(jwk.common.key_id, Some(not_err!(jwk.thumbprint_base64(ThumbprintHash::SHA256))));
+    }
+
+    /// Signs a payload with a generated key, and verifies the signature with the key
+    fn assert_sign_and_verify(jwk: &JWK<::Empty>, algorithm: jwa::SignatureAlgorithm) {
+        use jws::Secret;
+
+        let signing_key = not_err!(Secret::signing_key_from_jwk(jwk, algorithm));
+        let signature = not_err!(algorithm.sign(b"payload", &signing_key));
+        let verification_key = not_err!(Secret::verification_key_from_jwk(jwk, algorithm));
+        assert!(not_err!(algorithm.verify(&signature, b"payload", &verification_key)));
+    }
+
+    #[test]
+    fn generate_rsa_key() {
+        let algorithm = Algorithm::Signature(jwa::SignatureAlgorithm::RS256);
+        let jwk = not_err!(JWK::generate_rsa(2048, algorithm, ::Empty {}));
+        assert_generated(&jwk, algorithm, PublicKeyUse::Signature);
+        match jwk.algorithm {
+            AlgorithmParameters::RSA(ref parameters) => assert_eq!(parameters.n.bits(), 2048),
+            _ => panic!("Expected a RSA key"),
+        }
+        assert_sign_and_verify(&jwk, jwa::SignatureAlgorithm::RS256);
+
+        assert!(JWK::generate_rsa(1024, algorithm, ::Empty {}).is_err());
+        assert!(JWK::generate_rsa(2048, Algorithm::Signature(jwa::SignatureAlgorithm::ES256), ::Empty {}).is_err());
+    }
+
+    #[test]
+    fn generate_elliptic_curve_keys() {
+        for &(ref curve, algorithm) in &[(EllipticCurve::P256, jwa::SignatureAlgorithm::ES256),
+                                         (EllipticCurve::P384, jwa::SignatureAlgorithm::ES384)] {
+            let jwk = not_err!(JWK::generate_elliptic_curve(curve.clone(),
+                                                            Algorithm::Signature(algorithm),
+                                                            ::Empty {}));
+            assert_generated(&jwk, Algorithm::Signature(algorithm), PublicKeyUse::Signature);
+            assert_sign_and_verify(&jwk, algorithm);
+        }
+
+        let algorithm = Algorithm::KeyManagement(jwa::KeyManagementAlgorithm::ECDH_ES_A128KW);
+        let jwk = not_err!(JWK::generate_elliptic_curve(EllipticCurve::P256, algorithm, ::Empty {}));
+        assert_generated(&jwk, algorithm, PublicKeyUse::Encryption);
+
+        let result = JWK::generate_elliptic_curve(EllipticCurve::P256,
+                                                  Algorithm::Signature(jwa::SignatureAlgorithm::ES384),
+                                                  ::Empty {});
+        assert_matches!(result, Err(Error::GenericError(_)));
+        let result = JWK::generate_elliptic_curve(EllipticCurve::P521, algorithm, ::Empty {});
+        assert_matches!(result, Err(Error::UnsupportedOperation));
+    }
+
+    #[test]
+    fn generate_octet_key_pairs() {
+        let algorithm = Algorithm::Signature(jwa::SignatureAlgorithm::EdDSA);
+        let jwk = not_err!(JWK::generate_octet_key_pair(OctetKeyPairCurve::Ed25519, algorithm, ::Empty {}));
+        assert_generated(&jwk, algorithm, PublicKeyUse::Signature);
+        assert_sign_and_verify(&jwk, jwa::SignatureAlgorithm::EdDSA);
+
+        let algorithm = Algorithm::KeyManagement(jwa::KeyManagementAlgorithm::ECDH_ES);
+        let jwk = not_err!(JWK::generate_octet_key_pair(OctetKeyPairCurve::X25519, algorithm, ::Empty {}));
+        assert_generated(&jwk, algorithm, PublicKeyUse::Encryption);
+        match jwk.algorithm {
+            AlgorithmParameters::OctetKeyPair(ref parameters) => {
+                let mut secret = [0; 32];
+                secret.copy_from_slice(parameters.d.as_ref().unwrap());
+                let public_key = x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::from(secret));
+                assert_eq!(public_key.as_bytes().to_vec(), parameters.x);
+            }
+            _ => panic!("Expected an Octet Key Pair"),
+        }
+
+        let result = JWK::generate_octet_key_pair(OctetKeyPairCurve::Ed25519, algorithm, ::Empty {});
+        assert_matches!(result, Err(Error::GenericError(_)));
+        let result = JWK::generate_octet_key_pair(OctetKeyPairCurve::X448, algorithm, ::Empty {});
+        assert_matches!(result, Err(Error::UnsupportedOperation));
+    }
+
+    #[test]
+    fn generate_octect_keys() {
+        let algorithms = [(Algorithm::Signature(jwa::SignatureAlgorithm::HS256), PublicKeyUse::Signature, 32),
+                          (Algorithm::Signature(jwa::SignatureAlgorithm::HS512), PublicKeyUse::Signature, 64),
+                          (Algorithm::KeyManagement(jwa::KeyManagementAlgorithm::A192KW),
+                           PublicKeyUse::Encryption,
+                           24),
+                          (Algorithm::KeyManagement(jwa::KeyManagementAlgorithm::A256GCMKW),
+                           PublicKeyUse::Encryption,
+                           32),
+                          (Algorithm::ContentEncryption(jwa::ContentEncryptionAlgorithm::A128CBC_HS256),
+                           PublicKeyUse::Encryption,
+                           32)];
+        for &(algorithm, ref public_key_use, length) in &algorithms {
+            let jwk = not_err!(JWK::generate_octect_key(algorithm, ::Empty {}));
+            assert_generated(&jwk, algorithm, public_key_use.clone());
+            assert_eq!(not_err!(jwk.octect_key()).len(), length);
+
+            let other_jwk = not_err!(JWK::generate_octect_key(algorithm, ::Empty {}));
+            assert_ne!(jwk, other_jwk);
+        }
+
+        for &algorithm in &[Algorithm::Signature(jwa::SignatureAlgorithm::RS256),
+                            Algorithm::KeyManagement(jwa::KeyManagementAlgorithm::PBES2_HS256_A128KW)] {
+            assert_matches!(JWK::generate_octect_key(algorithm, ::Empty {}), Err(Error::GenericError(_)));
+        }
+    }
+
+    #[test]
+    fn jwk_set_to_public() {
+        let private_keys: JWKSet<::Empty> =
+            not_err!(::serde_json::from_str(include_str!("../test/fixtures/jwk_private_key.json")));
+        let public_keys: JWKSet<::Empty> =
+            not_err!(::serde_json::from_str(include_str!("../test/fixtures/jwk_public_key.json")));
+        assert!(private_keys.is_private());
+        assert!(!public_keys.is_private());
+        assert!(private_keys.keys.iter().all(JWK::is_private));
+
+        let projected_keys = not_err!(private_keys.to_public());
+        assert!(!projected_keys.is_private());
+        assert_eq!(projected_keys, public_keys);
+        assert_eq!(not_err!(public_keys.to_public()), public_keys);
+    }
+
+    #[test]
+    fn jwk_to_public() {
+        let algorithm = Algorithm::Signature(jwa::SignatureAlgorithm::EdDSA);
+        let mut jwk = not_err!(JWK::generate_octet_key_pair(OctetKeyPairCurve::Ed25519, algorithm, ::Empty {}));
+        jwk.common.x509_chain = Some(vec!["MIIE3jCCA8agAwIBAgICAwEwDQYJKoZIhvcNAQEFBQAwYzELMAkGA1UE".to_string()]);
+        assert!(jwk.is_private());
+
+        let public_jwk = not_err!(jwk.to_public());
+        assert!(!public_jwk.is_private());
+        assert_eq!(public_jwk.common, jwk.common);
+        assert_eq!(not_err!(public_jwk.thumbprint_json()), not_err!(jwk.thumbprint_json()));
+        let serialized = not_err!(::serde_json::to_value(&public_jwk));
+        assert!(serialized.get("d").is_none());
+        assert!(serialized.get("x5c").is_some());
+
+        // RSA keys with only some of the private members are private
+        let public_keys: JWKSet<::Empty> =
+            not_err!(::serde_json::from_str(include_str!("../test/fixtures/jwk_public_key.json")));
+        let mut rsa_jwk = public_keys.keys[1].clone();
+        assert!(!rsa_jwk.is_private());
+        if let AlgorithmParameters::RSA(ref mut parameters) = rsa_jwk.algorithm {
+            parameters.qi = Some(BigUint::from(1u8));
+        }
+        assert!(rsa_jwk.is_private());
+        assert!(!not_err!(rsa_jwk.to_public()).is_private());
+
+        // Symmetric keys have no public form
+        let oct_jwk = JWK::new_octect_key(b"secret", ::Empty {});
+        assert!(oct_jwk.is_private());
+        assert_matches!(oct_jwk.to_public(), Err(Error::GenericError(_)));
+        let jwks = JWKSet { keys: vec![public_jwk, oct_jwk] };
+        assert!(jwks.is_private());
+        assert_matches!(jwks.to_public(), Err(Error::GenericError(_)));
+    }
 }
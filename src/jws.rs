@@ -1,19 +1,27 @@
 //! JSON Web Signatures, including JWT signing and headers
 //!
 //! Defined in [RFC 7515](https://tools.ietf.org/html/rfc7515). For most common use,
-//! you will want to look at the  [`Compact`](enum.Compact.html) enum.
+//! you will want to look at the  [`Compact`](enum.Compact.html) enum. Payloads with more than one signature
+//! can be represented with the [`Json`](enum.Json.html) enum.
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::str;
+use std::str::{self, FromStr};
 
+use data_encoding::base64url;
+use p256;
+use p384;
 use ring::signature;
 use serde::{self, Serialize};
 use serde::de::DeserializeOwned;
 use serde_json;
 use untrusted;
 
-use {CompactJson, CompactPart, Empty};
+use {Base64Url, CompactJson, CompactPart, Empty};
+use critical;
+use der;
 use errors::{Error, ValidationError};
-use jwa::SignatureAlgorithm;
+use jwa::{Algorithm, SignatureAlgorithm};
+use jwk;
 use serde_custom;
 
 /// Compact representation of a JWS
@@ -78,7 +86,11 @@ impl<T: CompactPart, H: Serialize + DeserializeOwned> Compact<T, H> {
             } => {
                 let mut compact = ::Compact::with_capacity(3);
                 compact.push(header)?;
-                compact.push(payload)?;
+                if header.registered.payload_is_base64url_encoded()? {
+                    compact.push(payload)?;
+                } else {
+                    compact.parts.push(unencoded_compact_payload(payload)?);
+                }
                 let encoded_payload = compact.encode();
                 let signature = header
                     .registered
@@ -91,6 +103,18 @@ impl<T: CompactPart, H: Serialize + DeserializeOwned> Compact<T, H> {
         }
     }
 
+    /// Encode the JWT passed and sign the payload using the algorithm from the header and a JWK.
+    /// See [`Secret::signing_key_from_jwk`] for the keys that can be used.
+    pub fn encode_with_jwk<K: Serialize + DeserializeOwned>(&self, jwk: &jwk::JWK<K>) -> Result<Self, Error> {
+        match *self {
+            Compact::Decoded { ref header, .. } => {
+                let secret = Secret::signing_key_from_jwk(jwk, header.registered.algorithm)?;
+                self.encode(&secret)
+            }
+            Compact::Encoded(_) => Err(Error::UnsupportedOperation),
+        }
+    }
+
     /// Consumes self and convert into decoded form, verifying the signature, if any.
     /// If the token is already decoded, this is a no-op
     // TODO: Is the no-op dangerous? What if the secret between the previous decode and this time is different?
@@ -104,6 +128,137 @@ impl<T: CompactPart, H: Serialize + DeserializeOwned> Compact<T, H> {
     /// Decode a token into the JWT struct and verify its signature
     /// If the token or its signature is invalid, it will return an error
     pub fn decode(&self, secret: &Secret, algorithm: SignatureAlgorithm) -> Result<Self, Error> {
+        let resolved_key = ResolvedKey {
+            key: VerificationKey::Secret(secret.clone()),
+            algorithms: vec![algorithm],
+        };
+        self.decode_with_resolver(&resolved_key)
+    }
+
+    /// Decode a token into the JWT struct and verify its signature using a JWK.
+    /// See [`Secret::verification_key_from_jwk`] for the keys that can be used.
+    /// If the token or its signature is invalid, it will return an error
+    pub fn decode_with_jwk<K: Serialize + DeserializeOwned>(&self,
+                                                            jwk: &jwk::JWK<K>,
+                                                            algorithm: SignatureAlgorithm)
+                                                            -> Result<Self, Error> {
+        let resolved_key = ResolvedKey {
+            key: VerificationKey::JWK(jwk.clone_without_additional()),
+            algorithms: vec![algorithm],
+        };
+        self.decode_with_resolver(&resolved_key)
+    }
+
+    /// Decode a token into the JWT struct and verify its signature, accepting any of the allowed algorithms.
+    ///
+    /// Each allowed algorithm is tied to its own secret, and the secret for the algorithm in the header of the
+    /// token is used to verify its signature. This is useful when rotating keys across algorithms, for example
+    /// from `RS256` to `PS256`.
+    ///
+    /// To protect against algorithm confusion, an error is returned if a secret cannot be used with the algorithm
+    /// it is tied to (for example, an RSA public key with `HS256`), or if an algorithm is listed more than once.
+    /// Tokens with an algorithm that is not allowed are rejected with `ValidationError::WrongAlgorithmHeader`.
+    /// If the token or its signature is invalid, it will return an error
+    pub fn decode_with_allowed_algorithms(&self, allowed: &[(SignatureAlgorithm, &Secret)]) -> Result<Self, Error> {
+        for (index, &(algorithm, secret)) in allowed.iter().enumerate() {
+            secret.check_algorithm(algorithm)?;
+            if allowed[..index].iter().any(|&(other, _)| other == algorithm) {
+                Err(format!("{:?} is allowed more than once", algorithm))?;
+            }
+        }
+        self.decode_with_resolver(&AllowedAlgorithms(allowed))
+    }
+
+    /// Decode a token into the JWT struct and verify its signature using a key from a JWK Set.
+    /// See the [`KeyResolver`] implementation of [`jwk::JWKSet`] for how the key is selected.
+    /// If the token or its signature is invalid, it will return an error
+    pub fn decode_with_jwks<K: Serialize + DeserializeOwned>(&self, jwks: &jwk::JWKSet<K>) -> Result<Self, Error> {
+        self.decode_with_resolver(jwks)
+    }
+
+    /// Decode a token into the JWT struct and verify its signature using the key returned by a [`KeyResolver`].
+    ///
+    /// The resolver is called with the unverified header of the token. The token is rejected with
+    /// `ValidationError::WrongAlgorithmHeader` if the algorithm in the header is not one of the algorithms
+    /// returned by the resolver, or with `ValidationError::InvalidSignature` if its signature is invalid.
+    /// If the token or its signature is invalid, it will return an error
+    pub fn decode_with_resolver<R: KeyResolver<H> + ?Sized>(&self, resolver: &R) -> Result<Self, Error> {
+        self.decode_with_options(resolver, Default::default())
+    }
+
+    /// Decode a token like [`decode_with_resolver`](#method.decode_with_resolver), with the options set in `options`
+    pub fn decode_with_options<R: KeyResolver<H> + ?Sized>(&self,
+                                                           resolver: &R,
+                                                           options: DecodeOptions)
+                                                           -> Result<Self, Error> {
+        self.decode_parts(None, resolver, options)
+    }
+
+    /// Encode the JWT passed with a detached payload, so that the payload part of the token is empty.
+    /// The signature is the same as if the payload was not detached, and the payload has to be transported
+    /// separately from the token.
+    /// Defined in [RFC7515#F](https://tools.ietf.org/html/rfc7515#appendix-F).
+    pub fn encode_detached(&self, secret: &Secret) -> Result<Self, Error> {
+        match *self {
+            Compact::Decoded {
+                ref header,
+                ref payload,
+            } => {
+                let mut compact = ::Compact::with_capacity(3);
+                compact.push(header)?;
+                let signing_input = detached_signing_input(&compact.parts[0],
+                                                           &payload.to_bytes()?,
+                                                           header.registered.payload_is_base64url_encoded()?);
+                let signature = header.registered.algorithm.sign(&signing_input, secret)?;
+                compact.parts.push(FromStr::from_str("")?);
+                compact.push(&signature)?;
+                Ok(Compact::Encoded(compact))
+            }
+            Compact::Encoded(_) => Err(Error::UnsupportedOperation),
+        }
+    }
+
+    /// Decode a token with a detached payload into the JWT struct and verify its signature over the payload.
+    /// The payload part of the token must be empty.
+    /// If the token or its signature is invalid, it will return an error
+    pub fn decode_detached(&self,
+                           payload: &[u8],
+                           secret: &Secret,
+                           algorithm: SignatureAlgorithm)
+                           -> Result<Self, Error> {
+        let resolved_key = ResolvedKey {
+            key: VerificationKey::Secret(secret.clone()),
+            algorithms: vec![algorithm],
+        };
+        self.decode_detached_with_resolver(payload, &resolved_key)
+    }
+
+    /// Decode a token with a detached payload into the JWT struct and verify its signature over the payload,
+    /// using the key returned by a [`KeyResolver`]. The payload part of the token must be empty.
+    /// If the token or its signature is invalid, it will return an error
+    pub fn decode_detached_with_resolver<R: KeyResolver<H> + ?Sized>(&self,
+                                                                     payload: &[u8],
+                                                                     resolver: &R)
+                                                                     -> Result<Self, Error> {
+        self.decode_detached_with_options(payload, resolver, Default::default())
+    }
+
+    /// Decode a token with a detached payload like
+    /// [`decode_detached_with_resolver`](#method.decode_detached_with_resolver), with the options set in `options`
+    pub fn decode_detached_with_options<R: KeyResolver<H> + ?Sized>(&self,
+                                                                    payload: &[u8],
+                                                                    resolver: &R,
+                                                                    options: DecodeOptions)
+                                                                    -> Result<Self, Error> {
+        self.decode_parts(Some(payload), resolver, options)
+    }
+
+    /// Verify and decode the token, using the detached payload if there is one
+    fn decode_parts<R: KeyResolver<H> + ?Sized>(&self,
+                                                detached_payload: Option<&[u8]>,
+                                                resolver: &R,
+                                                options: DecodeOptions)
+                                                -> Result<Self, Error> {
         match *self {
             Compact::Decoded { .. } => Err(Error::UnsupportedOperation),
             Compact::Encoded(ref encoded) => {
@@ -114,25 +269,44 @@ impl<T: CompactPart, H: Serialize + DeserializeOwned> Compact<T, H> {
                         })?
                 }
 
-                let signature: Vec<u8> = encoded.part(2)?;
-                let payload = &encoded.parts[0..2].join(".").to_string();
-
-                if !algorithm
-                        .verify(signature.as_ref(), payload.as_ref(), secret)? {
-                    Err(ValidationError::InvalidSignature)?;
-                }
-
                 let header: Header<H> = encoded.part(0)?;
-                if header.registered.algorithm != algorithm {
-                    Err(ValidationError::WrongAlgorithmHeader)?;
-                }
-                let decoded_claims: T = encoded.part(1)?;
+                let parameters: Vec<u8> = encoded.part(0)?;
+                validate_critical(&serde_json::from_slice(&parameters)?, options)?;
+                let payload_is_encoded = header.registered.payload_is_base64url_encoded()?;
+                let signature: Vec<u8> = encoded.part(2)?;
+                let signing_input = match detached_payload {
+                    Some(payload) => {
+                        if !encoded.parts[1].is_empty() {
+                            Err(ValidationError::InvalidToken)?;
+                        }
+                        detached_signing_input(&encoded.parts[0], payload, payload_is_encoded)
+                    }
+                    None => encoded.parts[0..2].join(".").into_bytes(),
+                };
+                verify_with_resolver(&header, resolver, &signing_input, signature.as_ref())?;
+
+                let decoded_claims: T = match detached_payload {
+                    Some(payload) => T::from_bytes(payload)?,
+                    None if payload_is_encoded => encoded.part(1)?,
+                    None => T::from_bytes(encoded.parts[1].as_bytes())?,
+                };
 
                 Ok(Self::new_decoded(header, decoded_claims))
             }
         }
     }
 
+    /// Get the header of an encoded JWS *without* verifying its signature.
+    ///
+    /// The contents of the header cannot be trusted until the signature has been verified. This is useful to find
+    /// out which key (`kid`) or algorithm (`alg`) should be used to verify the token.
+    pub fn unverified_header(&self) -> Result<Header<H>, Error> {
+        match *self {
+            Compact::Decoded { .. } => Err(Error::UnsupportedOperation),
+            Compact::Encoded(ref encoded) => encoded.part(0),
+        }
+    }
+
     /// Convenience method to get a reference to the encoded string from an encoded compact JWS
     pub fn encoded(&self) -> Result<&::Compact, Error> {
         match *self {
@@ -218,7 +392,481 @@ impl<T: CompactPart, H: Serialize + DeserializeOwned> CompactPart for Compact<T,
     }
 }
 
+/// Header parameters defined for JWS, which cannot be listed in `crit`
+const REGISTERED_HEADERS: &[&str] = &["alg", "jku", "jwk", "kid", "x5u", "x5c", "x5t", "x5t#S256", "typ", "cty",
+                                      "crit"];
+
+/// Extension header parameters implemented by this library, which are always understood
+const BUILT_IN_EXTENSIONS: &[&str] = &["b64"];
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+/// Options for decoding a JWS
+pub struct DecodeOptions<'a> {
+    /// Names of the extension header parameters understood by the application, in addition to `b64`, which is
+    /// always understood. A JWS is rejected with `ValidationError::InvalidCriticalHeader` if its `crit` header
+    /// parameter lists any other extension. Defaults to none.
+    pub understood_critical_headers: &'a [&'a str],
+}
+
+/// Validate the critical header parameters of a JWS header, given as a JSON object
+fn validate_critical(parameters: &serde_json::Map<String, serde_json::Value>,
+                     options: DecodeOptions)
+                     -> Result<(), Error> {
+    let understood: Vec<&str> = BUILT_IN_EXTENSIONS
+        .iter()
+        .chain(options.understood_critical_headers)
+        .cloned()
+        .collect();
+    critical::validate(parameters, REGISTERED_HEADERS, &understood)
+}
+
+/// Convert an unencoded payload to a part of a compact JWS. The payload must not contain any periods.
+fn unencoded_compact_payload<T: CompactPart>(payload: &T) -> Result<Base64Url, Error> {
+    let payload = String::from_utf8(payload.to_bytes()?)?;
+    if payload.contains('.') {
+        Err("An unencoded payload containing periods cannot be used in the compact serialization".to_string())?;
+    }
+    Ok(FromStr::from_str(&payload)?)
+}
+
+/// The signing input for a detached payload, which is only base64url encoded if `encode` is true
+fn detached_signing_input(encoded_header: &Base64Url, payload: &[u8], encode: bool) -> Vec<u8> {
+    let mut signing_input = format!("{}.", encoded_header.str()).into_bytes();
+    if encode {
+        signing_input.extend_from_slice(base64url::encode_nopad(payload).as_bytes());
+    } else {
+        signing_input.extend_from_slice(payload);
+    }
+    signing_input
+}
+
+/// Returns whether the payload is base64url encoded. All the headers must agree on it.
+fn payload_is_base64url_encoded<'a, I>(headers: I) -> Result<bool, Error>
+    where I: Iterator<Item = &'a RegisteredHeader>
+{
+    let mut payload_is_encoded = None;
+    for header in headers {
+        let encoded = header.payload_is_base64url_encoded()?;
+        if payload_is_encoded.map_or(false, |payload_is_encoded| payload_is_encoded != encoded) {
+            Err("All signatures must have the same b64 header parameter".to_string())?;
+        }
+        payload_is_encoded = Some(encoded);
+    }
+    Ok(payload_is_encoded.unwrap_or(true))
+}
+
+/// The payload member of a JWS in the JSON serialization, which is only base64url encoded if `encode` is true
+fn json_payload<T: CompactPart>(payload: &T, encode: bool) -> Result<String, Error> {
+    if encode {
+        Ok(payload.to_base64()?.unwrap())
+    } else {
+        Ok(String::from_utf8(payload.to_bytes()?)?)
+    }
+}
+
+/// Verify the signature over the signing input of a JWS with the key returned by the resolver for its header
+fn verify_with_resolver<H, R: KeyResolver<H> + ?Sized>(header: &Header<H>,
+                                                       resolver: &R,
+                                                       signing_input: &[u8],
+                                                       signature: &[u8])
+                                                       -> Result<(), Error> {
+    let resolved_key = resolver.resolve(header)?;
+
+    // If the header specifies an algorithm that is not allowed, the signature is still verified
+    // with an allowed algorithm so that invalid signatures are reported first.
+    let algorithm = if resolved_key.algorithms.contains(&header.registered.algorithm) {
+        header.registered.algorithm
+    } else {
+        match resolved_key.algorithms.first() {
+            Some(algorithm) => *algorithm,
+            None => Err(ValidationError::WrongAlgorithmHeader)?,
+        }
+    };
+    let secret = match resolved_key.key {
+        VerificationKey::Secret(secret) => {
+            secret.check_algorithm(algorithm)?;
+            secret
+        }
+        VerificationKey::JWK(ref jwk) => Secret::verification_key_from_jwk(jwk, algorithm)?,
+    };
+
+    if !algorithm.verify(signature, signing_input, &secret)? {
+        Err(ValidationError::InvalidSignature)?;
+    }
+
+    if header.registered.algorithm != algorithm {
+        Err(ValidationError::WrongAlgorithmHeader)?;
+    }
+    Ok(())
+}
+
+/// JSON representation of a JWS, with one or more signatures over the same payload
+///
+/// Each signature has its own header, made up of integrity protected and unprotected header parameters. The
+/// encoded form can either be the General or the Flattened JWS JSON Serialization, as defined in
+/// [RFC7515#7.2](https://tools.ietf.org/html/rfc7515#section-7.2).
+///
+/// Serialization/deserialization is handled by serde. Before you transport the JWS, make sure you
+/// turn it into the encoded form first.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Json<T, H> {
+    /// Decoded form of the JWS.
+    /// This variant cannot be serialized or deserialized and will return an error.
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    Decoded {
+        /// Payload, usually a claims set
+        payload: T,
+        /// Headers of the signatures, in order
+        headers: Vec<JsonHeader<H>>,
+    },
+    /// Encoded and signed JWS. Use this form to send to your clients
+    Encoded(JsonSerialization),
+}
+
+/// How many of the signatures of a [`Json`] JWS have to be valid for it to be verified
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VerificationPolicy {
+    /// At least one signature has to be valid. Signatures that cannot be verified are dropped from the decoded JWS.
+    Any,
+    /// All signatures have to be valid
+    All,
+}
+
+impl<T: CompactPart, H: Serialize + DeserializeOwned> Json<T, H> {
+    /// New decoded JWS with one header for each signature
+    pub fn new_decoded(payload: T, headers: Vec<JsonHeader<H>>) -> Self {
+        Json::Decoded {
+            payload: payload,
+            headers: headers,
+        }
+    }
+
+    /// New encoded JWS
+    pub fn new_encoded(serialization: JsonSerialization) -> Self {
+        Json::Encoded(serialization)
+    }
+
+    /// Encode the JWS in the General JWS JSON Serialization, signing the payload once for each header.
+    ///
+    /// Each signature uses the algorithm from its header and the secret at the same position in `secrets`.
+    pub fn encode(&self, secrets: &[&Secret]) -> Result<Self, Error> {
+        match *self {
+            Json::Decoded {
+                ref payload,
+                ref headers,
+            } => {
+                if headers.len() != secrets.len() {
+                    Err(format!("{} secrets were provided for {} signatures", secrets.len(), headers.len()))?;
+                }
+
+                let encode = payload_is_base64url_encoded(headers.iter().map(|header| &header.header.registered))?;
+                let payload = json_payload(payload, encode)?;
+                let signatures = headers
+                    .iter()
+                    .zip(secrets)
+                    .map(|(header, secret)| header.sign(&payload, secret))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                Ok(Json::Encoded(JsonSerialization::General {
+                                     payload: payload,
+                                     signatures: signatures,
+                                 }))
+            }
+            Json::Encoded(_) => Err(Error::UnsupportedOperation),
+        }
+    }
+
+    /// Encode the JWS in the Flattened JWS JSON Serialization. The JWS must have exactly one header.
+    pub fn encode_flattened(&self, secret: &Secret) -> Result<Self, Error> {
+        match *self {
+            Json::Decoded {
+                ref payload,
+                ref headers,
+            } => {
+                if headers.len() != 1 {
+                    Err(format!("The flattened serialization requires exactly one signature, but there are {}",
+                                headers.len()))?;
+                }
+
+                let encode = headers[0].header.registered.payload_is_base64url_encoded()?;
+                let payload = json_payload(payload, encode)?;
+                let signature = headers[0].sign(&payload, secret)?;
+                Ok(Json::Encoded(JsonSerialization::Flattened {
+                                     payload: payload,
+                                     protected: signature.protected,
+                                     header: signature.header,
+                                     signature: signature.signature,
+                                 }))
+            }
+            Json::Encoded(_) => Err(Error::UnsupportedOperation),
+        }
+    }
+
+    /// Decode the JWS and verify its signatures using the keys returned by a [`KeyResolver`].
+    ///
+    /// The resolver is called with the unverified header of each signature, which is checked in the same way as
+    /// [`Compact::decode_with_resolver`]. Depending on the `policy`, either one or all of the signatures must be
+    /// valid. Otherwise, the error for the first signature that could not be verified is returned.
+    pub fn decode_with_resolver<R: KeyResolver<H> + ?Sized>(&self,
+                                                            resolver: &R,
+                                                            policy: VerificationPolicy)
+                                                            -> Result<Self, Error> {
+        self.decode_with_options(resolver, policy, Default::default())
+    }
+
+    /// Decode the JWS like [`decode_with_resolver`](#method.decode_with_resolver), with the options set in `options`
+    pub fn decode_with_options<R: KeyResolver<H> + ?Sized>(&self,
+                                                           resolver: &R,
+                                                           policy: VerificationPolicy,
+                                                           options: DecodeOptions)
+                                                           -> Result<Self, Error> {
+        match *self {
+            Json::Decoded { .. } => Err(Error::UnsupportedOperation),
+            Json::Encoded(ref encoded) => {
+                let signatures = encoded.signatures();
+                if signatures.is_empty() {
+                    Err(ValidationError::InvalidToken)?;
+                }
+
+                let mut headers = vec![];
+                let mut first_error = None;
+                for signature in &signatures {
+                    match signature.verify(encoded.payload(), resolver, options) {
+                        Ok(header) => headers.push(header),
+                        Err(e) => {
+                            if policy == VerificationPolicy::All {
+                                return Err(e);
+                            }
+                            first_error = first_error.or(Some(e));
+                        }
+                    }
+                }
+                if headers.is_empty() {
+                    Err(first_error.unwrap_or(Error::ValidationError(ValidationError::InvalidSignature)))?;
+                }
+
+                let payload = if payload_is_base64url_encoded(headers.iter().map(|header| &header.header.registered))? {
+                    T::from_base64(&encoded.payload())?
+                } else {
+                    T::from_bytes(encoded.payload().as_bytes())?
+                };
+                Ok(Self::new_decoded(payload, headers))
+            }
+        }
+    }
+
+    /// Convenience method to get a reference to the encoded serialization from an encoded JWS
+    pub fn encoded(&self) -> Result<&JsonSerialization, Error> {
+        match *self {
+            Json::Decoded { .. } => Err(Error::UnsupportedOperation),
+            Json::Encoded(ref encoded) => Ok(encoded),
+        }
+    }
+
+    /// Convenience method to get a reference to the payload from a decoded JWS
+    pub fn payload(&self) -> Result<&T, Error> {
+        match *self {
+            Json::Decoded { ref payload, .. } => Ok(payload),
+            Json::Encoded(_) => Err(Error::UnsupportedOperation),
+        }
+    }
+
+    /// Convenience method to get a reference to the headers of the signatures from a decoded JWS
+    pub fn headers(&self) -> Result<&[JsonHeader<H>], Error> {
+        match *self {
+            Json::Decoded { ref headers, .. } => Ok(headers),
+            Json::Encoded(_) => Err(Error::UnsupportedOperation),
+        }
+    }
+}
+
+/// Header of one of the signatures of a [`Json`] JWS
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JsonHeader<H> {
+    /// The JOSE header, which is the union of the protected and the unprotected header parameters
+    pub header: Header<H>,
+    /// Names of the header parameters that are not integrity protected. These are serialized to the `header`
+    /// member of the signature, and all other header parameters to the `protected` member.
+    pub unprotected: Vec<String>,
+}
+
+impl<H: Serialize + DeserializeOwned> JsonHeader<H> {
+    /// New header where only the named header parameters are unprotected
+    pub fn new(header: Header<H>, unprotected: &[&str]) -> Self {
+        Self {
+            header: header,
+            unprotected: unprotected.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    /// Sign the base64 URL encoded payload
+    fn sign(&self, payload: &str, secret: &Secret) -> Result<JsonSignature, Error> {
+        let parameters = match serde_json::to_value(&self.header)? {
+            serde_json::Value::Object(parameters) => parameters,
+            _ => Err("The header must be a JSON object".to_string())?,
+        };
+
+        if let Some(name) = self.unprotected.iter().find(|name| *name == "b64" || *name == "crit") {
+            Err(format!("The {} header parameter must be integrity protected", name))?;
+        }
+
+        let mut protected = serde_json::Map::new();
+        let mut unprotected = serde_json::Map::new();
+        for (name, value) in parameters {
+            if self.unprotected.contains(&name) {
+                unprotected.insert(name, value);
+            } else {
+                protected.insert(name, value);
+            }
+        }
+
+        let protected = if protected.is_empty() {
+            None
+        } else {
+            Some(base64url::encode_nopad(&serde_json::to_vec(&protected)?))
+        };
+        let signing_input = format!("{}.{}", protected.as_ref().map_or("", |protected| protected), payload);
+        let signature = self.header
+            .registered
+            .algorithm
+            .sign(signing_input.as_bytes(), secret)?;
+
+        Ok(JsonSignature {
+               protected: protected,
+               header: if unprotected.is_empty() {
+                   None
+               } else {
+                   Some(unprotected)
+               },
+               signature: base64url::encode_nopad(&signature),
+           })
+    }
+}
+
+impl<H: Serialize + DeserializeOwned> From<Header<H>> for JsonHeader<H> {
+    fn from(header: Header<H>) -> Self {
+        Self {
+            header: header,
+            unprotected: vec![],
+        }
+    }
+}
+
+/// Encoded JWS in one of the JWS JSON Serializations
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JsonSerialization {
+    /// General JWS JSON Serialization, with any number of signatures.
+    /// Defined in [RFC7515#7.2.1](https://tools.ietf.org/html/rfc7515#section-7.2.1).
+    General {
+        /// Base64 URL encoded payload
+        payload: String,
+        /// Signatures over the payload
+        signatures: Vec<JsonSignature>,
+    },
+    /// Flattened JWS JSON Serialization, with a single signature whose members are part of the top-level object.
+    /// Defined in [RFC7515#7.2.2](https://tools.ietf.org/html/rfc7515#section-7.2.2).
+    Flattened {
+        /// Base64 URL encoded payload
+        payload: String,
+        /// Base64 URL encoded protected header, if any
+        #[serde(skip_serializing_if = "Option::is_none")]
+        protected: Option<String>,
+        /// Unprotected header parameters, if any
+        #[serde(skip_serializing_if = "Option::is_none")]
+        header: Option<serde_json::Map<String, serde_json::Value>>,
+        /// Base64 URL encoded signature
+        signature: String,
+    },
+}
+
+impl JsonSerialization {
+    /// The base64 URL encoded payload
+    pub fn payload(&self) -> &str {
+        match *self {
+            JsonSerialization::General { ref payload, .. } |
+            JsonSerialization::Flattened { ref payload, .. } => payload,
+        }
+    }
+
+    /// The signatures over the payload. The Flattened serialization always has exactly one.
+    pub fn signatures(&self) -> Vec<JsonSignature> {
+        match *self {
+            JsonSerialization::General { ref signatures, .. } => signatures.clone(),
+            JsonSerialization::Flattened {
+                ref protected,
+                ref header,
+                ref signature,
+                ..
+            } => {
+                vec![JsonSignature {
+                         protected: protected.clone(),
+                         header: header.clone(),
+                         signature: signature.clone(),
+                     }]
+            }
+        }
+    }
+}
+
+/// A signature in the General JWS JSON Serialization
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct JsonSignature {
+    /// Base64 URL encoded protected header, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protected: Option<String>,
+    /// Unprotected header parameters, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Base64 URL encoded signature
+    pub signature: String,
+}
+
+impl JsonSignature {
+    /// Verify the signature over the base64 URL encoded payload, returning its header
+    fn verify<H, R>(&self, payload: &str, resolver: &R, options: DecodeOptions) -> Result<JsonHeader<H>, Error>
+        where H: Serialize + DeserializeOwned,
+              R: KeyResolver<H> + ?Sized
+    {
+        let mut parameters: serde_json::Map<String, serde_json::Value> = match self.protected {
+            Some(ref protected) => serde_json::from_slice(&base64url::decode_nopad(protected.as_bytes())?)?,
+            None => serde_json::Map::new(),
+        };
+
+        // The protected and unprotected header parameters must be disjoint
+        let mut unprotected = vec![];
+        if let Some(ref header) = self.header {
+            for (name, value) in header {
+                if parameters.contains_key(name) {
+                    Err(format!("Header parameter {} is both protected and unprotected", name))?;
+                }
+                if name == "b64" {
+                    Err("The b64 header parameter must be integrity protected".to_string())?;
+                }
+                if name == "crit" {
+                    Err(ValidationError::InvalidCriticalHeader("crit must be integrity protected".to_string()))?;
+                }
+                parameters.insert(name.clone(), value.clone());
+                unprotected.push(name.clone());
+            }
+        }
+        validate_critical(&parameters, options)?;
+        let header: Header<H> = serde_json::from_value(serde_json::Value::Object(parameters))?;
+
+        let signing_input = format!("{}.{}", self.protected.as_ref().map_or("", |protected| protected), payload);
+        let signature = base64url::decode_nopad(self.signature.as_bytes())?;
+        verify_with_resolver(&header, resolver, signing_input.as_bytes(), &signature)?;
+
+        Ok(JsonHeader {
+               header: header,
+               unprotected: unprotected,
+           })
+    }
+}
+
 /// The secrets used to sign and/or encrypt tokens
+#[derive(Clone)]
 pub enum Secret {
     /// Used with the `None` algorithm variant.
     None,
@@ -287,39 +935,514 @@ pub enum Secret {
     ///
     /// let secret = Secret::public_key_from_file("test/fixtures/rsa_public_key.der");
     PublicKey(Vec<u8>),
+    /// An ECDSA Key pair constructed from a PKCS#8 DER-encoded private key, or from an Elliptic Curve JWK
+    /// containing the private key parameter `d`. Only the P-256 and P-384 curves are supported.
+    ///
+    /// To generate a private key, use
+    ///
+    /// ```sh
+    /// openssl genpkey -algorithm EC \
+    ///                 -pkeyopt ec_paramgen_curve:P-256 \
+    ///                 -outform der \
+    ///                 -out private_key.der
+    /// ```
+    ///
+    /// Keys in the "traditional" `ECPrivateKey` format can be converted into PKCS#8 using
+    /// an OpenSSL command like this:
+    ///
+    /// ```sh
+    /// openssl pkcs8 -topk8 -nocrypt \
+    ///               -inform DER \
+    ///               -in private_key.der \
+    ///               -outform DER \
+    ///               -out private_key_pkcs8.der
+    /// ```
+    ///
+    /// # Examples
+    /// ```
+    /// use biscuit::jws::Secret;
+    ///
+    /// let secret = Secret::ecdsa_keypair_from_file("test/fixtures/ecdsa_p256_private_key.der");
+    /// ```
+    ECDSAKeyPair(Arc<ECDSAKeyPair>),
+    /// An Ed25519 Key pair, used with the `EdDSA` algorithm. It can be constructed from an Octet Key Pair JWK
+    /// containing the private key parameter `d`.
+    ///
+    /// To verify signatures, use the 32 byte public key (the `x` parameter of the JWK) with
+    /// [`Secret::PublicKey`].
+    Ed25519KeyPair(Arc<signature::Ed25519KeyPair>),
+}
+
+impl Secret {
+    fn read_bytes(path: &str) -> Result<Vec<u8>, Error> {
+        use std::io::prelude::*;
+        use std::fs::File;
+
+        let mut file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let mut bytes: Vec<u8> = Vec::with_capacity(metadata.len() as usize);
+        file.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Convenience function to create a secret bytes array from a string
+    /// See example in the [`Secret::Bytes`] variant documentation for usage.
+    pub fn bytes_from_str(secret: &str) -> Self {
+        Secret::Bytes(secret.to_string().into_bytes())
+    }
+
+    /// Convenience function to get the RSA Keypair from a DER encoded RSA private key.
+    /// See example in the [`Secret::RSAKeyPair`] variant documentation for usage.
+    pub fn rsa_keypair_from_file(path: &str) -> Result<Self, Error> {
+        let der = Self::read_bytes(path)?;
+        let key_pair = signature::RSAKeyPair::from_der(untrusted::Input::from(der.as_slice()))?;
+        Ok(Secret::RSAKeyPair(Arc::new(key_pair)))
+    }
+
+    /// Convenience function to create a Public key from a DER encoded RSA or ECDSA public key
+    /// See examples in the [`Secret::PublicKey`] variant documentation for usage.
+    pub fn public_key_from_file(path: &str) -> Result<Self, Error> {
+        let der = Self::read_bytes(path)?;
+        Ok(Secret::PublicKey(der.to_vec()))
+    }
+
+    /// Convenience function to get the ECDSA Keypair from a PKCS#8 DER encoded EC private key.
+    /// See example in the [`Secret::ECDSAKeyPair`] variant documentation for usage.
+    pub fn ecdsa_keypair_from_file(path: &str) -> Result<Self, Error> {
+        let der = Self::read_bytes(path)?;
+        let key_pair = ECDSAKeyPair::from_pkcs8(&der)?;
+        Ok(Secret::ECDSAKeyPair(Arc::new(key_pair)))
+    }
+
+    /// Convenience function to get the ECDSA Keypair from an Elliptic Curve JWK.
+    /// The JWK must contain the private key parameter `d`.
+    pub fn ecdsa_keypair_from_jwk<T: Serialize + DeserializeOwned>(jwk: &jwk::JWK<T>) -> Result<Self, Error> {
+        let key_pair = ECDSAKeyPair::from_jwk(jwk)?;
+        Ok(Secret::ECDSAKeyPair(Arc::new(key_pair)))
+    }
+
+    /// Convenience function to get the Ed25519 Keypair from an Octet Key Pair JWK.
+    /// The JWK must use the `Ed25519` curve and contain the private key parameter `d`.
+    pub fn ed25519_keypair_from_jwk<T: Serialize + DeserializeOwned>(jwk: &jwk::JWK<T>) -> Result<Self, Error> {
+        let parameters = match jwk.algorithm {
+            jwk::AlgorithmParameters::OctetKeyPair(ref parameters) => parameters,
+            ref others => Err(unexpected_key_type_error!(jwk::KeyType::OctectKeyPair, others.key_type()))?,
+        };
+        if parameters.curve != jwk::OctetKeyPairCurve::Ed25519 {
+            Err(Error::UnsupportedOperation)?;
+        }
+        let d = match parameters.d {
+            Some(ref d) => d,
+            None => Err("The JWK does not contain the private key parameter `d`".to_string())?,
+        };
+
+        let key_pair = signature::Ed25519KeyPair::from_bytes(d, &parameters.x)?;
+        Ok(Secret::Ed25519KeyPair(Arc::new(key_pair)))
+    }
+
+    /// Convert a JWK into the secret needed to sign with `algorithm`.
+    ///
+    /// - `HS256`, `HS384` and `HS512` require a symmetric `oct` key
+    /// - `RS*` and `PS*` algorithms require an `RSA` private key with all of the `d`, `p`, `q`, `dp`, `dq` and `qi`
+    ///   parameters. Keys with more than two primes are not supported.
+    /// - `ES256` and `ES384` require an `EC` private key on the P-256 and P-384 curves respectively
+    /// - `EdDSA` requires an `OKP` private key on the Ed25519 curve
+    ///
+    /// An error is returned if the key type of the JWK does not fit the algorithm.
+    pub fn signing_key_from_jwk<T: Serialize + DeserializeOwned>(jwk: &jwk::JWK<T>,
+                                                                 algorithm: SignatureAlgorithm)
+                                                                 -> Result<Self, Error> {
+        Self::check_jwk_for_algorithm(jwk, algorithm)?;
+
+        match jwk.algorithm {
+            jwk::AlgorithmParameters::OctectKey { ref value, .. } => Ok(Secret::Bytes(value.clone())),
+            jwk::AlgorithmParameters::RSA(ref parameters) => {
+                let der = rsa_private_key_der(parameters)?;
+                let key_pair = signature::RSAKeyPair::from_der(untrusted::Input::from(der.as_slice()))?;
+                Ok(Secret::RSAKeyPair(Arc::new(key_pair)))
+            }
+            jwk::AlgorithmParameters::EllipticCurve(_) => Self::ecdsa_keypair_from_jwk(jwk),
+            jwk::AlgorithmParameters::OctetKeyPair(_) => Self::ed25519_keypair_from_jwk(jwk),
+        }
+    }
+
+    /// Convert a JWK into the secret needed to verify a signature made with `algorithm`.
+    ///
+    /// The same key types as [`Secret::signing_key_from_jwk`] are accepted, but only the public parameters
+    /// of asymmetric keys are used.
+    ///
+    /// An error is returned if the key type of the JWK does not fit the algorithm.
+    pub fn verification_key_from_jwk<T: Serialize + DeserializeOwned>(jwk: &jwk::JWK<T>,
+                                                                      algorithm: SignatureAlgorithm)
+                                                                      -> Result<Self, Error> {
+        Self::check_jwk_for_algorithm(jwk, algorithm)?;
+
+        match jwk.algorithm {
+            jwk::AlgorithmParameters::OctectKey { ref value, .. } => Ok(Secret::Bytes(value.clone())),
+            jwk::AlgorithmParameters::RSA(ref parameters) => Ok(Secret::PublicKey(rsa_public_key_der(parameters))),
+            jwk::AlgorithmParameters::EllipticCurve(ref parameters) => {
+                let mut public_key = vec![0x04];
+                public_key.extend_from_slice(&parameters.x);
+                public_key.extend_from_slice(&parameters.y);
+                Ok(Secret::PublicKey(public_key))
+            }
+            jwk::AlgorithmParameters::OctetKeyPair(ref parameters) => Ok(Secret::PublicKey(parameters.x.clone())),
+        }
+    }
+
+    /// Returns a description of the type of secret
+    fn secret_type(&self) -> &'static str {
+        match *self {
+            Secret::None => "None",
+            Secret::Bytes(_) => "Bytes",
+            Secret::RSAKeyPair(_) => "RSAKeyPair",
+            Secret::PublicKey(_) => "PublicKey",
+            Secret::ECDSAKeyPair(_) => "ECDSAKeyPair",
+            Secret::Ed25519KeyPair(_) => "Ed25519KeyPair",
+        }
+    }
+
+    /// Check that the type of the secret can be used with the algorithm, to protect against algorithm confusion
+    fn check_algorithm(&self, algorithm: SignatureAlgorithm) -> Result<(), Error> {
+        use jwa::SignatureAlgorithm::*;
+
+        let (allowed, expected) = match algorithm {
+            None => (match *self { Secret::None => true, _ => false }, "None"),
+            HS256 | HS384 | HS512 => (match *self { Secret::Bytes(_) => true, _ => false }, "Bytes"),
+            RS256 | RS384 | RS512 | PS256 | PS384 | PS512 => {
+                (match *self { Secret::RSAKeyPair(_) | Secret::PublicKey(_) => true, _ => false },
+                 "RSAKeyPair or PublicKey")
+            }
+            ES256 | ES384 | ES512 => {
+                (match *self { Secret::ECDSAKeyPair(_) | Secret::PublicKey(_) => true, _ => false },
+                 "ECDSAKeyPair or PublicKey")
+            }
+            EdDSA => {
+                (match *self { Secret::Ed25519KeyPair(_) | Secret::PublicKey(_) => true, _ => false },
+                 "Ed25519KeyPair or PublicKey")
+            }
+        };
+
+        if !allowed {
+            Err(Error::WrongKeyType {
+                    expected: expected.to_string(),
+                    actual: self.secret_type().to_string(),
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Check that the key type, and curve if any, of the JWK can be used with the algorithm
+    fn check_jwk_for_algorithm<T>(jwk: &jwk::JWK<T>, algorithm: SignatureAlgorithm) -> Result<(), Error> {
+        use jwa::SignatureAlgorithm::*;
+
+        let expected_key_type = match algorithm {
+            None => Err("JWKs cannot be used with the `none` algorithm".to_string())?,
+            HS256 | HS384 | HS512 => jwk::KeyType::Octect,
+            RS256 | RS384 | RS512 | PS256 | PS384 | PS512 => jwk::KeyType::RSA,
+            ES256 | ES384 | ES512 => jwk::KeyType::EllipticCurve,
+            EdDSA => jwk::KeyType::OctectKeyPair,
+        };
+        let actual_key_type = jwk.algorithm.key_type();
+        if actual_key_type != expected_key_type {
+            Err(unexpected_key_type_error!(expected_key_type, actual_key_type))?;
+        }
+
+        let curve_matches = match jwk.algorithm {
+            jwk::AlgorithmParameters::EllipticCurve(ref parameters) => {
+                match (algorithm, &parameters.curve) {
+                    (ES256, &jwk::EllipticCurve::P256) |
+                    (ES384, &jwk::EllipticCurve::P384) |
+                    (ES512, &jwk::EllipticCurve::P521) => true,
+                    _ => false,
+                }
+            }
+            jwk::AlgorithmParameters::OctetKeyPair(ref parameters) => {
+                parameters.curve == jwk::OctetKeyPairCurve::Ed25519
+            }
+            _ => true,
+        };
+        if !curve_matches {
+            Err(format!("The curve of the JWK cannot be used with {:?}", algorithm))?;
+        }
+        Ok(())
+    }
+}
+
+/// The key used to verify a JWS, returned by a [`KeyResolver`]
+#[derive(Clone)]
+pub enum VerificationKey {
+    /// A secret that can be used with the algorithm directly
+    Secret(Secret),
+    /// A JWK, which will be converted with [`Secret::verification_key_from_jwk`] for the algorithm of the JWS
+    JWK(jwk::JWK<Empty>),
+}
+
+/// A key returned by a [`KeyResolver`], and the algorithms that the key may be used with
+#[derive(Clone)]
+pub struct ResolvedKey {
+    /// The key to verify the JWS with
+    pub key: VerificationKey,
+    /// The algorithms that the key may be used with. A JWS whose header specifies any other algorithm is rejected.
+    pub algorithms: Vec<SignatureAlgorithm>,
+}
+
+/// A fixed key which is used regardless of the header
+impl<H> KeyResolver<H> for ResolvedKey {
+    fn resolve(&self, _header: &Header<H>) -> Result<ResolvedKey, Error> {
+        Ok(self.clone())
+    }
+}
+
+/// Resolves the key used to verify a JWS from its header.
+///
+/// Implement this trait to look up keys from places such as a cache or a database, and use it with
+/// [`Compact::decode_with_resolver`].
+pub trait KeyResolver<H> {
+    /// Return the key to verify the JWS with, along with the algorithms that the key may be used with.
+    ///
+    /// The header has not been verified at this point, so it must only be used to find the key.
+    /// Return a `ValidationError` such as `ValidationError::KeyNotFound` if no key can be found.
+    fn resolve(&self, header: &Header<H>) -> Result<ResolvedKey, Error>;
+}
+
+/// A [`KeyResolver`] that holds its keys in memory.
+///
+/// Keys are selected by the key ID (`kid`) in the header. A default key can be set for tokens
+/// without a key ID.
+///
+/// # Examples
+/// ```
+/// use biscuit::jws::{InMemoryKeyResolver, ResolvedKey, Secret, VerificationKey};
+/// use biscuit::jwa::SignatureAlgorithm;
+///
+/// let mut resolver = InMemoryKeyResolver::new();
+/// resolver.insert("key-1",
+///                 ResolvedKey {
+///                     key: VerificationKey::Secret(Secret::bytes_from_str("secret")),
+///                     algorithms: vec![SignatureAlgorithm::HS256],
+///                 });
+/// ```
+#[derive(Clone, Default)]
+pub struct InMemoryKeyResolver {
+    keys: HashMap<String, ResolvedKey>,
+    default_key: Option<ResolvedKey>,
+}
+
+impl InMemoryKeyResolver {
+    /// Create an empty resolver
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a key for the key ID, returning the key previously added for the key ID, if any
+    pub fn insert(&mut self, key_id: &str, key: ResolvedKey) -> Option<ResolvedKey> {
+        self.keys.insert(key_id.to_string(), key)
+    }
+
+    /// Remove the key for the key ID, returning it if there was one
+    pub fn remove(&mut self, key_id: &str) -> Option<ResolvedKey> {
+        self.keys.remove(key_id)
+    }
+
+    /// Set the key used for tokens without a key ID in their header
+    pub fn set_default_key(&mut self, key: Option<ResolvedKey>) {
+        self.default_key = key;
+    }
+}
+
+impl<H> KeyResolver<H> for InMemoryKeyResolver {
+    fn resolve(&self, header: &Header<H>) -> Result<ResolvedKey, Error> {
+        let key = match header.registered.key_id {
+            Some(ref key_id) => self.keys.get(key_id),
+            None => self.default_key.as_ref(),
+        };
+        match key {
+            Some(key) => Ok(key.clone()),
+            None => Err(ValidationError::KeyNotFound(header.registered.key_id.clone()))?,
+        }
+    }
+}
+
+/// Selects a key using the key ID (`kid`) and algorithm (`alg`) in the header.
+///
+/// Keys whose `use`, `key_ops`, `alg` or key type do not allow them to verify signatures with the
+/// algorithm of the header are never used. If the header has no key ID, the only key in the set that
+/// is allowed to be used is selected.
+///
+/// The following validation errors are returned when a key cannot be selected:
+///
+/// - `ValidationError::KeyNotFound` when no key has the key ID of the header
+/// - `ValidationError::KeyNotAllowed` when none of the keys found can be used with the algorithm
+/// - `ValidationError::AmbiguousKey` when more than one key can be used
+impl<H, K: Serialize + DeserializeOwned> KeyResolver<H> for jwk::JWKSet<K> {
+    fn resolve(&self, header: &Header<H>) -> Result<ResolvedKey, Error> {
+        let algorithm = header.registered.algorithm;
+        let key_id = &header.registered.key_id;
+
+        let candidates: Vec<&jwk::JWK<K>> = match *key_id {
+            Some(ref key_id) => {
+                self.keys
+                    .iter()
+                    .filter(|jwk| jwk.common.key_id.as_ref() == Some(key_id))
+                    .collect()
+            }
+            None => self.keys.iter().collect(),
+        };
+        if candidates.is_empty() {
+            Err(ValidationError::KeyNotFound(key_id.clone()))?;
+        }
+
+        let allowed: Vec<&jwk::JWK<K>> = candidates
+            .into_iter()
+            .filter(|jwk| jwk_allows_verification(jwk, algorithm))
+            .collect();
+        match allowed.len() {
+            0 => Err(ValidationError::KeyNotAllowed(key_id.clone()))?,
+            1 => {
+                Ok(ResolvedKey {
+                       key: VerificationKey::JWK(allowed[0].clone_without_additional()),
+                       algorithms: vec![algorithm],
+                   })
+            }
+            _ => Err(ValidationError::AmbiguousKey(key_id.clone()))?,
+        }
+    }
 }
 
-impl Secret {
-    fn read_bytes(path: &str) -> Result<Vec<u8>, Error> {
-        use std::io::prelude::*;
-        use std::fs::File;
+/// Resolves the secret tied to the algorithm in the header
+struct AllowedAlgorithms<'a>(&'a [(SignatureAlgorithm, &'a Secret)]);
+
+impl<'a, H> KeyResolver<H> for AllowedAlgorithms<'a> {
+    fn resolve(&self, header: &Header<H>) -> Result<ResolvedKey, Error> {
+        let algorithm = header.registered.algorithm;
+        match self.0.iter().find(|&&(allowed, _)| allowed == algorithm) {
+            Some(&(_, secret)) => {
+                Ok(ResolvedKey {
+                       key: VerificationKey::Secret(secret.clone()),
+                       algorithms: vec![algorithm],
+                   })
+            }
+            None => Err(ValidationError::WrongAlgorithmHeader)?,
+        }
+    }
+}
 
-        let mut file = File::open(path)?;
-        let metadata = file.metadata()?;
-        let mut bytes: Vec<u8> = Vec::with_capacity(metadata.len() as usize);
-        file.read_to_end(&mut bytes)?;
-        Ok(bytes)
+/// Returns whether the `use`, `key_ops` and `alg` parameters and key type of the JWK allow it to be used to
+/// verify signatures made with `algorithm`
+fn jwk_allows_verification<T>(jwk: &jwk::JWK<T>, algorithm: SignatureAlgorithm) -> bool {
+    let use_allowed = match jwk.common.public_key_use {
+        Some(ref public_key_use) => *public_key_use == jwk::PublicKeyUse::Signature,
+        None => true,
+    };
+    let operations_allowed = match jwk.common.key_operations {
+        Some(ref operations) => operations.contains(&jwk::KeyOperations::Verify),
+        None => true,
+    };
+    let algorithm_allowed = match jwk.common.algorithm {
+        Some(ref key_algorithm) => *key_algorithm == Algorithm::Signature(algorithm),
+        None => true,
+    };
+
+    use_allowed && operations_allowed && algorithm_allowed && Secret::check_jwk_for_algorithm(jwk, algorithm).is_ok()
+}
+
+/// DER encode the `RSAPublicKey` structure from
+/// [RFC 3447 Appendix A.1.1](https://tools.ietf.org/html/rfc3447#appendix-A.1.1)
+fn rsa_public_key_der(parameters: &jwk::RSAKeyParameters) -> Vec<u8> {
+    der::sequence(&[der::integer(&parameters.n.to_bytes_be()), der::integer(&parameters.e.to_bytes_be())])
+}
+
+/// DER encode the two-prime `RSAPrivateKey` structure from
+/// [RFC 3447 Appendix A.1.2](https://tools.ietf.org/html/rfc3447#appendix-A.1.2)
+fn rsa_private_key_der(parameters: &jwk::RSAKeyParameters) -> Result<Vec<u8>, Error> {
+    if parameters.other_primes_info.is_some() {
+        Err(Error::UnsupportedOperation)?;
     }
 
-    /// Convenience function to create a secret bytes array from a string
-    /// See example in the [`Secret::Bytes`] variant documentation for usage.
-    pub fn bytes_from_str(secret: &str) -> Self {
-        Secret::Bytes(secret.to_string().into_bytes())
+    let private_parameters = [&parameters.d,
+                              &parameters.p,
+                              &parameters.q,
+                              &parameters.dp,
+                              &parameters.dq,
+                              &parameters.qi];
+    let mut values = vec![der::integer(&[0]),
+                          der::integer(&parameters.n.to_bytes_be()),
+                          der::integer(&parameters.e.to_bytes_be())];
+    for parameter in private_parameters.iter() {
+        match **parameter {
+            Some(ref value) => values.push(der::integer(&value.to_bytes_be())),
+            None => {
+                Err("The RSA JWK must contain all of the `d`, `p`, `q`, `dp`, `dq` and `qi` parameters".to_string())?
+            }
+        }
     }
+    Ok(der::sequence(&values))
+}
 
-    /// Convenience function to get the RSA Keypair from a DER encoded RSA private key.
-    /// See example in the [`Secret::RSAKeyPair`] variant documentation for usage.
-    pub fn rsa_keypair_from_file(path: &str) -> Result<Self, Error> {
-        let der = Self::read_bytes(path)?;
-        let key_pair = signature::RSAKeyPair::from_der(untrusted::Input::from(der.as_slice()))?;
-        Ok(Secret::RSAKeyPair(Arc::new(key_pair)))
+/// An ECDSA key pair used for signing with the `ES256` or `ES384` algorithms
+pub enum ECDSAKeyPair {
+    /// A key pair on the P-256 curve, used with `ES256`
+    P256(p256::ecdsa::SigningKey),
+    /// A key pair on the P-384 curve, used with `ES384`
+    P384(p384::ecdsa::SigningKey),
+}
+
+impl ECDSAKeyPair {
+    /// Parse a PKCS#8 DER encoded private key. The curve is detected from the key itself.
+    pub fn from_pkcs8(der: &[u8]) -> Result<Self, Error> {
+        use p256::pkcs8::DecodePrivateKey;
+
+        if let Ok(key) = p256::ecdsa::SigningKey::from_pkcs8_der(der) {
+            return Ok(ECDSAKeyPair::P256(key));
+        }
+        match p384::ecdsa::SigningKey::from_pkcs8_der(der) {
+            Ok(key) => Ok(ECDSAKeyPair::P384(key)),
+            Err(_) => Err(Error::UnspecifiedCryptographicError),
+        }
     }
 
-    /// Convenience function to create a Public key from a DER encoded RSA or ECDSA public key
-    /// See examples in the [`Secret::PublicKey`] variant documentation for usage.
-    pub fn public_key_from_file(path: &str) -> Result<Self, Error> {
-        let der = Self::read_bytes(path)?;
-        Ok(Secret::PublicKey(der.to_vec()))
+    /// Construct a key pair from an Elliptic Curve JWK containing the private key parameter `d`.
+    /// The public key coordinates in the JWK must match the private key.
+    pub fn from_jwk<T: Serialize + DeserializeOwned>(jwk: &jwk::JWK<T>) -> Result<Self, Error> {
+        let parameters = match jwk.algorithm {
+            jwk::AlgorithmParameters::EllipticCurve(ref parameters) => parameters,
+            ref others => Err(unexpected_key_type_error!(jwk::KeyType::EllipticCurve, others.key_type()))?,
+        };
+        let d = match parameters.d {
+            Some(ref d) => d,
+            None => Err("The JWK does not contain the private key parameter `d`".to_string())?,
+        };
+
+        let key_pair = match parameters.curve {
+            jwk::EllipticCurve::P256 => {
+                ECDSAKeyPair::P256(p256::ecdsa::SigningKey::from_slice(d)
+                                       .map_err(|_| Error::UnspecifiedCryptographicError)?)
+            }
+            jwk::EllipticCurve::P384 => {
+                ECDSAKeyPair::P384(p384::ecdsa::SigningKey::from_slice(d)
+                                       .map_err(|_| Error::UnspecifiedCryptographicError)?)
+            }
+            jwk::EllipticCurve::P521 => Err(Error::UnsupportedOperation)?,
+        };
+
+        let mut expected_public_key = vec![0x04];
+        expected_public_key.extend_from_slice(&parameters.x);
+        expected_public_key.extend_from_slice(&parameters.y);
+        if key_pair.public_key() != expected_public_key {
+            Err("The public key parameters `x` and `y` do not match the private key `d`".to_string())?;
+        }
+
+        Ok(key_pair)
+    }
+
+    /// Returns the public key as an uncompressed curve point, suitable for use with
+    /// [`Secret::PublicKey`] for verification.
+    pub fn public_key(&self) -> Vec<u8> {
+        match *self {
+            ECDSAKeyPair::P256(ref key) => key.verifying_key().to_encoded_point(false).as_bytes().to_vec(),
+            ECDSAKeyPair::P384(ref key) => key.verifying_key().to_encoded_point(false).as_bytes().to_vec(),
+        }
     }
 }
 
@@ -425,8 +1548,16 @@ pub struct RegisteredHeader {
     #[serde(rename = "x5t", skip_serializing_if = "Option::is_none")]
     pub x509_fingerprint: Option<String>,
 
+    /// Whether the payload is base64url encoded, which is the case when this is absent.
+    /// If this is present, `b64` must also be listed in `critical`.
+    /// Serialized to `b64`.
+    /// Defined in [RFC7797#3](https://tools.ietf.org/html/rfc7797#section-3).
+    #[serde(rename = "b64", skip_serializing_if = "Option::is_none")]
+    pub base64url_encode_payload: Option<bool>,
+
     /// List of critical extended headers.
-    /// This is currently not implemented (correctly).
+    /// A JWS is rejected when decoding if it lists extensions other than `b64` that are not in the
+    /// `understood_critical_headers` of the [`DecodeOptions`].
     /// Serialized to `crit`.
     /// Defined in [RFC7515#4.1.11](https://tools.ietf.org/html/rfc7515#section-4.1.11).
     #[serde(rename = "crit", skip_serializing_if = "Option::is_none")]
@@ -445,19 +1576,42 @@ impl Default for RegisteredHeader {
             x509_url: None,
             x509_chain: None,
             x509_fingerprint: None,
+            base64url_encode_payload: None,
             critical: None,
         }
     }
 }
 
+impl RegisteredHeader {
+    /// Returns whether the payload is base64url encoded, checking that `b64` is listed in `critical` if present
+    fn payload_is_base64url_encoded(&self) -> Result<bool, Error> {
+        match self.base64url_encode_payload {
+            None => Ok(true),
+            Some(encoded) => {
+                let critical = self.critical
+                    .as_ref()
+                    .map_or(false, |critical| critical.iter().any(|name| name == "b64"));
+                if !critical {
+                    Err(ValidationError::MissingRequired("b64 in crit".to_string()))?;
+                }
+                Ok(encoded)
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::{self, FromStr};
 
+    use data_encoding::base64url;
     use serde_json;
 
-    use {Empty, ClaimsSet, RegisteredClaims, SingleOrMultiple, CompactJson};
-    use super::{Secret, SignatureAlgorithm, Header, RegisteredHeader, Compact};
+    use {Empty, ClaimsSet, RegisteredClaims, SingleOrMultiple, CompactJson, CompactPart};
+    use errors::{Error, ValidationError};
+    use jwk::{JWK, JWKSet};
+    use super::{Secret, SignatureAlgorithm, Header, RegisteredHeader, Compact, InMemoryKeyResolver, KeyResolver,
+                ResolvedKey, VerificationKey, Json, JsonHeader, JsonSerialization, VerificationPolicy, DecodeOptions};
 
     #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
     struct PrivateClaims {
@@ -708,6 +1862,748 @@ mod tests {
         assert_eq!(payload, *not_err!(biscuit.payload()));
     }
 
+    fn private_jwk_set() -> JWKSet<Empty> {
+        not_err!(serde_json::from_str(include_str!("../test/fixtures/jwk_private_key.json")))
+    }
+
+    fn public_jwk_set() -> JWKSet<Empty> {
+        not_err!(serde_json::from_str(include_str!("../test/fixtures/jwk_public_key.json")))
+    }
+
+    #[test]
+    fn compact_jws_round_trip_with_jwk() {
+        let private_keys = private_jwk_set();
+        let public_keys = public_jwk_set();
+        let payload = b"payload".to_vec();
+
+        // The first key in each set is an EC P-256 key, and the second an RSA key
+        for &(index, algorithm) in &[(0, SignatureAlgorithm::ES256),
+                                     (1, SignatureAlgorithm::RS256),
+                                     (1, SignatureAlgorithm::PS512)] {
+            let jws = Compact::<Vec<u8>, Empty>::new_decoded(From::from(RegisteredHeader {
+                                                                            algorithm: algorithm,
+                                                                            ..Default::default()
+                                                                        }),
+                                                             payload.clone());
+            let token = not_err!(jws.encode_with_jwk(&private_keys.keys[index]));
+            let token = Compact::<Vec<u8>, Empty>::new_encoded(&not_err!(token.encoded()).to_string());
+
+            let biscuit = not_err!(token.decode_with_jwk(&public_keys.keys[index], algorithm));
+            assert_eq!(payload, *not_err!(biscuit.payload()));
+        }
+    }
+
+    #[test]
+    fn compact_jws_round_trip_hs256_with_jwk() {
+        let jwk = JWK::new_octect_key(b"secret", Empty {});
+        let payload = b"payload".to_vec();
+
+        let jws = Compact::<Vec<u8>, Empty>::new_decoded(From::from(RegisteredHeader {
+                                                                        algorithm: SignatureAlgorithm::HS256,
+                                                                        ..Default::default()
+                                                                    }),
+                                                         payload.clone());
+        let token = not_err!(jws.encode_with_jwk(&jwk));
+        // Same signature as with the equivalent `Secret`
+        let expected_token = not_err!(jws.encode(&Secret::bytes_from_str("secret")));
+        assert_eq!(token, expected_token);
+
+        let biscuit = not_err!(token.decode_with_jwk(&jwk, SignatureAlgorithm::HS256));
+        assert_eq!(payload, *not_err!(biscuit.payload()));
+    }
+
+    #[test]
+    fn compact_jws_decode_with_jwk_of_wrong_key_type() {
+        let public_keys = public_jwk_set();
+        let token = Compact::<Vec<u8>, Empty>::new_encoded("eyJhbGciOiJIUzI1NiJ9.cGF5bG9hZA.c2lnbmF0dXJl");
+
+        let result = token.decode_with_jwk(&public_keys.keys[1], SignatureAlgorithm::HS256);
+        assert_matches!(result, Err(Error::WrongKeyType { .. }));
+
+        // An EC key on the P-256 curve cannot be used with ES384
+        let result = token.decode_with_jwk(&public_keys.keys[0], SignatureAlgorithm::ES384);
+        assert_matches!(result, Err(Error::GenericError(_)));
+    }
+
+    #[test]
+    fn compact_jws_encode_with_incomplete_rsa_jwk() {
+        let public_keys = public_jwk_set();
+        let jws = Compact::<Vec<u8>, Empty>::new_decoded(From::from(RegisteredHeader {
+                                                                        algorithm: SignatureAlgorithm::RS256,
+                                                                        ..Default::default()
+                                                                    }),
+                                                         b"payload".to_vec());
+
+        let result = jws.encode_with_jwk(&public_keys.keys[1]);
+        assert_matches!(result, Err(Error::GenericError(_)));
+    }
+
+    /// Sign a `b"payload"` token with the RSA key from the private JWK Set fixture
+    fn rsa_signed_token(algorithm: SignatureAlgorithm, key_id: Option<&str>) -> Compact<Vec<u8>, Empty> {
+        let jws = Compact::<Vec<u8>, Empty>::new_decoded(From::from(RegisteredHeader {
+                                                                        algorithm: algorithm,
+                                                                        key_id: key_id.map(|id| id.to_string()),
+                                                                        ..Default::default()
+                                                                    }),
+                                                         b"payload".to_vec());
+        let token = not_err!(jws.encode_with_jwk(&private_jwk_set().keys[1]));
+        Compact::new_encoded(&not_err!(token.encoded()).to_string())
+    }
+
+    #[test]
+    fn compact_jws_decode_with_jwks() {
+        let token = rsa_signed_token(SignatureAlgorithm::RS256, Some("2011-04-29"));
+        let biscuit = not_err!(token.decode_with_jwks(&public_jwk_set()));
+        assert_eq!(b"payload".to_vec(), *not_err!(biscuit.payload()));
+        assert_eq!(not_err!(biscuit.header()).registered.key_id,
+                   Some("2011-04-29".to_string()));
+    }
+
+    #[test]
+    fn compact_jws_decode_with_jwks_without_key_id() {
+        // The EC key in the set is meant for encryption, so the RSA key is the only one that can be used
+        let token = rsa_signed_token(SignatureAlgorithm::RS256, None);
+        let biscuit = not_err!(token.decode_with_jwks(&public_jwk_set()));
+        assert_eq!(b"payload".to_vec(), *not_err!(biscuit.payload()));
+    }
+
+    #[test]
+    fn compact_jws_decode_with_jwks_key_not_found() {
+        let token = rsa_signed_token(SignatureAlgorithm::RS256, Some("unknown"));
+        let result = token.decode_with_jwks(&public_jwk_set());
+        let kid = assert_matches!(result, Err(Error::ValidationError(ValidationError::KeyNotFound(Some(kid)))), kid);
+        assert_eq!(kid, "unknown");
+    }
+
+    #[test]
+    fn compact_jws_decode_with_jwks_key_not_allowed() {
+        // The RSA key only allows `RS256`
+        let token = rsa_signed_token(SignatureAlgorithm::PS256, Some("2011-04-29"));
+        let result = token.decode_with_jwks(&public_jwk_set());
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::KeyNotAllowed(Some(_)))));
+
+        // The EC key is meant for encryption
+        let token = "eyJhbGciOiJFUzI1NiIsImtpZCI6IjEifQ.cGF5bG9hZA.c2lnbmF0dXJl";
+        let token = Compact::<Vec<u8>, Empty>::new_encoded(token);
+        let result = token.decode_with_jwks(&public_jwk_set());
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::KeyNotAllowed(Some(_)))));
+    }
+
+    #[test]
+    fn compact_jws_decode_with_jwks_ambiguous_key() {
+        let mut jwks = public_jwk_set();
+        let duplicate = jwks.keys[1].clone();
+        jwks.keys.push(duplicate);
+
+        let token = rsa_signed_token(SignatureAlgorithm::RS256, Some("2011-04-29"));
+        let result = token.decode_with_jwks(&jwks);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::AmbiguousKey(Some(_)))));
+
+        let token = rsa_signed_token(SignatureAlgorithm::RS256, None);
+        let result = token.decode_with_jwks(&jwks);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::AmbiguousKey(None))));
+    }
+
+    #[test]
+    fn compact_jws_decode_with_jwks_invalid_signature() {
+        let token = rsa_signed_token(SignatureAlgorithm::RS256, Some("2011-04-29"));
+        let mut encoded = not_err!(token.encoded()).to_string();
+        encoded.push_str("AAAA");
+
+        let token = Compact::<Vec<u8>, Empty>::new_encoded(&encoded);
+        let result = token.decode_with_jwks(&public_jwk_set());
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::InvalidSignature)));
+    }
+
+    fn hs256_resolved_key(secret: &str) -> ResolvedKey {
+        ResolvedKey {
+            key: VerificationKey::Secret(Secret::bytes_from_str(secret)),
+            algorithms: vec![SignatureAlgorithm::HS256],
+        }
+    }
+
+    fn hs256_signed_token(secret: &str, key_id: Option<&str>) -> Compact<Vec<u8>, Empty> {
+        let jws = Compact::<Vec<u8>, Empty>::new_decoded(From::from(RegisteredHeader {
+                                                                        algorithm: SignatureAlgorithm::HS256,
+                                                                        key_id: key_id.map(|id| id.to_string()),
+                                                                        ..Default::default()
+                                                                    }),
+                                                         b"payload".to_vec());
+        let token = not_err!(jws.encode(&Secret::bytes_from_str(secret)));
+        Compact::new_encoded(&not_err!(token.encoded()).to_string())
+    }
+
+    #[test]
+    fn compact_jws_decode_with_in_memory_resolver() {
+        let mut resolver = InMemoryKeyResolver::new();
+        resolver.insert("first", hs256_resolved_key("first secret"));
+        resolver.insert("second", hs256_resolved_key("second secret"));
+
+        for &(key_id, secret) in &[("first", "first secret"), ("second", "second secret")] {
+            let token = hs256_signed_token(secret, Some(key_id));
+            let biscuit = not_err!(token.decode_with_resolver(&resolver));
+            assert_eq!(b"payload".to_vec(), *not_err!(biscuit.payload()));
+        }
+
+        let token = hs256_signed_token("first secret", Some("second"));
+        let result = token.decode_with_resolver(&resolver);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::InvalidSignature)));
+
+        let token = hs256_signed_token("first secret", Some("third"));
+        let result = token.decode_with_resolver(&resolver);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::KeyNotFound(Some(_)))));
+
+        assert!(resolver.remove("first").is_some());
+        let token = hs256_signed_token("first secret", Some("first"));
+        let result = token.decode_with_resolver(&resolver);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::KeyNotFound(Some(_)))));
+    }
+
+    #[test]
+    fn compact_jws_decode_with_in_memory_resolver_default_key() {
+        let mut resolver = InMemoryKeyResolver::new();
+        let token = hs256_signed_token("secret", None);
+        let result = token.decode_with_resolver(&resolver);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::KeyNotFound(None))));
+
+        resolver.set_default_key(Some(hs256_resolved_key("secret")));
+        let biscuit = not_err!(token.decode_with_resolver(&resolver));
+        assert_eq!(b"payload".to_vec(), *not_err!(biscuit.payload()));
+    }
+
+    #[test]
+    fn compact_jws_decode_with_resolver_rejects_disallowed_algorithm() {
+        let mut resolver = InMemoryKeyResolver::new();
+        resolver.insert("key",
+                        ResolvedKey {
+                            key: VerificationKey::Secret(Secret::bytes_from_str("secret")),
+                            algorithms: vec![SignatureAlgorithm::HS384, SignatureAlgorithm::HS512],
+                        });
+
+        let token = hs256_signed_token("secret", Some("key"));
+        let result = token.decode_with_resolver(&resolver);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::InvalidSignature)));
+
+        // Signed with an allowed algorithm, but the header claims otherwise
+        let header: Header<Empty> = From::from(RegisteredHeader {
+                                                   algorithm: SignatureAlgorithm::HS256,
+                                                   key_id: Some("key".to_string()),
+                                                   ..Default::default()
+                                               });
+        let mut compact = ::Compact::with_capacity(3);
+        not_err!(compact.push(&header));
+        not_err!(compact.push(&b"payload".to_vec()));
+        let signature = not_err!(SignatureAlgorithm::HS384.sign(compact.encode().as_bytes(),
+                                                                &Secret::bytes_from_str("secret")));
+        not_err!(compact.push(&signature));
+
+        let token = Compact::<Vec<u8>, Empty>::Encoded(compact);
+        let result = token.decode_with_resolver(&resolver);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::WrongAlgorithmHeader)));
+    }
+
+    #[test]
+    fn compact_jws_decode_with_custom_resolver() {
+        /// Resolves the key ID into the JWK Set fixture index
+        struct IndexResolver(JWKSet<Empty>);
+
+        impl KeyResolver<Empty> for IndexResolver {
+            fn resolve(&self, header: &Header<Empty>) -> Result<ResolvedKey, Error> {
+                let index: usize = match header.registered.key_id {
+                    Some(ref key_id) => key_id.parse().map_err(|_| ValidationError::InvalidToken)?,
+                    None => Err(ValidationError::KeyNotFound(None))?,
+                };
+                let jwk = self.0.keys.get(index).ok_or(ValidationError::KeyNotFound(header.registered.key_id.clone()))?;
+                Ok(ResolvedKey {
+                       key: VerificationKey::JWK(jwk.clone()),
+                       algorithms: vec![SignatureAlgorithm::RS256],
+                   })
+            }
+        }
+
+        let resolver = IndexResolver(public_jwk_set());
+        let token = rsa_signed_token(SignatureAlgorithm::RS256, Some("1"));
+        let biscuit = not_err!(token.decode_with_resolver(&resolver));
+        assert_eq!(b"payload".to_vec(), *not_err!(biscuit.payload()));
+
+        let token = rsa_signed_token(SignatureAlgorithm::RS256, Some("0"));
+        let result = token.decode_with_resolver(&resolver);
+        assert_matches!(result, Err(Error::WrongKeyType { .. }));
+    }
+
+    #[test]
+    fn compact_jws_decode_with_jwk_set_resolver() {
+        let resolver: &KeyResolver<Empty> = &public_jwk_set();
+        let token = rsa_signed_token(SignatureAlgorithm::RS256, Some("2011-04-29"));
+        let biscuit = not_err!(token.decode_with_resolver(resolver));
+        assert_eq!(b"payload".to_vec(), *not_err!(biscuit.payload()));
+    }
+
+    #[test]
+    fn compact_jws_decode_with_allowed_algorithms() {
+        let rsa_public_key = not_err!(Secret::verification_key_from_jwk(&public_jwk_set().keys[1],
+                                                                        SignatureAlgorithm::RS256));
+        let hmac_secret = Secret::bytes_from_str("secret");
+        let allowed = [(SignatureAlgorithm::RS256, &rsa_public_key),
+                       (SignatureAlgorithm::PS256, &rsa_public_key),
+                       (SignatureAlgorithm::HS256, &hmac_secret)];
+
+        for algorithm in &[SignatureAlgorithm::RS256, SignatureAlgorithm::PS256] {
+            let token = rsa_signed_token(*algorithm, None);
+            let biscuit = not_err!(token.decode_with_allowed_algorithms(&allowed));
+            assert_eq!(b"payload".to_vec(), *not_err!(biscuit.payload()));
+        }
+
+        let token = hs256_signed_token("secret", None);
+        let biscuit = not_err!(token.decode_with_allowed_algorithms(&allowed));
+        assert_eq!(b"payload".to_vec(), *not_err!(biscuit.payload()));
+
+        let token = rsa_signed_token(SignatureAlgorithm::RS384, None);
+        let result = token.decode_with_allowed_algorithms(&allowed);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::WrongAlgorithmHeader)));
+    }
+
+    #[test]
+    fn compact_jws_decode_with_allowed_algorithms_rejects_rsa_key_as_hmac_secret() {
+        let rsa_public_key = not_err!(Secret::verification_key_from_jwk(&public_jwk_set().keys[1],
+                                                                        SignatureAlgorithm::RS256));
+        let token = hs256_signed_token("secret", None);
+
+        let result = token.decode_with_allowed_algorithms(&[(SignatureAlgorithm::HS256, &rsa_public_key)]);
+        assert_matches!(result, Err(Error::WrongKeyType { .. }));
+
+        // The same protection applies to keys returned by a resolver
+        let resolved_key = ResolvedKey {
+            key: VerificationKey::Secret(rsa_public_key.clone()),
+            algorithms: vec![SignatureAlgorithm::RS256, SignatureAlgorithm::HS256],
+        };
+        let result = token.decode_with_resolver(&resolved_key);
+        assert_matches!(result, Err(Error::WrongKeyType { .. }));
+    }
+
+    #[test]
+    fn compact_jws_decode_with_allowed_algorithms_rejects_hmac_secret_for_rsa() {
+        let hmac_secret = Secret::bytes_from_str("secret");
+        let token = rsa_signed_token(SignatureAlgorithm::RS256, None);
+
+        let result = token.decode_with_allowed_algorithms(&[(SignatureAlgorithm::RS256, &hmac_secret)]);
+        assert_matches!(result, Err(Error::WrongKeyType { .. }));
+    }
+
+    #[test]
+    fn compact_jws_decode_with_allowed_algorithms_rejects_duplicates() {
+        let first = Secret::bytes_from_str("first secret");
+        let second = Secret::bytes_from_str("second secret");
+        let token = hs256_signed_token("first secret", None);
+
+        let result = token.decode_with_allowed_algorithms(&[(SignatureAlgorithm::HS256, &first),
+                                                            (SignatureAlgorithm::HS256, &second)]);
+        assert_matches!(result, Err(Error::GenericError(_)));
+    }
+
+    /// Test case from [RFC 8037 Appendix A.5](https://tools.ietf.org/html/rfc8037#appendix-A.5)
+    #[test]
+    fn compact_jws_decode_eddsa() {
+        use data_encoding::base64url;
+
+        let token = "eyJhbGciOiJFZERTQSJ9.RXhhbXBsZSBvZiBFZDI1NTE5IHNpZ25pbmc.\
+                     hgyY0il_MGCjP0JzlnLWG1PPOt7-09PGcvMg3AIbQR6dWbhijcNR4ki4iylGjg5BhVsPt9g7sVvpAr_MuM0KAg";
+        let public_key = not_err!(base64url::decode_nopad("11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo".as_bytes()));
+
+        let token = Compact::<Vec<u8>, Empty>::new_encoded(token);
+        let biscuit = not_err!(token.into_decoded(&Secret::PublicKey(public_key), SignatureAlgorithm::EdDSA));
+        assert_eq!(b"Example of Ed25519 signing".to_vec(), *not_err!(biscuit.payload()));
+    }
+
+    /// Payload used in the examples of RFC 7520
+    const RFC7520_PAYLOAD: &str = "It\u{2019}s a dangerous business, Frodo, going out your door. You step onto the \
+                                   road, and if you don't keep your feet, there\u{2019}s no knowing where you might \
+                                   be swept off to.";
+
+    const RFC7520_ENCODED_PAYLOAD: &str = "SXTigJlzIGEgZGFuZ2Vyb3VzIGJ1c2luZXNzLCBGcm9kbywgZ29pbmcgb3V0IHlvdXIgZG9vci\
+                                           4gWW91IHN0ZXAgb250byB0aGUgcm9hZCwgYW5kIGlmIHlvdSBkb24ndCBrZWVwIHlvdXIgZm\
+                                           VldCwgdGhlcmXigJlzIG5vIGtub3dpbmcgd2hlcmUgeW91IG1pZ2h0IGJlIHN3ZXB0IG9mZi\
+                                           B0by4";
+
+    const RFC7520_RSA_KEY_ID: &str = "bilbo.baggins@hobbiton.example";
+    const RFC7520_HMAC_KEY_ID: &str = "018c0ae5-4d9b-471b-bfd6-eef314bc7037";
+
+    /// The symmetric key from RFC 7520 Section 3.5
+    fn rfc7520_hmac_secret() -> Secret {
+        Secret::Bytes(not_err!(base64url::decode_nopad(b"hJtXIZ2uSN5kbQfbtTNWbpdmhkV8FJG-Onbc6mxCcYg")))
+    }
+
+    /// Resolves the keys of RFC 7520 Sections 3.4 and 3.5 by their key IDs
+    fn rfc7520_resolver() -> InMemoryKeyResolver {
+        let mut resolver = InMemoryKeyResolver::new();
+        let rsa_public_key = not_err!(Secret::public_key_from_file("test/fixtures/rfc7520_rsa_public_key.der"));
+        resolver.insert(RFC7520_RSA_KEY_ID,
+                        ResolvedKey {
+                            key: VerificationKey::Secret(rsa_public_key),
+                            algorithms: vec![SignatureAlgorithm::RS256],
+                        });
+        resolver.insert(RFC7520_HMAC_KEY_ID,
+                        ResolvedKey {
+                            key: VerificationKey::Secret(rfc7520_hmac_secret()),
+                            algorithms: vec![SignatureAlgorithm::HS256],
+                        });
+        resolver
+    }
+
+    /// The General JWS JSON Serialization with multiple signatures from RFC 7520 Section 4.8.
+    ///
+    /// The ES512 signature of the example is left out, because ES512 is not supported.
+    fn rfc7520_multiple_signatures() -> serde_json::Value {
+        json!({
+            "payload": RFC7520_ENCODED_PAYLOAD,
+            "signatures": [
+                {
+                    "protected": "eyJhbGciOiJSUzI1NiJ9",
+                    "header": {
+                        "kid": "bilbo.baggins@hobbiton.example"
+                    },
+                    "signature": "MIsjqtVlOpa71KE-Mss8_Nq2YH4FGhiocsqrgi5NvyG53uoimic1tcMdSg-qptrzZc7CG6Svw2Y13TDIqHzTU\
+                                  rL_lR2ZFcryNFiHkSw129EghGpwkpxaTn_THJTCglNbADko1MZBCdwzJxwqZc-1RlpO2HibUYyXSwO97BSe\
+                                  0_evZKdjvvKSgsIqjytKSeAMbhMBdMma622_BG5t4sdbuCHtFjp9iJmkio47AIwqkZV1aIZsv33uPUqBBCX\
+                                  bYoQJwt7mxPftHmNlGoOSMxR_3thmXTCm4US-xiNOyhbm8afKK64jU6_TPtQHiJeQJxz9G3Tx-083B745_A\
+                                  fYOnlC9w"
+                },
+                {
+                    "protected": "eyJhbGciOiJIUzI1NiIsImtpZCI6IjAxOGMwYWU1LTRkOWItNDcxYi1iZmQ2LWVlZjMxNGJjNzAzNyJ9",
+                    "signature": "s0h6KThzkfBBBkLspW1h84VsJZFTsPPqMDA7g1Md7p0"
+                }
+            ]
+        })
+    }
+
+    fn rfc7520_headers() -> Vec<JsonHeader<Empty>> {
+        vec![JsonHeader::new(From::from(RegisteredHeader {
+                                            algorithm: SignatureAlgorithm::RS256,
+                                            media_type: None,
+                                            key_id: Some(RFC7520_RSA_KEY_ID.to_string()),
+                                            ..Default::default()
+                                        }),
+                             &["kid"]),
+             JsonHeader::from(Header::from(RegisteredHeader {
+                                               algorithm: SignatureAlgorithm::HS256,
+                                               media_type: None,
+                                               key_id: Some(RFC7520_HMAC_KEY_ID.to_string()),
+                                               ..Default::default()
+                                           }))]
+    }
+
+    #[test]
+    fn json_jws_sign_rfc7520_multiple_signatures() {
+        let rsa_key_pair = not_err!(Secret::rsa_keypair_from_file("test/fixtures/rfc7520_rsa_private_key.der"));
+        let hmac_secret = rfc7520_hmac_secret();
+
+        let jws = Json::<Vec<u8>, Empty>::new_decoded(RFC7520_PAYLOAD.as_bytes().to_vec(), rfc7520_headers());
+        let encoded = not_err!(jws.encode(&[&rsa_key_pair, &hmac_secret]));
+        assert_eq!(not_err!(serde_json::to_value(&encoded)), rfc7520_multiple_signatures());
+    }
+
+    #[test]
+    fn json_jws_verify_rfc7520_multiple_signatures() {
+        let jws: Json<Vec<u8>, Empty> = not_err!(serde_json::from_value(rfc7520_multiple_signatures()));
+        assert_matches!(jws, Json::Encoded(JsonSerialization::General { .. }));
+
+        for policy in &[VerificationPolicy::Any, VerificationPolicy::All] {
+            let decoded = not_err!(jws.decode_with_resolver(&rfc7520_resolver(), *policy));
+            assert_eq!(*not_err!(decoded.payload()), RFC7520_PAYLOAD.as_bytes().to_vec());
+            assert_eq!(not_err!(decoded.headers()), &rfc7520_headers()[..]);
+        }
+    }
+
+    #[test]
+    fn json_jws_verification_policy() {
+        let mut value = rfc7520_multiple_signatures();
+        value["signatures"][1]["signature"] = json!("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA");
+        let jws: Json<Vec<u8>, Empty> = not_err!(serde_json::from_value(value));
+
+        let decoded = not_err!(jws.decode_with_resolver(&rfc7520_resolver(), VerificationPolicy::Any));
+        assert_eq!(not_err!(decoded.headers()), &rfc7520_headers()[..1]);
+
+        let result = jws.decode_with_resolver(&rfc7520_resolver(), VerificationPolicy::All);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::InvalidSignature)));
+
+        // None of the signatures can be verified
+        let result = jws.decode_with_resolver(&InMemoryKeyResolver::new(), VerificationPolicy::Any);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::KeyNotFound(_))));
+    }
+
+    #[test]
+    fn json_jws_flattened_rfc7520() {
+        // RFC 7520 Section 4.1.3
+        let expected_json = json!({
+            "payload": RFC7520_ENCODED_PAYLOAD,
+            "protected": "eyJhbGciOiJSUzI1NiIsImtpZCI6ImJpbGJvLmJhZ2dpbnNAaG9iYml0b24uZXhhbXBsZSJ9",
+            "signature": "MRjdkly7_-oTPTS3AXP41iQIGKa80A0ZmTuV5MEaHoxnW2e5CZ5NlKtainoFmKZopdHM1O2U4mwzJdQx996ivp83xug\
+                          lII7PNDi84wnB-BDkoBwA78185hX-Es4JIwmDLJK3lfWRa-XtL0RnltuYv746iYTh_qHRD68BNt1uSNCrUCTJDt5aAE\
+                          6x8wW1Kt9eRo4QPocSadnHXFxnt8Is9UzpERV0ePPQdLuW3IS_de3xyIrDaLGdjluPxUAhb6L2aXic1U12podGU0KLU\
+                          QSE_oI-ZnmKJ3F4uOZDnd6QZWJushZ41Axf_fcIe8u9ipH84ogoree7vjbU5y18kDquDg"
+        });
+        let header = Header::from(RegisteredHeader {
+                                      algorithm: SignatureAlgorithm::RS256,
+                                      media_type: None,
+                                      key_id: Some(RFC7520_RSA_KEY_ID.to_string()),
+                                      ..Default::default()
+                                  });
+
+        let rsa_key_pair = not_err!(Secret::rsa_keypair_from_file("test/fixtures/rfc7520_rsa_private_key.der"));
+        let jws = Json::<Vec<u8>, Empty>::new_decoded(RFC7520_PAYLOAD.as_bytes().to_vec(),
+                                                      vec![From::from(header.clone())]);
+        let encoded = not_err!(jws.encode_flattened(&rsa_key_pair));
+        assert_eq!(not_err!(serde_json::to_value(&encoded)), expected_json);
+
+        let jws: Json<Vec<u8>, Empty> = not_err!(serde_json::from_value(expected_json));
+        assert_matches!(jws, Json::Encoded(JsonSerialization::Flattened { .. }));
+        let decoded = not_err!(jws.decode_with_resolver(&rfc7520_resolver(), VerificationPolicy::All));
+        assert_eq!(*not_err!(decoded.payload()), RFC7520_PAYLOAD.as_bytes().to_vec());
+        assert_eq!(not_err!(decoded.headers()), &[From::from(header)][..]);
+    }
+
+    #[test]
+    fn json_jws_encode_requires_one_secret_per_signature() {
+        let hmac_secret = rfc7520_hmac_secret();
+        let jws = Json::<Vec<u8>, Empty>::new_decoded(RFC7520_PAYLOAD.as_bytes().to_vec(), rfc7520_headers());
+
+        assert_matches!(jws.encode(&[&hmac_secret]), Err(Error::GenericError(_)));
+        assert_matches!(jws.encode_flattened(&hmac_secret), Err(Error::GenericError(_)));
+    }
+
+    #[test]
+    fn json_jws_rejects_overlapping_headers() {
+        let mut value = rfc7520_multiple_signatures();
+        value["signatures"][1]["header"] = json!({ "alg": "HS256" });
+        let jws: Json<Vec<u8>, Empty> = not_err!(serde_json::from_value(value));
+
+        let result = jws.decode_with_resolver(&rfc7520_resolver(), VerificationPolicy::Any);
+        assert_eq!(not_err!(result.map(|decoded| decoded.headers().unwrap().len())), 1);
+
+        let result = jws.decode_with_resolver(&rfc7520_resolver(), VerificationPolicy::All);
+        assert_matches!(result, Err(Error::GenericError(_)));
+    }
+
+    /// The HMAC key used in the examples of RFC 7797 Section 4
+    fn rfc7797_secret() -> Secret {
+        let key = "AyM1SysPpbyDfgZld3umj1qzKObwVMkoqQ-EstJQLr_T-1qS0gZH75aKtMN3Yj0iPS4hcgUuTwjAzZr1Z9CAow";
+        Secret::Bytes(not_err!(base64url::decode_nopad(key.as_bytes())))
+    }
+
+    fn unencoded_payload_header() -> Header<Empty> {
+        From::from(RegisteredHeader {
+                       algorithm: SignatureAlgorithm::HS256,
+                       media_type: None,
+                       base64url_encode_payload: Some(false),
+                       critical: Some(vec!["b64".to_string()]),
+                       ..Default::default()
+                   })
+    }
+
+    #[test]
+    fn compact_jws_rfc7797_encoded_payload() {
+        // RFC 7797 Section 4.1
+        let expected_token = "eyJhbGciOiJIUzI1NiJ9.JC4wMg.5mvfOroL-g7HyqJoozehmsaqmvTYGEq5jTI1gVvoEoQ";
+        let jws = Compact::<Vec<u8>, Empty>::new_decoded(From::from(RegisteredHeader {
+                                                                        algorithm: SignatureAlgorithm::HS256,
+                                                                        media_type: None,
+                                                                        ..Default::default()
+                                                                    }),
+                                                         b"$.02".to_vec());
+        let token = not_err!(jws.encode(&rfc7797_secret()));
+        assert_eq!(expected_token, not_err!(token.encoded()).to_string());
+
+        let token = Compact::<Vec<u8>, Empty>::new_encoded(expected_token);
+        let biscuit = not_err!(token.decode(&rfc7797_secret(), SignatureAlgorithm::HS256));
+        assert_eq!(b"$.02".to_vec(), *not_err!(biscuit.payload()));
+    }
+
+    #[test]
+    fn json_jws_rfc7797_unencoded_payload() {
+        // RFC 7797 Section 4.2
+        let expected_json = json!({
+            "protected": "eyJhbGciOiJIUzI1NiIsImI2NCI6ZmFsc2UsImNyaXQiOlsiYjY0Il19",
+            "payload": "$.02",
+            "signature": "A5dxf2s96_n5FLueVuW1Z_vh161FwXZC4YLPff6dmDY"
+        });
+
+        let jws = Json::<Vec<u8>, Empty>::new_decoded(b"$.02".to_vec(), vec![From::from(unencoded_payload_header())]);
+        let encoded = not_err!(jws.encode_flattened(&rfc7797_secret()));
+        assert_eq!(not_err!(serde_json::to_value(&encoded)), expected_json);
+
+        let jws: Json<Vec<u8>, Empty> = not_err!(serde_json::from_value(expected_json));
+        let resolved_key = ResolvedKey {
+            key: VerificationKey::Secret(rfc7797_secret()),
+            algorithms: vec![SignatureAlgorithm::HS256],
+        };
+        let decoded = not_err!(jws.decode_with_resolver(&resolved_key, VerificationPolicy::All));
+        assert_eq!(b"$.02".to_vec(), *not_err!(decoded.payload()));
+        assert_eq!(not_err!(decoded.headers()), &[From::from(unencoded_payload_header())][..]);
+    }
+
+    #[test]
+    fn compact_jws_round_trip_unencoded_payload() {
+        let expected_token = "eyJhbGciOiJIUzI1NiIsImI2NCI6ZmFsc2UsImNyaXQiOlsiYjY0Il19.$02.\
+                              uB970NMwI0DGAK72LfbzudKpWHiz3tNXh6BzgYICrPA";
+        let jws = Compact::<Vec<u8>, Empty>::new_decoded(unencoded_payload_header(), b"$02".to_vec());
+        let token = not_err!(jws.encode(&rfc7797_secret()));
+        assert_eq!(expected_token, not_err!(token.encoded()).to_string());
+
+        let token = Compact::<Vec<u8>, Empty>::new_encoded(expected_token);
+        let biscuit = not_err!(token.decode(&rfc7797_secret(), SignatureAlgorithm::HS256));
+        assert_eq!(b"$02".to_vec(), *not_err!(biscuit.payload()));
+        assert_eq!(*not_err!(biscuit.header()), unencoded_payload_header());
+    }
+
+    #[test]
+    fn compact_jws_unencoded_payload_with_periods_is_rejected() {
+        let jws = Compact::<Vec<u8>, Empty>::new_decoded(unencoded_payload_header(), b"$.02".to_vec());
+        assert_matches!(jws.encode(&rfc7797_secret()), Err(Error::GenericError(_)));
+    }
+
+    #[test]
+    fn compact_jws_unencoded_payload_requires_critical() {
+        let header = Header::from(RegisteredHeader {
+                                      algorithm: SignatureAlgorithm::HS256,
+                                      media_type: None,
+                                      base64url_encode_payload: Some(false),
+                                      ..Default::default()
+                                  });
+        let jws = Compact::<Vec<u8>, Empty>::new_decoded(header.clone(), b"$02".to_vec());
+        let result = jws.encode(&rfc7797_secret());
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::MissingRequired(_))));
+
+        // {"alg":"HS256","b64":false}
+        let encoded_header = not_err!(header.to_base64());
+        let signing_input = format!("{}.$02", encoded_header.str());
+        let signature = not_err!(SignatureAlgorithm::HS256.sign(signing_input.as_bytes(), &rfc7797_secret()));
+        let token = format!("{}.{}", signing_input, base64url::encode_nopad(&signature));
+
+        let token = Compact::<Vec<u8>, Empty>::new_encoded(&token);
+        let result = token.decode(&rfc7797_secret(), SignatureAlgorithm::HS256);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::MissingRequired(_))));
+    }
+
+    #[test]
+    fn json_jws_unencoded_payload_must_be_protected() {
+        let jws = Json::<Vec<u8>, Empty>::new_decoded(b"$.02".to_vec(),
+                                                      vec![JsonHeader::new(unencoded_payload_header(), &["b64"])]);
+        assert_matches!(jws.encode(&[&rfc7797_secret()]), Err(Error::GenericError(_)));
+    }
+
+    #[test]
+    fn compact_jws_detached_rfc7797() {
+        // RFC 7797 Sections 4.1 and 4.2, with the payload detached
+        let encoded_payload_header = Header::from(RegisteredHeader {
+                                                      algorithm: SignatureAlgorithm::HS256,
+                                                      media_type: None,
+                                                      ..Default::default()
+                                                  });
+        let examples = [(encoded_payload_header,
+                         "eyJhbGciOiJIUzI1NiJ9..5mvfOroL-g7HyqJoozehmsaqmvTYGEq5jTI1gVvoEoQ"),
+                        (unencoded_payload_header(),
+                         "eyJhbGciOiJIUzI1NiIsImI2NCI6ZmFsc2UsImNyaXQiOlsiYjY0Il19..\
+                          A5dxf2s96_n5FLueVuW1Z_vh161FwXZC4YLPff6dmDY")];
+
+        for &(ref header, expected_token) in &examples {
+            let jws = Compact::<Vec<u8>, Empty>::new_decoded(header.clone(), b"$.02".to_vec());
+            let token = not_err!(jws.encode_detached(&rfc7797_secret()));
+            assert_eq!(expected_token, not_err!(token.encoded()).to_string());
+
+            let token = Compact::<Vec<u8>, Empty>::new_encoded(expected_token);
+            let biscuit = not_err!(token.decode_detached(b"$.02", &rfc7797_secret(), SignatureAlgorithm::HS256));
+            assert_eq!(b"$.02".to_vec(), *not_err!(biscuit.payload()));
+            assert_eq!(not_err!(biscuit.header()), header);
+
+            let result = token.decode_detached(b"$.03", &rfc7797_secret(), SignatureAlgorithm::HS256);
+            assert_matches!(result, Err(Error::ValidationError(ValidationError::InvalidSignature)));
+        }
+    }
+
+    #[test]
+    fn compact_jws_detached_requires_empty_payload_part() {
+        let token = Compact::<Vec<u8>, Empty>::new_encoded("eyJhbGciOiJIUzI1NiJ9.JC4wMg.\
+                                                            5mvfOroL-g7HyqJoozehmsaqmvTYGEq5jTI1gVvoEoQ");
+        let result = token.decode_detached(b"$.02", &rfc7797_secret(), SignatureAlgorithm::HS256);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::InvalidToken)));
+    }
+
+    #[test]
+    fn compact_jws_detached_claims_with_resolver() {
+        let claims = ClaimsSet::<Empty> {
+            registered: RegisteredClaims {
+                issuer: Some(not_err!(FromStr::from_str("https://www.acme.com"))),
+                ..Default::default()
+            },
+            private: Empty {},
+        };
+        let jws = Compact::new_decoded(From::from(RegisteredHeader {
+                                                      algorithm: SignatureAlgorithm::HS256,
+                                                      key_id: Some("first".to_string()),
+                                                      ..Default::default()
+                                                  }),
+                                       claims.clone());
+        let token = not_err!(jws.encode_detached(&Secret::bytes_from_str("first secret")));
+        assert!(not_err!(token.encoded()).parts[1].is_empty());
+
+        let mut resolver = InMemoryKeyResolver::new();
+        resolver.insert("first", hs256_resolved_key("first secret"));
+        let payload = not_err!(claims.to_bytes());
+        let biscuit = not_err!(token.decode_detached_with_resolver(&payload, &resolver));
+        assert_eq!(*not_err!(biscuit.payload()), claims);
+    }
+
+    /// Sign a compact JWS with HS256 and the RFC 7797 key, using the header as-is
+    fn hs256_token_with_header(header: &str) -> Compact<Vec<u8>, Empty> {
+        let signing_input = format!("{}.cGF5bG9hZA", base64url::encode_nopad(header.as_bytes()));
+        let signature = not_err!(SignatureAlgorithm::HS256.sign(signing_input.as_bytes(), &rfc7797_secret()));
+        Compact::new_encoded(&format!("{}.{}", signing_input, base64url::encode_nopad(&signature)))
+    }
+
+    #[test]
+    fn compact_jws_decode_with_understood_critical_header() {
+        let token = hs256_token_with_header(r#"{"alg":"HS256","http://example.com/jws-extension":true,
+                                                "crit":["http://example.com/jws-extension"]}"#);
+        let result = token.decode(&rfc7797_secret(), SignatureAlgorithm::HS256);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::InvalidCriticalHeader(_))));
+
+        let resolved_key = ResolvedKey {
+            key: VerificationKey::Secret(rfc7797_secret()),
+            algorithms: vec![SignatureAlgorithm::HS256],
+        };
+        let options = DecodeOptions { understood_critical_headers: &["http://example.com/jws-extension"] };
+        let biscuit = not_err!(token.decode_with_options(&resolved_key, options));
+        assert_eq!(b"payload".to_vec(), *not_err!(biscuit.payload()));
+    }
+
+    #[test]
+    fn compact_jws_decode_with_invalid_critical_header() {
+        let headers = [r#"{"alg":"HS256","crit":[]}"#,
+                       r#"{"alg":"HS256","kid":"key","crit":["kid"]}"#,
+                       r#"{"alg":"HS256","crit":["b64"]}"#,
+                       r#"{"alg":"HS256","http://example.com/unknown":true,"crit":["http://example.com/unknown"]}"#];
+
+        for header in &headers {
+            let token = hs256_token_with_header(header);
+            let result = token.decode(&rfc7797_secret(), SignatureAlgorithm::HS256);
+            assert_matches!(result, Err(Error::ValidationError(ValidationError::InvalidCriticalHeader(_))));
+        }
+    }
+
+    #[test]
+    fn json_jws_critical_header_must_be_protected() {
+        let mut value = rfc7520_multiple_signatures();
+        value["signatures"][0]["header"]["crit"] = json!(["b64"]);
+        let jws: Json<Vec<u8>, Empty> = not_err!(serde_json::from_value(value));
+
+        let result = jws.decode_with_resolver(&rfc7520_resolver(), VerificationPolicy::All);
+        assert_matches!(result, Err(Error::ValidationError(ValidationError::InvalidCriticalHeader(_))));
+
+        let jws = Json::<Vec<u8>, Empty>::new_decoded(b"$.02".to_vec(),
+                                                      vec![JsonHeader::new(unencoded_payload_header(), &["crit"])]);
+        assert_matches!(jws.encode(&[&rfc7797_secret()]), Err(Error::GenericError(_)));
+    }
+
     #[test]
     fn header_serialization_round_trip_no_optional() {
         let expected = RegisteredHeader::default();
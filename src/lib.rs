@@ -49,18 +49,34 @@
 #![deny(missing_docs)]
 #![doc(test(attr(allow(unused_variables), deny(warnings))))]
 
+extern crate aes;
+#[cfg(feature = "aes192")]
+extern crate aes_gcm;
+extern crate cbc;
 extern crate chrono;
 extern crate data_encoding;
+extern crate flate2;
 #[macro_use]
 extern crate lazy_static;
 extern crate num;
+extern crate p256;
+extern crate p384;
+extern crate pbkdf2;
+extern crate rand;
 extern crate ring;
+extern crate rsa;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+#[cfg_attr(test, macro_use)]
 extern crate serde_json;
+extern crate sha1;
+extern crate sha2;
+#[cfg(feature = "rsa-decryption")]
+extern crate subtle;
 extern crate untrusted;
 extern crate url;
+extern crate x25519_dalek;
 
 #[cfg(test)]
 extern crate serde_test;
@@ -88,6 +104,9 @@ mod serde_custom;
 #[macro_use]
 mod macros;
 
+mod aes_key_wrap;
+mod critical;
+mod der;
 pub mod errors;
 pub mod jwa;
 pub mod jws;
@@ -18,6 +18,15 @@ macro_rules! assert_matches {
     })
 }
 
+/// Decodes a hexadecimal test vector, ignoring any whitespace used to lay it out
+pub fn hex(string: &str) -> Vec<u8> {
+    let string: String = string.split_whitespace().collect();
+    (0..string.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&string[i..i + 2], 16).unwrap())
+        .collect()
+}
+
 /// Tests that `value` can be serialized to JSON, and then back to type `T` and that the deserialized type `T`
 /// is equal to the provided `value`.
 /// If `expected_json` is provided, it will be deserialized to `T` and checked for equality with `value`.